@@ -0,0 +1,288 @@
+//! Minimal DER encoder and decoder for the structures the CA produces.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+pub(crate) const ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+pub(crate) const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+pub(crate) const SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+
+pub(crate) const ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+pub(crate) const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+pub(crate) const SUBJECT_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x0e];
+pub(crate) const KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+pub(crate) const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+pub(crate) const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+pub(crate) const CRL_NUMBER: &[u8] = &[0x55, 0x1d, 0x14];
+pub(crate) const AUTHORITY_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x23];
+pub(crate) const EXT_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+
+pub(crate) const SERVER_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+pub(crate) const CLIENT_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+
+pub(crate) fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    match content.len() {
+        len @ 0..=0x7f => der.push(len as u8),
+        len => {
+            let bytes = len.to_be_bytes();
+            let skip = bytes.iter().take_while(|x| **x == 0).count();
+            der.push(0x80 | (bytes.len() - skip) as u8);
+            der.extend_from_slice(&bytes[skip..]);
+        }
+    }
+    der.extend_from_slice(content);
+    der
+}
+
+pub(crate) fn sequence<T: AsRef<[u8]>>(items: impl IntoIterator<Item = T>) -> Vec<u8> {
+    constructed(0x30, items)
+}
+
+pub(crate) fn set<T: AsRef<[u8]>>(items: impl IntoIterator<Item = T>) -> Vec<u8> {
+    constructed(0x31, items)
+}
+
+/// Context-specific constructed tag `[n]`.
+pub(crate) fn explicit<T: AsRef<[u8]>>(n: u8, items: impl IntoIterator<Item = T>) -> Vec<u8> {
+    constructed(0xa0 | n, items)
+}
+
+fn constructed<T: AsRef<[u8]>>(tag: u8, items: impl IntoIterator<Item = T>) -> Vec<u8> {
+    let content = items.into_iter().fold(Vec::new(), |mut acc, x| {
+        acc.extend_from_slice(x.as_ref());
+        acc
+    });
+
+    tlv(tag, &content)
+}
+
+pub(crate) fn boolean(value: bool) -> Vec<u8> {
+    tlv(0x01, &[if value { 0xff } else { 0x00 }])
+}
+
+pub(crate) fn integer(value: u64) -> Vec<u8> {
+    unsigned_integer(&value.to_be_bytes())
+}
+
+/// Encodes big-endian `bytes` as a non-negative `INTEGER`.
+pub(crate) fn unsigned_integer(bytes: &[u8]) -> Vec<u8> {
+    let bytes = &bytes[bytes.iter().take_while(|x| **x == 0).count()..];
+    let mut content = Vec::with_capacity(bytes.len() + 1);
+    if bytes.first().is_none_or(|x| x & 0x80 != 0) {
+        content.push(0);
+    }
+    content.extend_from_slice(bytes);
+
+    tlv(0x02, &content)
+}
+
+pub(crate) fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    let mut content = vec![0];
+    content.extend_from_slice(bytes);
+    tlv(0x03, &content)
+}
+
+/// Encodes a named bit list, e.g. `KeyUsage`, from the bits set in `flags`, bit 0 first.
+pub(crate) fn named_bits(flags: u8) -> Vec<u8> {
+    tlv(0x03, &[flags.trailing_zeros() as u8 % 8, flags])
+}
+
+pub(crate) fn octet_string(bytes: &[u8]) -> Vec<u8> {
+    tlv(0x04, bytes)
+}
+
+pub(crate) fn null() -> Vec<u8> {
+    tlv(0x05, &[])
+}
+
+pub(crate) fn oid(oid: &[u8]) -> Vec<u8> {
+    tlv(0x06, oid)
+}
+
+pub(crate) fn utf8_string(value: &str) -> Vec<u8> {
+    tlv(0x0c, value.as_bytes())
+}
+
+/// Encodes an X.509 `Time`, as `UTCTime` until 2049 as required by RFC 5280.
+///
+/// Times before 1970 are encoded as 1970.
+pub(crate) fn time(time: SystemTime) -> Vec<u8> {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    let rest = format!(
+        "{month:02}{day:02}{:02}{:02}{:02}Z",
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if year < 2050 {
+        tlv(0x17, format!("{:02}{rest}", year % 100).as_bytes())
+    } else {
+        tlv(0x18, format!("{year:04}{rest}").as_bytes())
+    }
+}
+
+/// Reads the leading TLV of `der`, returning the tag, the content and the remaining bytes.
+pub(crate) fn read_tlv(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rem) = der.split_first()?;
+    let (&len, rem) = rem.split_first()?;
+    let (len, rem) = match len {
+        0..=0x7f => (usize::from(len), rem),
+        0x81..=0x84 => {
+            let (bytes, rem) = rem.split_at_checked(usize::from(len & 0x7f))?;
+            let len = bytes.iter().fold(0, |acc, x| acc << 8 | usize::from(*x));
+            (len, rem)
+        }
+        _ => return None,
+    };
+    let (content, rem) = rem.split_at_checked(len)?;
+
+    Some((tag, content, rem))
+}
+
+/// Returns the fields of the `tbsCertificate` of a certificate, from the `serialNumber` on.
+fn tbs_fields(cert: &[u8]) -> Option<&[u8]> {
+    let (0x30, cert, _) = read_tlv(cert)? else {
+        return None;
+    };
+    let (0x30, tbs_certificate, _) = read_tlv(cert)? else {
+        return None;
+    };
+
+    match read_tlv(tbs_certificate)? {
+        (0xa0, _, rem) => Some(rem),
+        _ => Some(tbs_certificate),
+    }
+}
+
+/// Returns the content of the `serialNumber` of a certificate.
+pub(crate) fn serial_number(cert: &[u8]) -> Option<&[u8]> {
+    let (0x02, serial_number, _) = read_tlv(tbs_fields(cert)?)? else {
+        return None;
+    };
+
+    Some(serial_number)
+}
+
+/// Returns the `n`-th field of the `tbsCertificate` of a certificate, counted from the
+/// `serialNumber`, with its tag and length, if it is a `SEQUENCE`.
+fn tbs_field(cert: &[u8], n: usize) -> Option<&[u8]> {
+    let mut fields = tbs_fields(cert)?;
+    for _ in 0..n {
+        (_, _, fields) = read_tlv(fields)?;
+    }
+    let (0x30, _, rem) = read_tlv(fields)? else {
+        return None;
+    };
+
+    Some(&fields[..fields.len() - rem.len()])
+}
+
+/// Returns the DER-encoded `subject` of a certificate.
+pub(crate) fn subject(cert: &[u8]) -> Option<&[u8]> {
+    // after serialNumber, signature, issuer and validity
+    tbs_field(cert, 4)
+}
+
+/// Returns the DER-encoded `subjectPublicKeyInfo` of a certificate.
+pub(crate) fn spki(cert: &[u8]) -> Option<&[u8]> {
+    tbs_field(cert, 5)
+}
+
+/// Returns the `notAfter` of a certificate.
+pub(crate) fn not_after(cert: &[u8]) -> Option<SystemTime> {
+    let (_, validity, _) = read_tlv(tbs_field(cert, 3)?)?;
+    let (_, _, rem) = read_tlv(validity)?;
+    let (tag, not_after, _) = read_tlv(rem)?;
+
+    parse_time(tag, not_after)
+}
+
+/// Parses an X.509 `Time`, either a `UTCTime` or a `GeneralizedTime` in UTC.
+fn parse_time(tag: u8, time: &[u8]) -> Option<SystemTime> {
+    let time = str::from_utf8(time).ok()?.strip_suffix('Z')?;
+    let (year, rest) = match tag {
+        0x17 => {
+            let (year, rest) = time.split_at_checked(2)?;
+            let year = year.parse::<u64>().ok()?;
+            (if year < 50 { 2000 + year } else { 1900 + year }, rest)
+        }
+        0x18 => {
+            let (year, rest) = time.split_at_checked(4)?;
+            (year.parse().ok()?, rest)
+        }
+        _ => return None,
+    };
+    if rest.len() != 10 || !rest.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let field = |i: usize| rest[i..i + 2].parse::<u64>().ok();
+    let (month, day) = (field(0)?, field(2)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // days from civil, http://howardhinnant.github.io/date_algorithms.html
+    let year = year.checked_sub(u64::from(month <= 2))?;
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day.checked_sub(1)?;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    let seconds = days * 86_400 + field(4)? * 3_600 + field(6)? * 60 + field(8)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time() {
+        let time_at = |seconds| time(UNIX_EPOCH + Duration::from_secs(seconds));
+
+        // 2024-11-06T10:40:26Z
+        assert_eq!(time_at(1_730_889_626)[2..], *b"241106104026Z");
+        // 2050-01-01T00:00:00Z
+        assert_eq!(time_at(2_524_608_000)[2..], *b"20500101000000Z");
+    }
+
+    #[test]
+    fn test_parse_time() {
+        for seconds in [0, 951_782_400, 1_730_889_626, 2_524_608_000, 4_107_542_400] {
+            let time = time(UNIX_EPOCH + Duration::from_secs(seconds));
+            let (tag, content, _) = read_tlv(&time).unwrap();
+            assert_eq!(
+                parse_time(tag, content),
+                Some(UNIX_EPOCH + Duration::from_secs(seconds))
+            );
+        }
+        assert_eq!(parse_time(0x17, b"241306104026Z"), None);
+    }
+
+    #[test]
+    fn test_integer() {
+        assert_eq!(integer(0), [0x02, 0x01, 0x00]);
+        assert_eq!(integer(0x80), [0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(named_bits(0x06), [0x03, 0x02, 0x01, 0x06]);
+    }
+}
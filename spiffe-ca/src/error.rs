@@ -0,0 +1,26 @@
+use spiffe::{SpiffeError, TokenError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CaError {
+    #[error("failed to generate a key")]
+    KeyGeneration,
+
+    #[error("invalid private key")]
+    InvalidKey,
+
+    #[error("invalid certificate")]
+    InvalidCertificate,
+
+    #[error("the private key does not match the CA certificate")]
+    KeyMismatch,
+
+    #[error("the CA certificate has expired")]
+    Expired,
+
+    #[error("invalid JWT signing key: {0}")]
+    JwtKey(#[from] TokenError),
+
+    #[error("failed to issue an SVID: {0}")]
+    Svid(#[from] SpiffeError),
+}
@@ -0,0 +1,138 @@
+use ed25519_dalek::Signer as _;
+use p256::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey};
+use rand_core::{OsRng, RngCore};
+use rsa::{RsaPrivateKey, pkcs1v15, signature::SignatureEncoding};
+use rustls_pki_types::PrivatePkcs8KeyDer;
+use sha2::{Digest, Sha256};
+
+use crate::{CaError, der};
+
+/// Key algorithm of an issued certificate.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum KeyType {
+    #[default]
+    P256,
+    P384,
+    Ed25519,
+    /// Slow to generate in debug builds.
+    Rsa2048,
+}
+
+enum Signer {
+    P256(p256::ecdsa::SigningKey),
+    P384(p384::ecdsa::SigningKey),
+    Ed25519(ed25519_dalek::SigningKey),
+    Rsa(Box<pkcs1v15::SigningKey<Sha256>>),
+}
+
+/// Signing key of a CA or key of an issued certificate, along with its encodings.
+pub(crate) struct KeyPair {
+    signer: Signer,
+    spki: Vec<u8>,
+    pkcs8: PrivatePkcs8KeyDer<'static>,
+}
+
+impl KeyPair {
+    pub(crate) fn generate(key_type: KeyType) -> Result<Self, CaError> {
+        let signer = match key_type {
+            KeyType::P256 => Signer::P256(p256::ecdsa::SigningKey::random(&mut OsRng)),
+            KeyType::P384 => Signer::P384(p384::ecdsa::SigningKey::random(&mut OsRng)),
+            KeyType::Ed25519 => {
+                let mut secret = [0; 32];
+                OsRng.fill_bytes(&mut secret);
+                Signer::Ed25519(ed25519_dalek::SigningKey::from_bytes(&secret))
+            }
+            KeyType::Rsa2048 => {
+                let key =
+                    RsaPrivateKey::new(&mut OsRng, 2048).map_err(|_| CaError::KeyGeneration)?;
+                Signer::Rsa(Box::new(pkcs1v15::SigningKey::new(key)))
+            }
+        };
+
+        Self::new(signer)
+    }
+
+    /// Parses a key of any [`KeyType`] from its PKCS#8 encoding.
+    pub(crate) fn from_pkcs8(der: &[u8]) -> Result<Self, CaError> {
+        let signer = if let Ok(key) = p256::SecretKey::from_pkcs8_der(der) {
+            Signer::P256(key.into())
+        } else if let Ok(key) = p384::SecretKey::from_pkcs8_der(der) {
+            Signer::P384(key.into())
+        } else if let Ok(key) = ed25519_dalek::SigningKey::from_pkcs8_der(der) {
+            Signer::Ed25519(key)
+        } else {
+            let key = RsaPrivateKey::from_pkcs8_der(der).map_err(|_| CaError::InvalidKey)?;
+            Signer::Rsa(Box::new(pkcs1v15::SigningKey::new(key)))
+        };
+
+        Self::new(signer)
+    }
+
+    fn new(signer: Signer) -> Result<Self, CaError> {
+        let spki = match &signer {
+            Signer::P256(key) => p256::PublicKey::from(key.verifying_key()).to_public_key_der(),
+            Signer::P384(key) => p384::PublicKey::from(key.verifying_key()).to_public_key_der(),
+            Signer::Ed25519(key) => key.verifying_key().to_public_key_der(),
+            Signer::Rsa(key) => key.as_ref().as_ref().to_public_key().to_public_key_der(),
+        };
+        let pkcs8 = match &signer {
+            Signer::P256(key) => p256::SecretKey::from(key).to_pkcs8_der(),
+            Signer::P384(key) => p384::SecretKey::from(key).to_pkcs8_der(),
+            Signer::Ed25519(key) => key.to_pkcs8_der(),
+            Signer::Rsa(key) => key.as_ref().as_ref().to_pkcs8_der(),
+        };
+
+        Ok(Self {
+            spki: spki.map_err(|_| CaError::InvalidKey)?.into_vec(),
+            pkcs8: pkcs8
+                .map_err(|_| CaError::InvalidKey)?
+                .as_bytes()
+                .to_vec()
+                .into(),
+            signer,
+        })
+    }
+
+    /// Returns the DER-encoded `SubjectPublicKeyInfo`.
+    #[inline]
+    pub(crate) fn spki(&self) -> &[u8] {
+        &self.spki
+    }
+
+    /// Returns the key identifier, the leftmost 160 bits of the SHA-256 hash of the
+    /// `SubjectPublicKeyInfo`.
+    pub(crate) fn key_id(&self) -> Vec<u8> {
+        Sha256::digest(&self.spki)[..20].to_vec()
+    }
+
+    #[inline]
+    pub(crate) fn pkcs8(&self) -> &PrivatePkcs8KeyDer<'static> {
+        &self.pkcs8
+    }
+
+    /// Returns the DER-encoded `AlgorithmIdentifier` of the signatures.
+    pub(crate) fn signature_algorithm(&self) -> Vec<u8> {
+        match self.signer {
+            Signer::P256(_) => der::sequence([der::oid(der::ECDSA_WITH_SHA256)]),
+            Signer::P384(_) => der::sequence([der::oid(der::ECDSA_WITH_SHA384)]),
+            Signer::Ed25519(_) => der::sequence([der::oid(der::ED25519)]),
+            Signer::Rsa(_) => der::sequence([der::oid(der::SHA256_WITH_RSA), der::null()]),
+        }
+    }
+
+    /// Signs `message`, returning the signature as encoded in X.509.
+    pub(crate) fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match &self.signer {
+            Signer::P256(key) => {
+                let sig: p256::ecdsa::Signature = key.sign(message);
+                sig.to_der().to_vec()
+            }
+            Signer::P384(key) => {
+                let sig: p384::ecdsa::Signature = key.sign(message);
+                sig.to_der().to_vec()
+            }
+            Signer::Ed25519(key) => key.sign(message).to_vec(),
+            Signer::Rsa(key) => key.sign(message).to_vec(),
+        }
+    }
+}
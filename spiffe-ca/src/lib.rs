@@ -0,0 +1,815 @@
+//! Certificate authority of a SPIFFE trust domain, minting X.509-SVIDs, JWT-SVIDs, WIT-SVIDs
+//! and CRLs from local keys.
+//!
+//! The CA backs `spiffe-lite-agent` and the `TestCa` of `spiffe-testkit`. It is not meant to
+//! replace a SPIRE server: keys live in memory and certificates carry a fixed set of extensions.
+
+mod der;
+mod error;
+mod key;
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use rustls_pki_types::{CertificateDer, CertificateRevocationListDer, PrivatePkcs8KeyDer};
+use serde_json::{Value as JsonValue, json};
+use spiffe::{
+    JwtSvid, WitSvid, X509Bundle, X509Svid,
+    jose::{Algorithm, JwkSet, SigningKey},
+};
+use spiffe_id::{SpiffeId, TrustDomain};
+
+use self::key::KeyPair;
+pub use self::{error::CaError, key::KeyType};
+
+/// Options of a certificate issued by a [`Ca`].
+#[derive(Clone, Debug)]
+pub struct IssueOptions {
+    key_type: KeyType,
+    not_before: SystemTime,
+    not_after: SystemTime,
+    dns_names: Vec<String>,
+}
+
+impl Default for IssueOptions {
+    fn default() -> Self {
+        let now = SystemTime::now();
+
+        Self {
+            key_type: KeyType::default(),
+            not_before: now - Duration::from_secs(3_600),
+            not_after: now + Duration::from_secs(86_400),
+            dns_names: Vec::new(),
+        }
+    }
+}
+
+impl IssueOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Default: [`KeyType::P256`]
+    #[must_use]
+    pub fn key_type(mut self, key_type: KeyType) -> Self {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Certificates issued by a CA never outlive it, `not_after` is capped at its expiry.
+    ///
+    /// Default: from an hour ago until a day from now
+    #[must_use]
+    pub fn validity(mut self, not_before: SystemTime, not_after: SystemTime) -> Self {
+        self.not_before = not_before;
+        self.not_after = not_after;
+        self
+    }
+
+    /// Adds a DNS name to the `subjectAltName` of a leaf, to serve it to WebPKI clients.
+    ///
+    /// Default: none
+    #[must_use]
+    pub fn dns_name(mut self, name: impl Into<String>) -> Self {
+        self.dns_names.push(name.into());
+        self
+    }
+}
+
+/// Names and constraints of a leaf issued with [`Ca::issue_leaf`], which unlike
+/// [`Ca::issue_x509_chain`] does not enforce the X.509-SVID profile.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct LeafTemplate {
+    uris: Vec<String>,
+    ca: bool,
+}
+
+impl LeafTemplate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a URI to the `subjectAltName`.
+    ///
+    /// Default: none
+    #[must_use]
+    pub fn uri(mut self, uri: impl Into<String>) -> Self {
+        self.uris.push(uri.into());
+        self
+    }
+
+    /// Marks the leaf as a CA, with the `keyCertSign` key usage.
+    ///
+    /// Default: not a CA
+    #[must_use]
+    pub fn ca(mut self) -> Self {
+        self.ca = true;
+        self
+    }
+}
+
+/// Certificate authority of a trust domain, issuing X.509-SVIDs, JWT-SVIDs and CRLs.
+///
+/// Every CA of a trust domain, the root and its intermediates, shares the root as X.509
+/// authority and one JWT signing key.
+pub struct Ca {
+    trust_domain: TrustDomain<'static>,
+    key: KeyPair,
+    subject: Vec<u8>,
+    certificate: CertificateDer<'static>,
+    not_after: SystemTime,
+    /// Intermediates from this CA up to, excluding, the root.
+    intermediates: Vec<CertificateDer<'static>>,
+    root: CertificateDer<'static>,
+    jwt_key: Arc<SigningKey>,
+    serial: Arc<AtomicU64>,
+    crl_number: AtomicU64,
+}
+
+impl Ca {
+    /// Creates a root CA with the default options.
+    pub fn new(trust_domain: TrustDomain<'_>) -> Result<Self, CaError> {
+        Self::with_options(trust_domain, &IssueOptions::new())
+    }
+
+    /// Creates a root CA.
+    pub fn with_options(
+        trust_domain: TrustDomain<'_>,
+        options: &IssueOptions,
+    ) -> Result<Self, CaError> {
+        let key = KeyPair::generate(options.key_type)?;
+        let subject = name(&format!("{trust_domain} root CA"));
+        let serial = Arc::new(AtomicU64::new(1));
+
+        let certificate = sign_certificate(
+            &key,
+            &subject,
+            serial.fetch_add(1, Ordering::Relaxed),
+            &subject,
+            key.spki(),
+            (options.not_before, options.not_after),
+            &ca_extensions(&trust_domain, &key),
+        );
+        let jwt_key = SigningKey::generate(Algorithm::ES256)
+            .map_err(|_| CaError::KeyGeneration)?
+            .with_key_id(format!("{trust_domain}-jwt"));
+
+        Ok(Self {
+            trust_domain: trust_domain.into_owned(),
+            key,
+            subject,
+            root: certificate.clone(),
+            certificate,
+            not_after: options.not_after,
+            intermediates: Vec::new(),
+            jwt_key: Arc::new(jwt_key),
+            serial,
+            crl_number: AtomicU64::new(1),
+        })
+    }
+
+    /// Loads a root CA saved with [`certificate`](Self::certificate), [`key`](Self::key) and
+    /// [`jwt_key`](Self::jwt_key).
+    ///
+    /// Fails if the certificate has expired or was not issued for `key`.
+    pub fn load(
+        trust_domain: TrustDomain<'_>,
+        certificate: CertificateDer<'static>,
+        key: &PrivatePkcs8KeyDer<'_>,
+        jwt_key: SigningKey,
+    ) -> Result<Self, CaError> {
+        let key = KeyPair::from_pkcs8(key.secret_pkcs8_der())?;
+        let subject = der::subject(&certificate)
+            .ok_or(CaError::InvalidCertificate)?
+            .to_vec();
+        let not_after = der::not_after(&certificate).ok_or(CaError::InvalidCertificate)?;
+        if der::spki(&certificate) != Some(key.spki()) {
+            return Err(CaError::KeyMismatch);
+        }
+        let now = SystemTime::now();
+        if not_after <= now {
+            return Err(CaError::Expired);
+        }
+
+        Ok(Self {
+            trust_domain: trust_domain.into_owned(),
+            key,
+            subject,
+            root: certificate.clone(),
+            certificate,
+            not_after,
+            intermediates: Vec::new(),
+            jwt_key: Arc::new(jwt_key),
+            // keeps the serials of a restarted CA apart from the ones it issued before
+            serial: Arc::new(AtomicU64::new(unix_seconds(now) << 20)),
+            crl_number: AtomicU64::new(1),
+        })
+    }
+
+    /// Creates an intermediate CA signed by this CA.
+    pub fn intermediate(&self, options: &IssueOptions) -> Result<Self, CaError> {
+        let key = KeyPair::generate(options.key_type)?;
+        let serial = self.next_serial();
+        let subject = name(&format!("{} intermediate CA {serial}", self.trust_domain));
+        let validity = self.validity(options);
+
+        let certificate = sign_certificate(
+            &self.key,
+            &self.subject,
+            serial,
+            &subject,
+            key.spki(),
+            validity,
+            &ca_extensions(&self.trust_domain, &key),
+        );
+        let mut intermediates = vec![certificate.clone()];
+        intermediates.extend(self.intermediates.iter().cloned());
+
+        Ok(Self {
+            trust_domain: self.trust_domain.clone(),
+            key,
+            subject,
+            certificate,
+            not_after: validity.1,
+            intermediates,
+            root: self.root.clone(),
+            jwt_key: self.jwt_key.clone(),
+            serial: self.serial.clone(),
+            crl_number: AtomicU64::new(1),
+        })
+    }
+
+    #[inline]
+    pub fn trust_domain(&self) -> &TrustDomain<'static> {
+        &self.trust_domain
+    }
+
+    /// Returns the certificate of this CA.
+    #[inline]
+    pub fn certificate(&self) -> &CertificateDer<'static> {
+        &self.certificate
+    }
+
+    /// Returns the `notAfter` of the certificate of this CA.
+    #[inline]
+    pub fn expiry(&self) -> SystemTime {
+        self.not_after
+    }
+
+    /// Returns the private key of this CA.
+    #[inline]
+    pub fn key(&self) -> &PrivatePkcs8KeyDer<'static> {
+        self.key.pkcs8()
+    }
+
+    /// Returns the JWT signing key of the trust domain, also signing the WITs.
+    #[inline]
+    pub fn jwt_key(&self) -> &SigningKey {
+        &self.jwt_key
+    }
+
+    /// Returns the X.509 bundle of the trust domain, holding the root.
+    pub fn x509_bundle(&self) -> X509Bundle {
+        X509Bundle::from_der(&self.root).expect("the root is a single certificate")
+    }
+
+    /// Returns the JWT bundle of the trust domain as a JWKS document.
+    pub fn jwt_bundle(&self) -> String {
+        self.jwks("jwt-svid")
+    }
+
+    /// Returns the WIT bundle of the trust domain as a JWKS document.
+    pub fn wit_bundle(&self) -> String {
+        self.jwks("wit-svid")
+    }
+
+    /// Issues an X.509-SVID leaf, returning the chain up to, excluding, the root and the key.
+    pub fn issue_x509_chain(
+        &self,
+        spiffe_id: &SpiffeId,
+        options: &IssueOptions,
+    ) -> Result<(Vec<CertificateDer<'static>>, PrivatePkcs8KeyDer<'static>), CaError> {
+        self.issue_leaf(&LeafTemplate::new().uri(spiffe_id.as_str()), options)
+    }
+
+    /// Issues a leaf certificate, returning the chain up to, excluding, the root and the key.
+    pub fn issue_leaf(
+        &self,
+        template: &LeafTemplate,
+        options: &IssueOptions,
+    ) -> Result<(Vec<CertificateDer<'static>>, PrivatePkcs8KeyDer<'static>), CaError> {
+        let key = KeyPair::generate(options.key_type)?;
+
+        let key_usage = if template.ca {
+            KEY_CERT_SIGN
+        } else {
+            DIGITAL_SIGNATURE
+        };
+        let mut extensions = vec![
+            extension(
+                der::BASIC_CONSTRAINTS,
+                true,
+                &basic_constraints(template.ca),
+            ),
+            extension(der::KEY_USAGE, true, &der::named_bits(key_usage)),
+            extension(
+                der::EXT_KEY_USAGE,
+                false,
+                &der::sequence([der::oid(der::SERVER_AUTH), der::oid(der::CLIENT_AUTH)]),
+            ),
+        ];
+        if template.ca {
+            extensions.push(subject_key_identifier(&key));
+        }
+        if !template.uris.is_empty() || !options.dns_names.is_empty() {
+            extensions.push(extension(
+                der::SUBJECT_ALT_NAME,
+                false,
+                &san(&template.uris, &options.dns_names),
+            ));
+        }
+
+        let certificate = sign_certificate(
+            &self.key,
+            &self.subject,
+            self.next_serial(),
+            &name("SPIRE workload"),
+            key.spki(),
+            self.validity(options),
+            &extensions,
+        );
+        let mut chain = vec![certificate];
+        chain.extend(self.intermediates.iter().cloned());
+
+        Ok((chain, key.pkcs8().clone_key()))
+    }
+
+    /// Issues an X.509-SVID with the trust domain's bundle attached.
+    pub fn issue_x509_svid(
+        &self,
+        spiffe_id: &SpiffeId,
+        options: &IssueOptions,
+    ) -> Result<X509Svid, CaError> {
+        let (chain, key) = self.issue_x509_chain(spiffe_id, options)?;
+
+        Ok(spiffe_proto::X509Svid {
+            spiffe_id: spiffe_id.to_string(),
+            x509_svid: chain
+                .iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .concat()
+                .into(),
+            x509_svid_key: key.secret_pkcs8_der().to_vec().into(),
+            bundle: self.root.to_vec().into(),
+            hint: String::new(),
+        }
+        .try_into()?)
+    }
+
+    /// Issues a JWT-SVID signed with the trust domain's JWT key.
+    pub fn issue_jwt_svid(
+        &self,
+        spiffe_id: &SpiffeId,
+        audiences: &[&str],
+        expiry: SystemTime,
+    ) -> Result<JwtSvid, CaError> {
+        let claims = json!({
+            "sub": spiffe_id.as_str(),
+            "aud": audiences,
+            "exp": unix_seconds(expiry),
+            "iat": unix_seconds(SystemTime::now()),
+        });
+        let token = self.sign_token("JWT", &claims);
+
+        Ok(spiffe_proto::JwtSvid {
+            spiffe_id: spiffe_id.to_string(),
+            svid: token,
+            hint: String::new(),
+        }
+        .try_into()?)
+    }
+
+    /// Issues a WIT-SVID signed with the trust domain's JWT key, bound to a new ES256 key.
+    pub fn issue_wit_svid(
+        &self,
+        spiffe_id: &SpiffeId,
+        expiry: SystemTime,
+    ) -> Result<WitSvid, CaError> {
+        let key = SigningKey::generate(Algorithm::ES256).map_err(|_| CaError::KeyGeneration)?;
+        let claims = json!({
+            "sub": spiffe_id.as_str(),
+            "exp": unix_seconds(expiry),
+            "iat": unix_seconds(SystemTime::now()),
+            "jti": self.next_serial().to_string(),
+            "cnf": { "jwk": key.public_key().to_value() },
+        });
+
+        Ok(spiffe_proto::WitSvid {
+            spiffe_id: spiffe_id.to_string(),
+            wit_svid: self.sign_token("wit+jwt", &claims),
+            wit_svid_key: key.to_jwk().into_inner(),
+            hint: String::new(),
+        }
+        .try_into()?)
+    }
+
+    /// Issues a CRL revoking `revoked`, valid from a minute ago until a day from now.
+    pub fn crl(
+        &self,
+        revoked: &[&CertificateDer<'_>],
+    ) -> Result<CertificateRevocationListDer<'static>, CaError> {
+        let now = SystemTime::now();
+
+        self.crl_at(
+            revoked,
+            now - Duration::from_secs(60),
+            now + Duration::from_secs(86_400),
+        )
+    }
+
+    /// Issues a CRL revoking `revoked` with the given `thisUpdate` and `nextUpdate`.
+    pub fn crl_at(
+        &self,
+        revoked: &[&CertificateDer<'_>],
+        this_update: SystemTime,
+        next_update: SystemTime,
+    ) -> Result<CertificateRevocationListDer<'static>, CaError> {
+        let revoked = revoked
+            .iter()
+            .map(|cert| {
+                let serial = der::serial_number(cert).ok_or(CaError::InvalidCertificate)?;
+                Ok(der::sequence([
+                    der::tlv(0x02, serial),
+                    der::time(this_update),
+                ]))
+            })
+            .collect::<Result<Vec<_>, CaError>>()?;
+        let crl_number = self.crl_number.fetch_add(1, Ordering::Relaxed);
+
+        let mut tbs = vec![
+            der::integer(1),
+            self.key.signature_algorithm(),
+            self.subject.clone(),
+            der::time(this_update),
+            der::time(next_update),
+        ];
+        if !revoked.is_empty() {
+            tbs.push(der::sequence(revoked));
+        }
+        tbs.push(der::explicit(
+            0,
+            [der::sequence([
+                authority_key_identifier(&self.key),
+                extension(der::CRL_NUMBER, false, &der::integer(crl_number)),
+            ])],
+        ));
+
+        Ok(sign(&self.key, &der::sequence(tbs)).into())
+    }
+
+    fn jwks(&self, public_use: &str) -> String {
+        let jwk = self
+            .jwt_key
+            .public_key()
+            .clone()
+            .with_public_use(public_use);
+
+        JwkSet::new(vec![jwk]).to_value().to_string()
+    }
+
+    /// Encodes `claims` into a compact JWS signed with the JWT key.
+    fn sign_token(&self, typ: &str, claims: &JsonValue) -> String {
+        let header = json!({
+            "alg": self.jwt_key.algorithm().as_str(),
+            "kid": self.jwt_key.public_key().key_id(),
+            "typ": typ,
+        });
+        let signing_input = format!(
+            "{}.{}",
+            Base64UrlUnpadded::encode_string(header.to_string().as_bytes()),
+            Base64UrlUnpadded::encode_string(claims.to_string().as_bytes()),
+        );
+        let signature = self.jwt_key.sign(signing_input.as_bytes());
+
+        format!(
+            "{signing_input}.{}",
+            Base64UrlUnpadded::encode_string(&signature)
+        )
+    }
+
+    /// The validity of `options`, capped at the expiry of this CA.
+    fn validity(&self, options: &IssueOptions) -> (SystemTime, SystemTime) {
+        (options.not_before, options.not_after.min(self.not_after))
+    }
+
+    fn next_serial(&self) -> u64 {
+        self.serial.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
+const DIGITAL_SIGNATURE: u8 = 0x80;
+const KEY_CERT_SIGN: u8 = 0x04;
+const CRL_SIGN: u8 = 0x02;
+
+fn name(common_name: &str) -> Vec<u8> {
+    let attribute =
+        |oid, value| der::set([der::sequence([der::oid(oid), der::utf8_string(value)])]);
+
+    der::sequence([
+        attribute(der::ORGANIZATION, "SPIFFE"),
+        attribute(der::COMMON_NAME, common_name),
+    ])
+}
+
+fn extension(oid: &[u8], critical: bool, value: &[u8]) -> Vec<u8> {
+    let mut fields = vec![der::oid(oid)];
+    if critical {
+        fields.push(der::boolean(true));
+    }
+    fields.push(der::octet_string(value));
+
+    der::sequence(fields)
+}
+
+fn basic_constraints(ca: bool) -> Vec<u8> {
+    if ca {
+        der::sequence([der::boolean(true)])
+    } else {
+        der::sequence::<&[u8]>([])
+    }
+}
+
+fn san(uris: &[String], dns_names: &[String]) -> Vec<u8> {
+    der::sequence(
+        uris.iter()
+            .map(|x| der::tlv(0x86, x.as_bytes()))
+            .chain(dns_names.iter().map(|x| der::tlv(0x82, x.as_bytes()))),
+    )
+}
+
+fn subject_key_identifier(key: &KeyPair) -> Vec<u8> {
+    extension(
+        der::SUBJECT_KEY_IDENTIFIER,
+        false,
+        &der::octet_string(&key.key_id()),
+    )
+}
+
+fn authority_key_identifier(issuer_key: &KeyPair) -> Vec<u8> {
+    extension(
+        der::AUTHORITY_KEY_IDENTIFIER,
+        false,
+        &der::sequence([der::tlv(0x80, &issuer_key.key_id())]),
+    )
+}
+
+fn ca_extensions(trust_domain: &TrustDomain<'_>, key: &KeyPair) -> Vec<Vec<u8>> {
+    vec![
+        subject_key_identifier(key),
+        extension(der::BASIC_CONSTRAINTS, true, &basic_constraints(true)),
+        extension(
+            der::KEY_USAGE,
+            true,
+            &der::named_bits(KEY_CERT_SIGN | CRL_SIGN),
+        ),
+        extension(
+            der::SUBJECT_ALT_NAME,
+            false,
+            &san(&[format!("spiffe://{trust_domain}")], &[]),
+        ),
+    ]
+}
+
+fn sign_certificate(
+    issuer_key: &KeyPair,
+    issuer: &[u8],
+    serial: u64,
+    subject: &[u8],
+    spki: &[u8],
+    (not_before, not_after): (SystemTime, SystemTime),
+    extensions: &[Vec<u8>],
+) -> CertificateDer<'static> {
+    let extensions = extensions
+        .iter()
+        .cloned()
+        .chain([authority_key_identifier(issuer_key)]);
+    let tbs = der::sequence([
+        der::explicit(0, [der::integer(2)]),
+        der::integer(serial),
+        issuer_key.signature_algorithm(),
+        issuer.to_vec(),
+        der::sequence([der::time(not_before), der::time(not_after)]),
+        subject.to_vec(),
+        spki.to_vec(),
+        der::explicit(3, [der::sequence(extensions)]),
+    ]);
+
+    sign(issuer_key, &tbs).into()
+}
+
+/// Wraps `tbs` into a signed structure, e.g. a `Certificate` or `CertificateList`.
+fn sign(key: &KeyPair, tbs: &[u8]) -> Vec<u8> {
+    der::sequence([
+        tbs.to_vec(),
+        key.signature_algorithm(),
+        der::bit_string(&key.sign(tbs)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls_pki_types::{SignatureVerificationAlgorithm, UnixTime};
+    use spiffe::{Crl, jose::Jwk, spiffe_id_from_x509_svid_unchecked};
+    use webpki::{
+        BorrowedCertRevocationList, CertRevocationList, EndEntityCert, Error, KeyUsage,
+        RevocationOptionsBuilder, anchor_from_trusted_cert, ring,
+    };
+
+    use super::*;
+
+    const ALGORITHMS: &[&dyn SignatureVerificationAlgorithm] = &[
+        ring::ECDSA_P256_SHA256,
+        ring::ECDSA_P384_SHA384,
+        ring::ED25519,
+        ring::RSA_PKCS1_2048_8192_SHA256,
+    ];
+
+    fn verify(
+        ca: &Ca,
+        chain: &[CertificateDer<'_>],
+        crls: &[&CertRevocationList<'_>],
+    ) -> Result<(), Error> {
+        let anchors = [anchor_from_trusted_cert(&ca.root).unwrap()];
+        let revocation = match crls {
+            [] => None,
+            crls => Some(RevocationOptionsBuilder::new(crls).unwrap().build()),
+        };
+
+        EndEntityCert::try_from(&chain[0])?
+            .verify_for_usage(
+                ALGORITHMS,
+                &anchors,
+                &chain[1..],
+                UnixTime::since_unix_epoch(UNIX_EPOCH.elapsed().unwrap()),
+                KeyUsage::server_auth(),
+                revocation,
+                None,
+            )
+            .map(|_| ())
+    }
+
+    fn spiffe_id() -> SpiffeId {
+        SpiffeId::new("spiffe://example.org/workload").unwrap()
+    }
+
+    #[test]
+    fn test_issue_x509_svid() {
+        let root = Ca::new(TrustDomain::new("example.org").unwrap()).unwrap();
+        let intermediate = root
+            .intermediate(&IssueOptions::new().key_type(KeyType::P384))
+            .unwrap();
+
+        for (ca, key_type) in [
+            (&root, KeyType::P256),
+            (&root, KeyType::Ed25519),
+            (&intermediate, KeyType::P384),
+        ] {
+            let options = IssueOptions::new().key_type(key_type);
+            let svid = ca.issue_x509_svid(&spiffe_id(), &options).unwrap();
+
+            verify(ca, svid.svid(), &[]).unwrap();
+            assert_eq!(
+                spiffe_id_from_x509_svid_unchecked(&svid.svid()[0]).unwrap(),
+                spiffe_id()
+            );
+            assert_eq!(svid.bundle(), &root.x509_bundle());
+            assert!(svid.expiry().unwrap() > SystemTime::now());
+        }
+
+        let expired = IssueOptions::new().validity(
+            UNIX_EPOCH + Duration::from_secs(1_000_000_000),
+            UNIX_EPOCH + Duration::from_secs(1_000_086_400),
+        );
+        let svid = root.issue_x509_svid(&spiffe_id(), &expired).unwrap();
+        assert!(matches!(
+            verify(&root, svid.svid(), &[]),
+            Err(Error::CertExpired { .. })
+        ));
+
+        // leaves never outlive their CA
+        let long = IssueOptions::new().validity(
+            SystemTime::now(),
+            SystemTime::now() + Duration::from_secs(10 * 86_400),
+        );
+        let svid = root.issue_x509_svid(&spiffe_id(), &long).unwrap();
+        assert!(svid.expiry().unwrap() <= root.expiry());
+    }
+
+    #[test]
+    fn test_crl() {
+        let ca = Ca::new(TrustDomain::new("example.org").unwrap()).unwrap();
+        let options = IssueOptions::new();
+        let good = ca.issue_x509_chain(&spiffe_id(), &options).unwrap().0;
+        let revoked = ca.issue_x509_chain(&spiffe_id(), &options).unwrap().0;
+        let crl = ca.crl(&[&revoked[0]]).unwrap();
+
+        let parsed = Crl::from_der(&crl).unwrap();
+        assert!(parsed.is_issued_by(ca.certificate()));
+        assert!(parsed.is_revoked(&revoked[0]).unwrap());
+        assert!(!parsed.is_revoked(&good[0]).unwrap());
+        assert!(!parsed.is_stale());
+
+        let crl = CertRevocationList::from(BorrowedCertRevocationList::from_der(&crl).unwrap());
+        verify(&ca, &good, &[&crl]).unwrap();
+        assert!(matches!(
+            verify(&ca, &revoked, &[&crl]),
+            Err(Error::CertRevoked)
+        ));
+    }
+
+    #[test]
+    fn test_issue_jwt_svid() {
+        let ca = Ca::new(TrustDomain::new("example.org").unwrap()).unwrap();
+        let expiry = SystemTime::now() + Duration::from_secs(300);
+        let svid = ca.issue_jwt_svid(&spiffe_id(), &["db"], expiry).unwrap();
+        assert_eq!(svid.spiffe_id(), &spiffe_id());
+
+        let jwks = JwkSet::from_json(&ca.jwt_bundle()).unwrap();
+        let jwk: &Jwk = jwks.find(&format!("{}-jwt", ca.trust_domain())).unwrap();
+        assert_eq!(jwk.public_use(), Some("jwt-svid"));
+
+        let (signing_input, signature) = svid.svid().rsplit_once('.').unwrap();
+        let signature = Base64UrlUnpadded::decode_vec(signature).unwrap();
+        jwk.verify(Algorithm::ES256, signing_input.as_bytes(), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_load() {
+        let td = TrustDomain::new("example.org").unwrap();
+        let jwt_key =
+            |ca: &Ca| SigningKey::from_jwk(ca.jwt_key().to_jwk().expose_secret()).unwrap();
+
+        for key_type in [KeyType::P256, KeyType::Ed25519] {
+            let ca = Ca::with_options(td.clone(), &IssueOptions::new().key_type(key_type)).unwrap();
+            let loaded =
+                Ca::load(td.clone(), ca.certificate().clone(), ca.key(), jwt_key(&ca)).unwrap();
+
+            assert_eq!(loaded.x509_bundle(), ca.x509_bundle());
+            assert_eq!(loaded.jwt_bundle(), ca.jwt_bundle());
+            assert_eq!(unix_seconds(loaded.expiry()), unix_seconds(ca.expiry()));
+            let svid = loaded
+                .issue_x509_svid(&spiffe_id(), &IssueOptions::new())
+                .unwrap();
+            verify(&ca, svid.svid(), &[]).unwrap();
+        }
+
+        let ca = Ca::new(td.clone()).unwrap();
+        let other = Ca::new(td.clone()).unwrap();
+        assert!(matches!(
+            Ca::load(
+                td.clone(),
+                ca.certificate().clone(),
+                other.key(),
+                jwt_key(&ca)
+            ),
+            Err(CaError::KeyMismatch)
+        ));
+
+        let now = SystemTime::now();
+        let expired = IssueOptions::new().validity(
+            now - Duration::from_secs(7_200),
+            now - Duration::from_secs(3_600),
+        );
+        let ca = Ca::with_options(td.clone(), &expired).unwrap();
+        assert!(matches!(
+            Ca::load(td, ca.certificate().clone(), ca.key(), jwt_key(&ca)),
+            Err(CaError::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_issue_wit_svid() {
+        let ca = Ca::new(TrustDomain::new("example.org").unwrap()).unwrap();
+        let expiry = SystemTime::now() + Duration::from_secs(300);
+        let svid = ca.issue_wit_svid(&spiffe_id(), expiry).unwrap();
+
+        let bundles =
+            spiffe::wit::parse_wit_bundles(&[(ca.trust_domain().clone(), ca.wit_bundle())].into())
+                .unwrap();
+        let wit = spiffe::wit::Wit::validate(svid.svid(), &bundles).unwrap();
+        assert_eq!(wit.spiffe_id(), &spiffe_id());
+        svid.signing_key().unwrap();
+    }
+}
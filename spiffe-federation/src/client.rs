@@ -0,0 +1,438 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::{Stream, StreamExt, future, stream};
+use http::{Request, StatusCode, Uri, header::HOST, uri::Scheme};
+use http_body_util::{BodyExt, Empty, Limited};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use spiffe::{BundleError, X509Bundle, bundle::TrustDomainBundle, client::X509BundlesContext};
+use spiffe_id::{SpiffeId, TrustDomain};
+use spiffe_tls::server_cert_verifier;
+use tokio::{
+    net::TcpStream,
+    time::{sleep, timeout},
+};
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{ClientConfig, RootCertStore, crypto::CryptoProvider, pki_types::ServerName},
+};
+
+use crate::FederationError;
+
+const MAX_BUNDLE_SIZE: usize = 1 << 20;
+const MIN_REFRESH_HINT: Duration = Duration::from_secs(1);
+
+/// Authentication of a bundle endpoint.
+#[derive(Clone, Debug)]
+pub enum EndpointProfile {
+    /// `https_web`: the endpoint presents a certificate for the host of its URL, issued by one
+    /// of `roots`.
+    HttpsWeb { roots: Arc<RootCertStore> },
+
+    /// `https_spiffe`: the endpoint presents an X.509-SVID for `endpoint_id`, issued by `bundle`,
+    /// the X.509 authorities of its trust domain.
+    ///
+    /// When the endpoint is in the federated trust domain, `bundle` is replaced with the X.509
+    /// authorities of every bundle fetched.
+    HttpsSpiffe {
+        endpoint_id: SpiffeId,
+        bundle: X509Bundle,
+    },
+}
+
+/// Client polling the bundle endpoint of a federated trust domain.
+#[derive(Debug)]
+pub struct BundleEndpointClient {
+    trust_domain: TrustDomain<'static>,
+    uri: Uri,
+    host: String,
+    port: u16,
+    server_name: ServerName<'static>,
+    profile: EndpointProfile,
+    crypto_provider: Arc<CryptoProvider>,
+    refresh_interval: Duration,
+    retry_interval: Duration,
+    timeout: Duration,
+}
+
+impl BundleEndpointClient {
+    /// Creates a client fetching the bundle of `trust_domain` from the `https` URL `url`.
+    pub fn new(
+        trust_domain: TrustDomain<'_>,
+        url: &str,
+        profile: EndpointProfile,
+        crypto_provider: Arc<CryptoProvider>,
+    ) -> Result<Self, FederationError> {
+        let uri = url
+            .parse::<Uri>()
+            .map_err(|e| FederationError::InvalidUrl(e.to_string()))?;
+        if uri.scheme() != Some(&Scheme::HTTPS) {
+            return Err(FederationError::InvalidUrl(
+                "the scheme must be `https`".into(),
+            ));
+        }
+
+        let host = uri
+            .host()
+            .ok_or_else(|| FederationError::InvalidUrl("the host is missing".into()))?;
+        let host = host
+            .strip_prefix('[')
+            .and_then(|x| x.strip_suffix(']'))
+            .unwrap_or(host)
+            .to_owned();
+        let server_name = ServerName::try_from(host.clone())
+            .map_err(|e| FederationError::InvalidUrl(e.to_string()))?;
+
+        Ok(Self {
+            trust_domain: trust_domain.into_owned(),
+            port: uri.port_u16().unwrap_or(443),
+            uri,
+            host,
+            server_name,
+            profile,
+            crypto_provider,
+            refresh_interval: Duration::from_secs(300),
+            retry_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Sets the polling interval of bundles without a refresh hint.
+    ///
+    /// Default: 5 minutes
+    #[must_use]
+    pub fn refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Sets the delay before polling again after a failure.
+    ///
+    /// Default: 30 seconds
+    #[must_use]
+    pub fn retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Sets the timeout of a fetch, from connecting to reading the bundle.
+    ///
+    /// Default: 30 seconds
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[inline]
+    pub fn trust_domain(&self) -> &TrustDomain<'static> {
+        &self.trust_domain
+    }
+
+    #[inline]
+    pub fn profile(&self) -> &EndpointProfile {
+        &self.profile
+    }
+
+    /// Fetches the bundle once.
+    pub async fn fetch(&mut self) -> Result<TrustDomainBundle, FederationError> {
+        let bundle = timeout(self.timeout, self.request())
+            .await
+            .map_err(|_| FederationError::Timeout)??;
+
+        if let EndpointProfile::HttpsSpiffe {
+            endpoint_id,
+            bundle: held,
+        } = &mut self.profile
+            && endpoint_id.trust_domain() == self.trust_domain
+            && let Some(x509_bundle) = bundle.x509_bundle()
+        {
+            *held = x509_bundle;
+        }
+
+        Ok(bundle)
+    }
+
+    /// Polls the bundle endpoint, yielding the bundle whenever it changes.
+    ///
+    /// The bundle is fetched again after its refresh hint, of at least a second, or the refresh
+    /// interval. Failures are yielded and retried after the retry interval.
+    pub fn watch(
+        self,
+    ) -> impl Stream<Item = Result<TrustDomainBundle, FederationError>> + Send + 'static {
+        stream::unfold(
+            (self, None, Duration::ZERO),
+            |(mut this, mut last, mut delay)| async move {
+                loop {
+                    sleep(delay).await;
+                    match this.fetch().await {
+                        Ok(bundle) => {
+                            delay = bundle
+                                .refresh_hint()
+                                .map_or(this.refresh_interval, |x| x.max(MIN_REFRESH_HINT));
+                            if last.as_ref() != Some(&bundle) {
+                                last = Some(bundle.clone());
+                                return Some((Ok(bundle), (this, last, delay)));
+                            }
+                        }
+                        Err(e) => {
+                            let delay = this.retry_interval;
+                            return Some((Err(e), (this, last, delay)));
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    /// Polls the bundle endpoint like [`watch`](Self::watch), yielding the X.509 authorities
+    /// for `spiffe-tls` and skipping failures.
+    pub fn x509_bundles(self) -> impl Stream<Item = X509BundlesContext> + Send + 'static {
+        self.watch()
+            .filter_map(|x| future::ready(x.ok().map(X509BundlesContext::from)))
+    }
+
+    async fn request(&self) -> Result<TrustDomainBundle, FederationError> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        let tls = TlsConnector::from(Arc::new(self.tls_config()?))
+            .connect(self.server_name.clone(), tcp)
+            .await?;
+
+        let (mut sender, connection) =
+            hyper::client::conn::http1::handshake(TokioIo::new(tls)).await?;
+        tokio::spawn(connection);
+
+        let request = Request::get(self.uri.path_and_query().map_or("/", |x| x.as_str()))
+            .header(HOST, self.uri.authority().map_or("", |x| x.as_str()))
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| FederationError::InvalidUrl(e.to_string()))?;
+        let response = sender.send_request(request).await?;
+        if response.status() != StatusCode::OK {
+            return Err(FederationError::Status(response.status()));
+        }
+
+        let body = Limited::new(response.into_body(), MAX_BUNDLE_SIZE)
+            .collect()
+            .await
+            .map_err(|e| match e.downcast::<hyper::Error>() {
+                Ok(e) => FederationError::Http(*e),
+                Err(_) => FederationError::TooLarge(MAX_BUNDLE_SIZE),
+            })?
+            .to_bytes();
+        let json = str::from_utf8(&body).map_err(|_| BundleError::Malformed)?;
+
+        Ok(TrustDomainBundle::from_json(
+            self.trust_domain.clone(),
+            json,
+        )?)
+    }
+
+    fn tls_config(&self) -> Result<ClientConfig, FederationError> {
+        let builder = ClientConfig::builder_with_provider(self.crypto_provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(FederationError::Tls)?;
+
+        let builder = match &self.profile {
+            EndpointProfile::HttpsWeb { roots } => builder.with_root_certificates(roots.clone()),
+            EndpointProfile::HttpsSpiffe {
+                endpoint_id,
+                bundle,
+            } => builder.dangerous().with_custom_certificate_verifier(
+                server_cert_verifier(
+                    endpoint_id.clone(),
+                    bundle.clone(),
+                    self.crypto_provider.clone(),
+                )
+                .map_err(FederationError::Tls)?,
+            ),
+        };
+
+        Ok(builder.with_no_client_auth())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::Ipv4Addr,
+        sync::{Arc, Mutex},
+    };
+
+    use http::Response;
+    use http_body_util::Full;
+    use hyper::service::service_fn;
+    use spiffe_testkit::{IssueOptions, TestCa};
+    use tokio::net::TcpListener;
+    use tokio_rustls::{
+        TlsAcceptor,
+        rustls::{
+            CertificateError, Error, ServerConfig,
+            crypto::ring,
+            pki_types::{CertificateDer, PrivateKeyDer},
+        },
+    };
+
+    use super::*;
+
+    fn provider() -> Arc<CryptoProvider> {
+        Arc::new(ring::default_provider())
+    }
+
+    /// Serves `document` over HTTPS with `chain`, returning the port.
+    async fn serve(
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+        document: Arc<Mutex<String>>,
+    ) -> u16 {
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            loop {
+                let (tcp, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                let document = document.clone();
+                tokio::spawn(async move {
+                    let Ok(tls) = acceptor.accept(tcp).await else {
+                        return;
+                    };
+                    let service = service_fn(move |_| {
+                        let body = document.lock().unwrap().clone();
+                        async move { Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(body)))) }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(tls), service)
+                        .await;
+                });
+            }
+        });
+
+        port
+    }
+
+    fn bundle(ca: &TestCa) -> TrustDomainBundle {
+        let mut bundle = TrustDomainBundle::new(ca.trust_domain().clone());
+        bundle.add_x509_authority(ca.certificate().clone());
+        bundle.set_refresh_hint(Some(Duration::from_secs(1)));
+        bundle
+    }
+
+    #[tokio::test]
+    async fn test_https_spiffe() {
+        let td = TrustDomain::new("example.org").unwrap();
+        let ca = TestCa::new(td.clone());
+        let endpoint_id = SpiffeId::new("spiffe://example.org/bundle-endpoint").unwrap();
+        let (chain, key) = ca.issue_x509_chain(&endpoint_id, &IssueOptions::new());
+
+        let document = Arc::new(Mutex::new(bundle(&ca).to_json().unwrap()));
+        let port = serve(chain, key.into(), document.clone()).await;
+        let url = format!("https://127.0.0.1:{port}/");
+
+        let profile = EndpointProfile::HttpsSpiffe {
+            endpoint_id: endpoint_id.clone(),
+            bundle: ca.x509_bundle(),
+        };
+        let mut watch = Box::pin(
+            BundleEndpointClient::new(td.clone(), &url, profile, provider())
+                .unwrap()
+                .watch(),
+        );
+        assert_eq!(watch.next().await.unwrap().unwrap(), bundle(&ca));
+
+        let mut updated = bundle(&ca);
+        updated.add_x509_authority(TestCa::new(td.clone()).certificate().clone());
+        updated.set_sequence(Some(2));
+        *document.lock().unwrap() = updated.to_json().unwrap();
+        assert_eq!(watch.next().await.unwrap().unwrap(), updated);
+
+        let profile = EndpointProfile::HttpsSpiffe {
+            endpoint_id: SpiffeId::new("spiffe://example.org/other").unwrap(),
+            bundle: ca.x509_bundle(),
+        };
+        let mut client = BundleEndpointClient::new(td.clone(), &url, profile, provider()).unwrap();
+        assert!(matches!(
+            client.fetch().await,
+            Err(FederationError::Tls(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure
+            )))
+        ));
+
+        let profile = EndpointProfile::HttpsSpiffe {
+            endpoint_id,
+            bundle: TestCa::new(td.clone()).x509_bundle(),
+        };
+        let mut client = BundleEndpointClient::new(td, &url, profile, provider()).unwrap();
+        assert!(matches!(
+            client.fetch().await,
+            Err(FederationError::Tls(Error::InvalidCertificate(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_https_web() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let (chain, key) = ca.issue_x509_chain(
+            &SpiffeId::new("spiffe://example.org/bundle-endpoint").unwrap(),
+            &IssueOptions::new().dns_name("localhost"),
+        );
+
+        let federated = TestCa::new(TrustDomain::new("federated.org").unwrap());
+        let document = Arc::new(Mutex::new(bundle(&federated).to_json().unwrap()));
+        let port = serve(chain, key.into(), document).await;
+
+        let roots = |ca: &TestCa| {
+            let mut roots = RootCertStore::empty();
+            roots.add(ca.certificate().clone()).unwrap();
+            EndpointProfile::HttpsWeb {
+                roots: Arc::new(roots),
+            }
+        };
+        let mut client = BundleEndpointClient::new(
+            federated.trust_domain().clone(),
+            &format!("https://localhost:{port}/bundle"),
+            roots(&ca),
+            provider(),
+        )
+        .unwrap();
+        assert_eq!(client.fetch().await.unwrap(), bundle(&federated));
+
+        let mut client = BundleEndpointClient::new(
+            federated.trust_domain().clone(),
+            &format!("https://localhost:{port}/bundle"),
+            roots(&federated),
+            provider(),
+        )
+        .unwrap();
+        assert!(matches!(
+            client.fetch().await,
+            Err(FederationError::Tls(Error::InvalidCertificate(_)))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        let td = TrustDomain::new("example.org").unwrap();
+        let profile = EndpointProfile::HttpsWeb {
+            roots: Arc::new(RootCertStore::empty()),
+        };
+
+        for url in ["http://example.org/", "https:///bundle", "example.org"] {
+            assert!(matches!(
+                BundleEndpointClient::new(td.clone(), url, profile.clone(), provider()),
+                Err(FederationError::InvalidUrl(_))
+            ));
+        }
+
+        let client =
+            BundleEndpointClient::new(td, "https://[::1]/bundle", profile, provider()).unwrap();
+        assert_eq!((client.host.as_str(), client.port), ("::1", 443));
+    }
+}
@@ -0,0 +1,45 @@
+use std::io::Error as IoError;
+
+use http::StatusCode;
+use spiffe::BundleError;
+use thiserror::Error;
+use tokio_rustls::rustls;
+
+#[derive(Error, Debug)]
+pub enum FederationError {
+    #[error("invalid bundle endpoint URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("failed to connect to the bundle endpoint: {0}")]
+    Io(IoError),
+
+    #[error("failed to authenticate the bundle endpoint: {0}")]
+    Tls(rustls::Error),
+
+    #[error("failed to request the bundle: {0}")]
+    Http(#[from] hyper::Error),
+
+    #[error("bundle endpoint responded with status {0}")]
+    Status(StatusCode),
+
+    #[error("bundle exceeds {0} bytes")]
+    TooLarge(usize),
+
+    #[error("bundle endpoint timed out")]
+    Timeout,
+
+    #[error("invalid bundle: {0}")]
+    Bundle(#[from] BundleError),
+}
+
+impl From<IoError> for FederationError {
+    fn from(error: IoError) -> Self {
+        match error
+            .get_ref()
+            .and_then(|x| x.downcast_ref::<rustls::Error>())
+        {
+            Some(tls) => Self::Tls(tls.clone()),
+            None => Self::Io(error),
+        }
+    }
+}
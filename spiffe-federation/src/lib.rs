@@ -0,0 +1,20 @@
+//! SPIFFE federation: exchanging the bundles of trust domains over their bundle endpoints.
+//!
+//! A [`BundleEndpointClient`] polls the bundle endpoint of a federated trust domain and
+//! authenticates it with an [`EndpointProfile`]. Its bundles can feed `spiffe-tls` through
+//! [`BundleEndpointClient::x509_bundles`]. A [`BundleEndpointServer`] serves the bundle of our
+//! own trust domain to federated ones.
+//!
+//! # References
+//!
+//! - [SPIFFE Federation](https://github.com/spiffe/spiffe/blob/main/standards/SPIFFE_Federation.md)
+
+mod client;
+mod error;
+mod server;
+
+pub use self::{
+    client::{BundleEndpointClient, EndpointProfile},
+    error::FederationError,
+    server::{BundleEndpointServer, svid_server_config},
+};
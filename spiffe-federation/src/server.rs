@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt::Display,
+    future::Future,
+    pin::pin,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::{FutureExt, Stream, StreamExt, future, stream, stream::BoxStream};
+use http::{HeaderValue, Method, Request, Response, StatusCode, header::CONTENT_TYPE};
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use spiffe::{
+    X509Bundle,
+    bundle::TrustDomainBundle,
+    client::{X509BundlesContext, X509SvidContext},
+    jose::JwkSet,
+};
+use spiffe_id::TrustDomain;
+use spiffe_tls::ServerConfigBuilder;
+use tokio::{net::TcpListener, select};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{Error, ServerConfig, crypto::CryptoProvider},
+};
+
+use crate::FederationError;
+
+type Document = Arc<RwLock<Option<Bytes>>>;
+
+/// Builds the TLS configuration of an `https_spiffe` bundle endpoint, presenting the
+/// X.509-SVID of `make_svid_stream` without requiring client certificates.
+pub async fn svid_server_config<MakeSvid, FutSvid, SvidStream, ESvid>(
+    crypto_provider: Arc<CryptoProvider>,
+    make_svid_stream: MakeSvid,
+) -> Result<ServerConfig, Error>
+where
+    MakeSvid: Fn() -> FutSvid + Send + Sync + 'static,
+    FutSvid: Future<Output = Result<SvidStream, ESvid>> + Send + 'static,
+    SvidStream: Stream<Item = X509SvidContext> + Send + 'static,
+    ESvid: Display,
+{
+    ServerConfigBuilder::new()
+        .with_crypto_provider(crypto_provider)
+        .with_x509_svid_stream(make_svid_stream)
+        .client_auth_optional()
+        .build()
+        .await
+}
+
+/// Server of the bundle endpoint of our trust domain.
+///
+/// The TLS configuration decides the profile: a WebPKI certificate for `https_web`, or an
+/// X.509-SVID from [`svid_server_config`] for `https_spiffe`.
+pub struct BundleEndpointServer {
+    trust_domain: TrustDomain<'static>,
+    tls_config: Arc<ServerConfig>,
+    refresh_hint: Option<Duration>,
+    document: Document,
+    updates: Option<BoxStream<'static, Update>>,
+}
+
+impl BundleEndpointServer {
+    /// Creates a server of the bundle of `trust_domain`, responding with `503` until a bundle is
+    /// set.
+    pub fn new(trust_domain: TrustDomain<'_>, tls_config: Arc<ServerConfig>) -> Self {
+        Self {
+            trust_domain: trust_domain.into_owned(),
+            tls_config,
+            refresh_hint: None,
+            document: Document::default(),
+            updates: None,
+        }
+    }
+
+    /// Sets the refresh hint of the bundles of [`with_bundle_streams`](Self::with_bundle_streams).
+    ///
+    /// Default: none
+    #[must_use]
+    pub fn refresh_hint(mut self, refresh_hint: Duration) -> Self {
+        self.refresh_hint = Some(refresh_hint);
+        self
+    }
+
+    /// Serves `bundle` as is.
+    pub fn with_bundle(self, bundle: &TrustDomainBundle) -> Result<Self, FederationError> {
+        let json = bundle.to_json()?;
+        *self.document.write().unwrap() = Some(json.into());
+        Ok(self)
+    }
+
+    /// Serves the authorities of our trust domain from the `fetch_x509_bundles` and
+    /// `fetch_jwt_bundles` streams of the Workload API, bumping the sequence whenever they
+    /// change.
+    ///
+    /// Serving starts once both streams yielded a bundle of our trust domain, an update missing
+    /// it or with an invalid JWT bundle is skipped. The streams are consumed by
+    /// [`serve`](Self::serve). The sequence starts at the Unix time of the first bundle, so that
+    /// it keeps increasing across restarts.
+    #[must_use]
+    pub fn with_bundle_streams<X, J>(mut self, x509_bundles: X, jwt_bundles: J) -> Self
+    where
+        X: Stream<Item = X509BundlesContext> + Send + 'static,
+        J: Stream<Item = HashMap<TrustDomain<'static>, String>> + Send + 'static,
+    {
+        let trust_domain = self.trust_domain.clone();
+        let x509_updates = x509_bundles.filter_map(move |mut x| {
+            future::ready(x.bundles.remove(&trust_domain).map(Update::X509))
+        });
+        let trust_domain = self.trust_domain.clone();
+        let jwt_updates = jwt_bundles.filter_map(move |x| {
+            let jwt_bundle = x.get(&trust_domain).and_then(|x| JwkSet::from_json(x).ok());
+            future::ready(jwt_bundle.map(Update::Jwt))
+        });
+
+        self.updates = Some(stream::select(x509_updates, jwt_updates).boxed());
+        self
+    }
+
+    /// Serves the bundle on `listener`, until accepting a connection fails.
+    pub async fn serve(self, listener: TcpListener) -> Result<(), FederationError> {
+        let acceptor = TlsAcceptor::from(self.tls_config);
+        let updates = self.updates.unwrap_or_else(|| stream::empty().boxed());
+        let mut update_document = pin!(
+            update_document(
+                self.trust_domain,
+                self.refresh_hint,
+                updates,
+                self.document.clone()
+            )
+            .fuse()
+        );
+
+        loop {
+            let (tcp, _) = select! {
+                () = &mut update_document => continue,
+                accept = listener.accept() => accept?,
+            };
+            let acceptor = acceptor.clone();
+            let document = self.document.clone();
+
+            tokio::spawn(async move {
+                let Ok(tls) = acceptor.accept(tcp).await else {
+                    return;
+                };
+                let service = service_fn(move |req| future::ready(respond(&req, &document)));
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(tls), service)
+                    .await;
+            });
+        }
+    }
+}
+
+enum Update {
+    X509(X509Bundle),
+    Jwt(JwkSet),
+}
+
+async fn update_document(
+    trust_domain: TrustDomain<'static>,
+    refresh_hint: Option<Duration>,
+    updates: impl Stream<Item = Update>,
+    document: Document,
+) {
+    let mut updates = pin!(updates);
+    let (mut x509_bundle, mut jwt_bundle) = (None, None);
+    let mut served: Option<TrustDomainBundle> = None;
+
+    while let Some(update) = updates.next().await {
+        match update {
+            Update::X509(x) => x509_bundle = Some(x),
+            Update::Jwt(x) => jwt_bundle = Some(x),
+        }
+
+        let (Some(x509_bundle), Some(jwt_bundle)) = (&x509_bundle, &jwt_bundle) else {
+            continue;
+        };
+        let Ok(mut bundle) = TrustDomainBundle::from_bundles(
+            trust_domain.clone(),
+            Some(x509_bundle.clone()),
+            jwt_bundle.clone(),
+        ) else {
+            continue;
+        };
+        bundle.set_refresh_hint(refresh_hint);
+
+        let sequence = match &served {
+            Some(x) if same_authorities(x, &bundle) => continue,
+            Some(x) => x.sequence().map_or(1, |x| x + 1),
+            None => unix_seconds(SystemTime::now()),
+        };
+        bundle.set_sequence(Some(sequence));
+
+        if let Ok(json) = bundle.to_json() {
+            *document.write().unwrap() = Some(json.into());
+            served = Some(bundle);
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
+fn same_authorities(a: &TrustDomainBundle, b: &TrustDomainBundle) -> bool {
+    a.x509_authorities() == b.x509_authorities() && a.jwt_authorities() == b.jwt_authorities()
+}
+
+fn respond(
+    req: &Request<Incoming>,
+    document: &Document,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let (status, body) = if req.method() != Method::GET {
+        (StatusCode::METHOD_NOT_ALLOWED, Bytes::new())
+    } else {
+        match &*document.read().unwrap() {
+            Some(json) => (StatusCode::OK, json.clone()),
+            None => (StatusCode::SERVICE_UNAVAILABLE, Bytes::new()),
+        }
+    };
+
+    let mut response = Response::new(Full::new(body));
+    *response.status_mut() = status;
+    if status == StatusCode::OK {
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use spiffe_id::SpiffeId;
+    use spiffe_testkit::{IssueOptions, TestCa};
+    use tokio::sync::mpsc;
+    use tokio_rustls::rustls::{RootCertStore, crypto::ring};
+
+    use super::*;
+    use crate::{BundleEndpointClient, EndpointProfile};
+
+    fn provider() -> Arc<CryptoProvider> {
+        Arc::new(ring::default_provider())
+    }
+
+    async fn listen() -> (TcpListener, String) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        (listener, format!("https://localhost:{port}/"))
+    }
+
+    #[tokio::test]
+    async fn test_https_spiffe_streams() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let endpoint_id = SpiffeId::new("spiffe://example.org/bundle-endpoint").unwrap();
+        let svid = ca.issue_x509_svid(&endpoint_id, &IssueOptions::new());
+        let tls_config = svid_server_config(provider(), move || {
+            let context = X509SvidContext {
+                svids: vec![svid.clone()],
+                crl: Vec::new(),
+                federated_bundles: HashMap::new(),
+            };
+            future::ready(Ok::<_, Infallible>(
+                stream::iter([context]).chain(stream::pending()),
+            ))
+        })
+        .await
+        .unwrap();
+
+        let (x509_tx, mut x509_rx) = mpsc::unbounded_channel();
+        let (jwt_tx, mut jwt_rx) = mpsc::unbounded_channel();
+        let x509_context = |ca: &TestCa| X509BundlesContext {
+            crl: Vec::new(),
+            bundles: HashMap::from([(ca.trust_domain().clone(), ca.x509_bundle())]),
+        };
+        let jwt_context =
+            |ca: &TestCa| HashMap::from([(ca.trust_domain().clone(), ca.jwt_bundle())]);
+        x509_tx.send(x509_context(&ca)).unwrap();
+        jwt_tx.send(jwt_context(&ca)).unwrap();
+        let start = unix_seconds(SystemTime::now());
+
+        let (listener, url) = listen().await;
+        let server = BundleEndpointServer::new(ca.trust_domain().clone(), Arc::new(tls_config))
+            .refresh_hint(Duration::from_secs(1))
+            .with_bundle_streams(
+                stream::poll_fn(move |cx| x509_rx.poll_recv(cx)),
+                stream::poll_fn(move |cx| jwt_rx.poll_recv(cx)),
+            );
+        tokio::spawn(server.serve(listener));
+
+        let profile = EndpointProfile::HttpsSpiffe {
+            endpoint_id,
+            bundle: ca.x509_bundle(),
+        };
+        let client =
+            BundleEndpointClient::new(ca.trust_domain().clone(), &url, profile, provider())
+                .unwrap()
+                .retry_interval(Duration::from_millis(10));
+        let mut watch = Box::pin(client.watch().filter_map(|x| future::ready(x.ok())));
+
+        let bundle = watch.next().await.unwrap();
+        let sequence = bundle.sequence().unwrap();
+        assert!(sequence >= start);
+        assert_eq!(bundle.refresh_hint(), Some(Duration::from_secs(1)));
+        assert_eq!(bundle.x509_bundle(), Some(ca.x509_bundle()));
+        assert_eq!(
+            bundle.jwt_bundle(),
+            JwkSet::from_json(&ca.jwt_bundle()).unwrap()
+        );
+
+        // Updates missing our trust domain keep the last bundles, and the unchanged X.509
+        // authorities do not bump the sequence on their own.
+        x509_tx
+            .send(X509BundlesContext {
+                crl: Vec::new(),
+                bundles: HashMap::new(),
+            })
+            .unwrap();
+        jwt_tx.send(HashMap::new()).unwrap();
+        x509_tx.send(x509_context(&ca)).unwrap();
+        let rotated = TestCa::new(ca.trust_domain().clone());
+        jwt_tx.send(jwt_context(&rotated)).unwrap();
+
+        let bundle = watch.next().await.unwrap();
+        assert_eq!(bundle.sequence(), Some(sequence + 1));
+        assert_eq!(bundle.x509_bundle(), Some(ca.x509_bundle()));
+        assert_eq!(
+            bundle.jwt_bundle(),
+            JwkSet::from_json(&rotated.jwt_bundle()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_https_web_static() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let (chain, key) = ca.issue_x509_chain(
+            &SpiffeId::new("spiffe://example.org/bundle-endpoint").unwrap(),
+            &IssueOptions::new().dns_name("localhost"),
+        );
+        let tls_config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(chain, key.into())
+            .unwrap();
+
+        let tls_config = Arc::new(tls_config);
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.certificate().clone()).unwrap();
+        let profile = EndpointProfile::HttpsWeb {
+            roots: Arc::new(roots),
+        };
+
+        let (listener, url) = listen().await;
+        let server = BundleEndpointServer::new(ca.trust_domain().clone(), tls_config.clone());
+        tokio::spawn(server.serve(listener));
+        let mut client =
+            BundleEndpointClient::new(ca.trust_domain().clone(), &url, profile.clone(), provider())
+                .unwrap();
+        assert!(matches!(
+            client.fetch().await,
+            Err(FederationError::Status(StatusCode::SERVICE_UNAVAILABLE))
+        ));
+
+        let mut bundle = TrustDomainBundle::new(ca.trust_domain().clone());
+        bundle.add_x509_authority(ca.certificate().clone());
+        bundle.set_sequence(Some(7));
+
+        let (listener, url) = listen().await;
+        let server = BundleEndpointServer::new(ca.trust_domain().clone(), tls_config)
+            .with_bundle(&bundle)
+            .unwrap();
+        tokio::spawn(server.serve(listener));
+        let mut client =
+            BundleEndpointClient::new(ca.trust_domain().clone(), &url, profile, provider())
+                .unwrap();
+        assert_eq!(client.fetch().await.unwrap(), bundle);
+    }
+}
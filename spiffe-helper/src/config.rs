@@ -0,0 +1,170 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use spiffe::client::Audiences;
+
+use crate::Signal;
+
+/// A JWT-SVID to keep written to disk.
+#[derive(Clone, Debug)]
+pub struct JwtSvidFile {
+    pub(crate) audiences: Audiences,
+    pub(crate) file_name: PathBuf,
+}
+
+impl JwtSvidFile {
+    pub fn new(audiences: Audiences, file_name: impl Into<PathBuf>) -> Self {
+        Self {
+            audiences,
+            file_name: file_name.into(),
+        }
+    }
+}
+
+/// Action taken after the files have been updated.
+#[derive(Clone, Debug)]
+pub enum Hook {
+    /// Runs a command and waits for it to exit.
+    Command {
+        program: OsString,
+        args: Vec<OsString>,
+    },
+    /// Sends a signal to the process whose PID is read from `pid_file`.
+    Signal { pid_file: PathBuf, signal: Signal },
+    /// Launches a command once the files have been written for the first time and sends it a
+    /// signal on later updates. The helper exits with the child, and forwards `SIGINT` and
+    /// `SIGTERM` to it.
+    Supervise {
+        program: OsString,
+        args: Vec<OsString>,
+        signal: Signal,
+    },
+}
+
+/// Configuration of a [`Helper`](crate::Helper).
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub(crate) cert_dir: PathBuf,
+    pub(crate) svid_file_name: PathBuf,
+    pub(crate) svid_key_file_name: PathBuf,
+    pub(crate) svid_bundle_file_name: PathBuf,
+    pub(crate) include_federated_bundles: bool,
+    pub(crate) jwt_svids: Vec<JwtSvidFile>,
+    pub(crate) cert_file_mode: u32,
+    pub(crate) key_file_mode: u32,
+    pub(crate) jwt_file_mode: u32,
+    pub(crate) hook: Option<Hook>,
+    pub(crate) retry_interval: Duration,
+}
+
+impl Config {
+    /// Creates a configuration writing files into `cert_dir`.
+    pub fn new(cert_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_dir: cert_dir.into(),
+            svid_file_name: "svid.pem".into(),
+            svid_key_file_name: "svid_key.pem".into(),
+            svid_bundle_file_name: "bundle.pem".into(),
+            include_federated_bundles: false,
+            jwt_svids: Vec::new(),
+            cert_file_mode: 0o644,
+            key_file_mode: 0o600,
+            jwt_file_mode: 0o600,
+            hook: None,
+            retry_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// Sets the file name of the X.509-SVID certificate chain.
+    ///
+    /// Default: `svid.pem`
+    #[must_use]
+    pub fn svid_file_name(mut self, name: impl Into<PathBuf>) -> Self {
+        self.svid_file_name = name.into();
+        self
+    }
+
+    /// Sets the file name of the X.509-SVID private key.
+    ///
+    /// Default: `svid_key.pem`
+    #[must_use]
+    pub fn svid_key_file_name(mut self, name: impl Into<PathBuf>) -> Self {
+        self.svid_key_file_name = name.into();
+        self
+    }
+
+    /// Sets the file name of the trust bundle.
+    ///
+    /// Default: `bundle.pem`
+    #[must_use]
+    pub fn svid_bundle_file_name(mut self, name: impl Into<PathBuf>) -> Self {
+        self.svid_bundle_file_name = name.into();
+        self
+    }
+
+    /// Appends the federated bundles to the trust bundle file.
+    ///
+    /// Default: `false`
+    #[must_use]
+    pub fn include_federated_bundles(mut self, include: bool) -> Self {
+        self.include_federated_bundles = include;
+        self
+    }
+
+    /// Also keeps a JWT-SVID written to disk, it is refreshed at half of its lifetime.
+    #[must_use]
+    pub fn jwt_svid(mut self, jwt_svid: JwtSvidFile) -> Self {
+        self.jwt_svids.push(jwt_svid);
+        self
+    }
+
+    /// Sets the mode of the certificate and bundle files.
+    ///
+    /// Default: `0o644`
+    #[must_use]
+    pub fn cert_file_mode(mut self, mode: u32) -> Self {
+        self.cert_file_mode = mode;
+        self
+    }
+
+    /// Sets the mode of the private key file.
+    ///
+    /// Default: `0o600`
+    #[must_use]
+    pub fn key_file_mode(mut self, mode: u32) -> Self {
+        self.key_file_mode = mode;
+        self
+    }
+
+    /// Sets the mode of the JWT-SVID files.
+    ///
+    /// Default: `0o600`
+    #[must_use]
+    pub fn jwt_file_mode(mut self, mode: u32) -> Self {
+        self.jwt_file_mode = mode;
+        self
+    }
+
+    /// Sets the action taken after each update.
+    #[must_use]
+    pub fn hook(mut self, hook: Hook) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
+    /// Sets the delay before fetching again after the Workload API stream failed.
+    ///
+    /// Default: `5s`
+    #[must_use]
+    pub fn retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    pub(crate) fn path(&self, file_name: &Path) -> PathBuf {
+        self.cert_dir.join(file_name)
+    }
+}
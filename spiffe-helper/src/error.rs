@@ -0,0 +1,34 @@
+use std::{io::Error as IoError, path::PathBuf, process::ExitStatus};
+
+use spiffe::{ConnectError, SpiffeError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HelperError {
+    #[error("{0}")]
+    Connect(#[from] ConnectError),
+
+    #[error("workload API call failed: {0}")]
+    Status(#[from] tonic::Status),
+
+    #[error("workload API stream closed")]
+    Closed,
+
+    #[error("workload API returned no SVID")]
+    NoSvid,
+
+    #[error("invalid SVID: {0}")]
+    Svid(#[from] SpiffeError),
+
+    #[error("failed to write `{}`: {error}", path.display())]
+    Write { path: PathBuf, error: IoError },
+
+    #[error("failed to run hook: {0}")]
+    Hook(IoError),
+
+    #[error("hook exited with {0}")]
+    HookFailed(ExitStatus),
+
+    #[error("unknown signal name")]
+    UnknownSignal,
+}
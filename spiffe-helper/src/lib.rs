@@ -0,0 +1,433 @@
+//! Keeps X.509-SVIDs, trust bundles and JWT-SVIDs from the Workload API written to disk, for
+//! workloads that can only read their credentials from files, in the spirit of SPIRE's
+//! `spiffe-helper`.
+//!
+//! Files are replaced atomically on every rotation, after which the configured [`Hook`] runs.
+
+mod config;
+mod error;
+mod signal;
+mod writer;
+
+use std::{
+    fs,
+    future::{pending, poll_fn},
+    io::ErrorKind,
+    pin::Pin,
+    process::ExitStatus,
+    time::{Duration, SystemTime},
+};
+
+use futures_core::Stream;
+use spiffe::{
+    JwtSvid, SpiffeError,
+    client::{SpiffeWorkloadApiClient, X509SvidContext, X509SvidContextStream},
+};
+use tokio::{
+    process::{Child, Command},
+    signal::unix::{SignalKind, signal},
+    time::{Instant, sleep_until},
+};
+use tonic::transport::Channel;
+
+pub use self::{
+    config::{Config, Hook, JwtSvidFile},
+    error::HelperError,
+    signal::Signal,
+};
+
+/// Refreshing a JWT-SVID is not attempted more often than this.
+const MIN_JWT_REFRESH: Duration = Duration::from_secs(1);
+
+pub struct Helper {
+    client: SpiffeWorkloadApiClient<Channel>,
+    config: Config,
+}
+
+impl Helper {
+    pub fn new(client: SpiffeWorkloadApiClient<Channel>, config: Config) -> Self {
+        Self { client, config }
+    }
+
+    /// Writes the current SVIDs and bundles once, then runs the hook.
+    ///
+    /// [`Hook::Supervise`] is not supported in this mode, the child is not launched.
+    pub async fn run_once(&self) -> Result<(), HelperError> {
+        let mut stream = self.client.fetch_x509_svid().await?;
+        let context = next(&mut stream).await.ok_or(HelperError::Closed)?;
+
+        writer::write_x509_context(&self.config, &context)?;
+        self.write_jwt_svids().await?;
+
+        match &self.config.hook {
+            Some(Hook::Supervise { .. }) | None => Ok(()),
+            Some(hook) => self.notify(hook, None).await,
+        }
+    }
+
+    /// Keeps the files up to date until an error occurs, or the supervised child exits.
+    ///
+    /// Failures of the Workload API are retried after [`Config::retry_interval`], failing to
+    /// write files or to run the hook stops the helper.
+    ///
+    /// Returns the exit status of the child when running with [`Hook::Supervise`].
+    pub async fn run(&self) -> Result<Option<ExitStatus>, HelperError> {
+        let mut stream = None;
+        let mut retry_at = Some(Instant::now());
+        let mut jwt_refresh_at = None;
+        let mut child = None;
+
+        let supervise = matches!(self.config.hook, Some(Hook::Supervise { .. }));
+        let mut terminate = supervise
+            .then(|| signal(SignalKind::terminate()))
+            .transpose()
+            .map_err(HelperError::Hook)?;
+        let mut interrupt = supervise
+            .then(|| signal(SignalKind::interrupt()))
+            .transpose()
+            .map_err(HelperError::Hook)?;
+
+        loop {
+            tokio::select! {
+                status = wait_child(&mut child) => {
+                    return status.map(Some).map_err(HelperError::Hook);
+                }
+                Some(()) = recv_signal(&mut terminate) => forward(child.as_ref().and_then(Child::id), Signal::TERM)?,
+                Some(()) = recv_signal(&mut interrupt) => forward(child.as_ref().and_then(Child::id), Signal::INT)?,
+                () = sleep_until_opt(retry_at) => {
+                    retry_at = None;
+                    match self.client.fetch_x509_svid().await {
+                        Ok(x) => stream = Some(x),
+                        Err(_) => retry_at = Some(Instant::now() + self.config.retry_interval),
+                    }
+                }
+                context = next_opt(&mut stream) => {
+                    let Some(context) = context else {
+                        stream = None;
+                        retry_at = Some(Instant::now() + self.config.retry_interval);
+                        continue;
+                    };
+
+                    // the hook runs once the JWT-SVIDs are written as well
+                    writer::write_x509_context(&self.config, &context)?;
+                    jwt_refresh_at = Some(Instant::now());
+                }
+                () = sleep_until_opt(jwt_refresh_at) => {
+                    match self.write_jwt_svids().await {
+                        Ok(at) => {
+                            jwt_refresh_at = at;
+                            self.after_update(&mut child).await?;
+                        }
+                        Err(HelperError::Status(_) | HelperError::NoSvid) => {
+                            jwt_refresh_at = Some(Instant::now() + self.config.retry_interval);
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes every configured JWT-SVID, returns when they should be refreshed.
+    async fn write_jwt_svids(&self) -> Result<Option<Instant>, HelperError> {
+        let mut refresh_at = None::<Instant>;
+
+        for jwt in &self.config.jwt_svids {
+            let svids = self.client.fetch_jwt_svid(&jwt.audiences, None).await?;
+            let svid = svids.first().ok_or(HelperError::NoSvid)?;
+
+            writer::write_jwt_svid(&self.config, &jwt.file_name, svid)?;
+
+            let at = Instant::now() + refresh_delay(svid)?;
+            refresh_at = Some(refresh_at.map_or(at, |x| x.min(at)));
+        }
+
+        Ok(refresh_at)
+    }
+
+    async fn after_update(&self, child: &mut Option<Child>) -> Result<(), HelperError> {
+        match &self.config.hook {
+            Some(Hook::Supervise { program, args, .. }) if child.is_none() => {
+                let spawned = Command::new(program)
+                    .args(args)
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(HelperError::Hook)?;
+                *child = Some(spawned);
+
+                Ok(())
+            }
+            Some(hook) => self.notify(hook, child.as_ref()).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn notify(&self, hook: &Hook, child: Option<&Child>) -> Result<(), HelperError> {
+        match hook {
+            Hook::Command { program, args } => {
+                let status = Command::new(program)
+                    .args(args)
+                    .status()
+                    .await
+                    .map_err(HelperError::Hook)?;
+
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(HelperError::HookFailed(status))
+                }
+            }
+            Hook::Signal { pid_file, signal } => {
+                let pid = fs::read_to_string(pid_file).map_err(HelperError::Hook)?;
+                let pid = pid
+                    .trim()
+                    .parse()
+                    .map_err(|_| HelperError::Hook(ErrorKind::InvalidData.into()))?;
+
+                signal.send(pid).map_err(HelperError::Hook)
+            }
+            Hook::Supervise { signal, .. } => forward(child.and_then(Child::id), *signal),
+        }
+    }
+}
+
+fn forward(pid: Option<u32>, signal: Signal) -> Result<(), HelperError> {
+    match pid {
+        Some(pid) => signal.send(pid).map_err(HelperError::Hook),
+        None => Ok(()),
+    }
+}
+
+/// Half of the remaining lifetime of a JWT-SVID, read from its `exp` claim.
+fn refresh_delay(svid: &JwtSvid) -> Result<Duration, HelperError> {
+    let expiry = svid
+        .expiry()
+        .map_err(|_| HelperError::Svid(SpiffeError::InvalidJwtSvid))?;
+    let remaining = expiry.duration_since(SystemTime::now()).unwrap_or_default();
+
+    Ok((remaining / 2).max(MIN_JWT_REFRESH))
+}
+
+async fn next(stream: &mut X509SvidContextStream) -> Option<X509SvidContext> {
+    poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+}
+
+async fn next_opt(stream: &mut Option<X509SvidContextStream>) -> Option<X509SvidContext> {
+    match stream {
+        Some(stream) => next(stream).await,
+        None => pending().await,
+    }
+}
+
+async fn sleep_until_opt(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => pending().await,
+    }
+}
+
+async fn wait_child(child: &mut Option<Child>) -> std::io::Result<ExitStatus> {
+    match child {
+        Some(child) => child.wait().await,
+        None => pending().await,
+    }
+}
+
+async fn recv_signal(signal: &mut Option<tokio::signal::unix::Signal>) -> Option<()> {
+    match signal {
+        Some(signal) => signal.recv().await,
+        None => pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        os::unix::{fs::PermissionsExt, process::ExitStatusExt},
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use spiffe::{X509Svid, client::Audiences};
+    use spiffe_id::{SpiffeId, TrustDomain};
+    use spiffe_proto::{JwtSvidResponse, X509Svid as ProtoX509Svid, X509SvidResponse};
+    use spiffe_testkit::{FakeWorkloadApi, IssueOptions, TestCa, TestServer};
+    use tonic::Status;
+
+    use super::*;
+
+    /// Creates an empty directory and serves an X.509-SVID, with its leaf as bundle, and a
+    /// JWT-SVID valid for an hour.
+    fn setup(name: &str) -> (PathBuf, TestServer) {
+        let dir = env::temp_dir().join(format!("spiffe-helper-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let spiffe_id = SpiffeId::new("spiffe://example.org/workload").unwrap();
+        let svid = ca.issue_x509_svid(&spiffe_id, &IssueOptions::new());
+        let jwt = ca.issue_jwt_svid(
+            &spiffe_id,
+            &["db"],
+            SystemTime::now() + Duration::from_secs(3600),
+        );
+
+        let server = TestServer::unix(Arc::new(FakeWorkloadApi::new())).unwrap();
+        server.api().x509_svid().rotate(X509SvidResponse {
+            svids: vec![ProtoX509Svid {
+                spiffe_id: spiffe_id.to_string(),
+                x509_svid: svid.svid()[0].to_vec().into(),
+                x509_svid_key: svid.key().secret_der().to_vec().into(),
+                bundle: svid.svid()[0].to_vec().into(),
+                hint: String::new(),
+            }],
+            ..Default::default()
+        });
+        server.api().jwt_svid().respond(JwtSvidResponse {
+            svids: vec![spiffe_proto::JwtSvid {
+                spiffe_id: spiffe_id.to_string(),
+                svid: jwt.svid().into(),
+                hint: String::new(),
+            }],
+        });
+
+        (dir, server)
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    #[tokio::test]
+    async fn test_run_once() {
+        let (dir, server) = setup("once");
+        let config = Config::new(&dir)
+            .cert_file_mode(0o640)
+            .jwt_svid(JwtSvidFile::new(Audiences::new("db").unwrap(), "jwt.token"))
+            .hook(Hook::Command {
+                program: "cp".into(),
+                args: vec![dir.join("svid.pem").into(), dir.join("hook.pem").into()],
+            });
+
+        Helper::new(server.client(), config)
+            .run_once()
+            .await
+            .unwrap();
+
+        let cert = fs::read_to_string(dir.join("svid.pem")).unwrap();
+        let key = fs::read_to_string(dir.join("svid_key.pem")).unwrap();
+        let svid = X509Svid::from_pem(cert.as_bytes(), key.as_bytes()).unwrap();
+        assert_eq!(svid.spiffe_id().as_str(), "spiffe://example.org/workload");
+        // the fake agent sends the leaf as bundle
+        assert_eq!(fs::read_to_string(dir.join("bundle.pem")).unwrap(), cert);
+        assert_eq!(fs::read_to_string(dir.join("hook.pem")).unwrap(), cert);
+        assert_eq!(mode(&dir.join("svid.pem")), 0o640);
+        assert_eq!(mode(&dir.join("svid_key.pem")), 0o600);
+
+        let token = fs::read_to_string(dir.join("jwt.token")).unwrap();
+        let audiences = Audiences::new("db").unwrap();
+        let jwt = server
+            .client()
+            .fetch_jwt_svid(&audiences, None)
+            .await
+            .unwrap();
+        assert_eq!(token, jwt[0].svid());
+        assert!(refresh_delay(&jwt[0]).unwrap() > Duration::from_secs(1700));
+        assert_eq!(mode(&dir.join("jwt.token")), 0o600);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_signal_pid_file() {
+        let (dir, server) = setup("signal");
+        let client = server.client();
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        fs::write(dir.join("app.pid"), child.id().to_string()).unwrap();
+
+        let config = Config::new(&dir).hook(Hook::Signal {
+            pid_file: dir.join("app.pid"),
+            signal: Signal::TERM,
+        });
+        Helper::new(client.clone(), config)
+            .run_once()
+            .await
+            .unwrap();
+
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+
+        // 0 would signal the process group of the helper
+        fs::write(dir.join("app.pid"), "0").unwrap();
+        let config = Config::new(&dir).hook(Hook::Signal {
+            pid_file: dir.join("app.pid"),
+            signal: Signal::TERM,
+        });
+        let error = Helper::new(client, config).run_once().await.unwrap_err();
+        assert!(matches!(error, HelperError::Hook(e) if e.kind() == ErrorKind::InvalidData));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_supervise() {
+        let (dir, server) = setup("supervise");
+
+        // the child must only start once the files exist
+        let script = format!("test -s {}/svid_key.pem && exit 7", dir.display());
+        let config = Config::new(&dir).hook(Hook::Supervise {
+            program: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            signal: Signal::HUP,
+        });
+
+        let status = Helper::new(server.client(), config)
+            .run()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.code(), Some(7));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_jwt_retry() {
+        let (dir, server) = setup("jwt-retry");
+        server
+            .api()
+            .jwt_svid()
+            .fail_next(Status::unavailable("agent restarting"));
+
+        let script = format!("test -s {}/jwt.token && exit 7", dir.display());
+        let config = Config::new(&dir)
+            .jwt_svid(JwtSvidFile::new(Audiences::new("db").unwrap(), "jwt.token"))
+            .retry_interval(Duration::from_millis(10))
+            .hook(Hook::Supervise {
+                program: "sh".into(),
+                args: vec!["-c".into(), script.into()],
+                signal: Signal::HUP,
+            });
+
+        let status = Helper::new(server.client(), config)
+            .run()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.code(), Some(7));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!("SIGHUP".parse::<Signal>().unwrap(), Signal::HUP);
+        assert_eq!("USR1".parse::<Signal>().unwrap(), Signal::USR1);
+        assert_eq!(Signal::TERM.to_string(), "SIGTERM");
+        assert!("SIGFOO".parse::<Signal>().is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{env, ffi::OsString, process::ExitCode};
+
+use spiffe::client::{Audiences, SpiffeWorkloadApiClient};
+use spiffe_helper::{Config, Helper, Hook, JwtSvidFile, Signal};
+
+const USAGE: &str = "\
+usage: spiffe-helper --cert-dir DIR [OPTIONS] [-- COMMAND [ARGS...]]
+
+Keeps the X.509-SVID, its key and trust bundle from the Workload API written to DIR.
+When COMMAND is given, it is launched once the files exist and signaled on every update.
+
+options:
+    --endpoint ADDR         Workload API address, default: $SPIFFE_ENDPOINT_SOCKET
+    --cert-dir DIR          directory to write files into
+    --svid-file NAME        default: svid.pem
+    --svid-key-file NAME    default: svid_key.pem
+    --bundle-file NAME      default: bundle.pem
+    --include-federated     append federated bundles to the bundle file
+    --jwt FILE=AUDIENCE     also write a JWT-SVID for AUDIENCE to FILE, repeatable
+    --cert-mode MODE        octal mode of certificate files, default: 644
+    --key-mode MODE         octal mode of the key file, default: 600
+    --jwt-mode MODE         octal mode of JWT-SVID files, default: 600
+    --cmd PROGRAM [ARGS]    run PROGRAM after each update, ARGS split on whitespace
+    --pid-file FILE         signal the process whose PID is in FILE after each update
+    --renew-signal SIGNAL   signal sent on update, default: SIGHUP
+    --once                  write files once and exit";
+
+struct Args {
+    endpoint: Option<String>,
+    config: Config,
+    once: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args_os().skip(1);
+
+    let mut endpoint = None;
+    let mut cert_dir = None;
+    let mut svid_file = None;
+    let mut svid_key_file = None;
+    let mut bundle_file = None;
+    let mut include_federated = false;
+    let mut jwt_svids = Vec::new();
+    let mut cert_mode = None;
+    let mut key_mode = None;
+    let mut jwt_mode = None;
+    let mut cmd = None;
+    let mut pid_file = None;
+    let mut signal = Signal::HUP;
+    let mut child = Vec::new();
+    let mut once = false;
+
+    while let Some(arg) = args.next() {
+        let arg = arg
+            .into_string()
+            .map_err(|x| format!("invalid argument `{}`", x.to_string_lossy()))?;
+
+        let mut value = || {
+            args.next()
+                .and_then(|x| x.into_string().ok())
+                .ok_or_else(|| format!("missing value for `{arg}`"))
+        };
+
+        match arg.as_str() {
+            "--endpoint" => endpoint = Some(value()?),
+            "--cert-dir" => cert_dir = Some(value()?),
+            "--svid-file" => svid_file = Some(value()?),
+            "--svid-key-file" => svid_key_file = Some(value()?),
+            "--bundle-file" => bundle_file = Some(value()?),
+            "--include-federated" => include_federated = true,
+            "--jwt" => {
+                let x = value()?;
+                let (file, audience) = x
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid JWT-SVID `{x}`, expected FILE=AUDIENCE"))?;
+                let audiences = Audiences::new(audience).map_err(|e| e.to_string())?;
+                jwt_svids.push(JwtSvidFile::new(audiences, file));
+            }
+            "--cert-mode" => cert_mode = Some(parse_mode(&value()?)?),
+            "--key-mode" => key_mode = Some(parse_mode(&value()?)?),
+            "--jwt-mode" => jwt_mode = Some(parse_mode(&value()?)?),
+            "--cmd" => cmd = Some(value()?),
+            "--pid-file" => pid_file = Some(value()?),
+            "--renew-signal" => {
+                let x = value()?;
+                signal = x.parse().map_err(|_| format!("unknown signal `{x}`"))?;
+            }
+            "--once" => once = true,
+            "--" => {
+                child.extend(args.by_ref());
+                break;
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("unknown argument `{arg}`\n\n{USAGE}")),
+        }
+    }
+
+    let cert_dir = cert_dir.ok_or_else(|| format!("`--cert-dir` is required\n\n{USAGE}"))?;
+    let mut config = Config::new(cert_dir).include_federated_bundles(include_federated);
+    if let Some(x) = svid_file {
+        config = config.svid_file_name(x);
+    }
+    if let Some(x) = svid_key_file {
+        config = config.svid_key_file_name(x);
+    }
+    if let Some(x) = bundle_file {
+        config = config.svid_bundle_file_name(x);
+    }
+    if let Some(x) = cert_mode {
+        config = config.cert_file_mode(x);
+    }
+    if let Some(x) = key_mode {
+        config = config.key_file_mode(x);
+    }
+    if let Some(x) = jwt_mode {
+        config = config.jwt_file_mode(x);
+    }
+    for jwt in jwt_svids {
+        config = config.jwt_svid(jwt);
+    }
+
+    let hook = match (cmd, pid_file, child.is_empty()) {
+        (None, None, true) => None,
+        (Some(cmd), None, true) => {
+            let mut words = cmd.split_whitespace().map(OsString::from);
+            let program = words.next().ok_or("`--cmd` is empty")?;
+
+            Some(Hook::Command {
+                program,
+                args: words.collect(),
+            })
+        }
+        (None, Some(pid_file), true) => Some(Hook::Signal {
+            pid_file: pid_file.into(),
+            signal,
+        }),
+        (None, None, false) => {
+            let mut child = child.into_iter();
+
+            Some(Hook::Supervise {
+                program: child.next().expect("checked non-empty"),
+                args: child.collect(),
+                signal,
+            })
+        }
+        _ => return Err("`--cmd`, `--pid-file` and COMMAND are mutually exclusive".into()),
+    };
+    if once && matches!(hook, Some(Hook::Supervise { .. })) {
+        return Err(format!("`--once` cannot be used with COMMAND\n\n{USAGE}"));
+    }
+    if let Some(hook) = hook {
+        config = config.hook(hook);
+    }
+
+    Ok(Args {
+        endpoint,
+        config,
+        once,
+    })
+}
+
+fn parse_mode(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|x| *x <= 0o7777)
+        .ok_or_else(|| format!("invalid file mode `{mode}`"))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let client = match &args.endpoint {
+        Some(addr) => SpiffeWorkloadApiClient::connect(addr).await,
+        None => SpiffeWorkloadApiClient::connect_default().await,
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("spiffe-helper: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let helper = Helper::new(client, args.config);
+    let result = if args.once {
+        helper.run_once().await.map(|()| None)
+    } else {
+        helper.run().await
+    };
+
+    match result {
+        // mirror the exit code of the supervised child
+        Ok(Some(status)) => status
+            .code()
+            .and_then(|x| u8::try_from(x).ok())
+            .map_or(ExitCode::FAILURE, ExitCode::from),
+        Ok(None) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("spiffe-helper: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    str::FromStr,
+};
+
+use crate::HelperError;
+
+/// A Unix signal sent to a process after an update.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Signal(libc::c_int);
+
+impl Signal {
+    pub const HUP: Self = Self(libc::SIGHUP);
+    pub const INT: Self = Self(libc::SIGINT);
+    pub const QUIT: Self = Self(libc::SIGQUIT);
+    pub const TERM: Self = Self(libc::SIGTERM);
+    pub const USR1: Self = Self(libc::SIGUSR1);
+    pub const USR2: Self = Self(libc::SIGUSR2);
+
+    const NAMES: [(&str, Self); 6] = [
+        ("HUP", Self::HUP),
+        ("INT", Self::INT),
+        ("QUIT", Self::QUIT),
+        ("TERM", Self::TERM),
+        ("USR1", Self::USR1),
+        ("USR2", Self::USR2),
+    ];
+
+    #[inline]
+    pub const fn as_raw(self) -> libc::c_int {
+        self.0
+    }
+
+    pub(crate) fn send(self, pid: u32) -> IoResult<()> {
+        // `kill(0, _)` would signal the whole process group of the helper
+        if pid == 0 {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        let pid = libc::pid_t::try_from(pid).map_err(IoError::other)?;
+
+        // SAFETY: `kill` has no memory safety requirements
+        if unsafe { libc::kill(pid, self.0) } == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+}
+
+impl FromStr for Signal {
+    type Err = HelperError;
+
+    /// Parses a signal name, with or without the `SIG` prefix, e.g. `SIGHUP` or `HUP`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_prefix("SIG").unwrap_or(s);
+
+        Self::NAMES
+            .iter()
+            .find(|(x, _)| *x == name)
+            .map(|(_, signal)| *signal)
+            .ok_or(HelperError::UnknownSignal)
+    }
+}
+
+impl Display for Signal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match Self::NAMES.iter().find(|(_, x)| x == self) {
+            Some((name, _)) => write!(f, "SIG{name}"),
+            None => write!(f, "signal {}", self.0),
+        }
+    }
+}
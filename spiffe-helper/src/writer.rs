@@ -0,0 +1,105 @@
+use std::{
+    fs::{self, File, OpenOptions, Permissions},
+    io::{Result as IoResult, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::Path,
+    process,
+};
+
+use spiffe::{JwtSvid, client::X509SvidContext};
+
+use crate::{Config, HelperError};
+
+/// Writes the default SVID of `context`, its key and the trust bundle.
+pub(crate) fn write_x509_context(
+    config: &Config,
+    context: &X509SvidContext,
+) -> Result<(), HelperError> {
+    let svid = context.default_svid().ok_or(HelperError::NoSvid)?;
+
+    let (chain, key) = svid.to_pem();
+    let mut bundle = svid.bundle().to_pem();
+    if config.include_federated_bundles {
+        // sorted, so an unchanged context yields the same file
+        let mut federated = context.federated_bundles.iter().collect::<Vec<_>>();
+        federated.sort_unstable_by_key(|(td, _)| td.as_str());
+
+        for (_, federated) in federated {
+            bundle.push_str(&federated.to_pem());
+        }
+    }
+
+    // the key is written first, so a certificate on disk never refers to a missing key
+    write(
+        config,
+        &config.svid_key_file_name,
+        key.expose_secret().as_bytes(),
+        config.key_file_mode,
+    )?;
+    write(
+        config,
+        &config.svid_file_name,
+        chain.as_bytes(),
+        config.cert_file_mode,
+    )?;
+    write(
+        config,
+        &config.svid_bundle_file_name,
+        bundle.as_bytes(),
+        config.cert_file_mode,
+    )
+}
+
+pub(crate) fn write_jwt_svid(
+    config: &Config,
+    file_name: &Path,
+    svid: &JwtSvid,
+) -> Result<(), HelperError> {
+    write(
+        config,
+        file_name,
+        svid.svid().as_bytes(),
+        config.jwt_file_mode,
+    )
+}
+
+fn write(config: &Config, file_name: &Path, contents: &[u8], mode: u32) -> Result<(), HelperError> {
+    let path = config.path(file_name);
+
+    write_atomic(&path, contents, mode).map_err(|error| HelperError::Write { path, error })
+}
+
+/// Replaces the file at `path` so readers only ever observe the old or the new contents.
+fn write_atomic(path: &Path, contents: &[u8], mode: u32) -> IoResult<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+    tmp_name.push(format!(".{}.tmp", process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(mode)
+            .open(&tmp_path)?;
+        // the mode passed to `open` is masked by the umask
+        file.set_permissions(Permissions::from_mode(mode))?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        fs::rename(&tmp_path, path)?;
+
+        // persist the rename itself
+        if let Some(dir) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
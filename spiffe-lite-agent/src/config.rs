@@ -0,0 +1,288 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde_json::{Map, Value as JsonValue};
+use spiffe::server::PeerInfo;
+use spiffe_id::{SpiffeId, TrustDomain};
+
+use crate::AgentError;
+
+/// Rule granting a SPIFFE ID to the callers matching all of its selectors.
+///
+/// An entry without selectors matches every caller of the socket.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub(crate) spiffe_id: SpiffeId,
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
+    pub(crate) exe: Option<PathBuf>,
+}
+
+impl Entry {
+    pub fn new(spiffe_id: SpiffeId) -> Self {
+        Self {
+            spiffe_id,
+            uid: None,
+            gid: None,
+            exe: None,
+        }
+    }
+
+    /// Only matches callers running as user `uid`.
+    #[must_use]
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Only matches callers running as group `gid`.
+    #[must_use]
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Only matches callers running the executable at `exe`.
+    #[must_use]
+    pub fn exe(mut self, exe: impl Into<PathBuf>) -> Self {
+        self.exe = Some(exe.into());
+        self
+    }
+
+    #[inline]
+    pub fn spiffe_id(&self) -> &SpiffeId {
+        &self.spiffe_id
+    }
+
+    pub fn matches(&self, peer: &PeerInfo) -> bool {
+        self.uid.is_none_or(|x| x == peer.uid())
+            && self.gid.is_none_or(|x| x == peer.gid())
+            && self.exe.as_deref().is_none_or(|x| peer.exe() == Some(x))
+    }
+}
+
+/// Configuration of an [`Agent`](crate::Agent).
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub(crate) trust_domain: TrustDomain<'static>,
+    pub(crate) socket_path: PathBuf,
+    pub(crate) data_dir: Option<PathBuf>,
+    pub(crate) x509_svid_ttl: Duration,
+    pub(crate) jwt_svid_ttl: Duration,
+    pub(crate) entries: Vec<Entry>,
+}
+
+impl Config {
+    /// Creates a configuration serving `trust_domain` on `socket_path`.
+    pub fn new(trust_domain: TrustDomain<'_>, socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            trust_domain: trust_domain.into_owned(),
+            socket_path: socket_path.into(),
+            data_dir: None,
+            x509_svid_ttl: Duration::from_secs(3_600),
+            jwt_svid_ttl: Duration::from_secs(300),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Parses a JSON configuration file.
+    ///
+    /// ```json
+    /// {
+    ///     "trust_domain": "example.org",
+    ///     "socket_path": "/tmp/spiffe-lite-agent.sock",
+    ///     "data_dir": "/var/lib/spiffe-lite-agent",
+    ///     "x509_svid_ttl": 3600,
+    ///     "jwt_svid_ttl": 300,
+    ///     "entries": [
+    ///         { "spiffe_id": "spiffe://example.org/web", "uid": 1000, "exe": "/usr/bin/web" }
+    ///     ]
+    /// }
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, AgentError> {
+        let value: JsonValue =
+            serde_json::from_str(json).map_err(|e| AgentError::Config(e.to_string()))?;
+        let config = object(&value, "configuration")?;
+
+        let trust_domain = TrustDomain::new(required_str(config, "trust_domain")?)
+            .map_err(|e| AgentError::Config(format!("invalid `trust_domain`: {e}")))?;
+        let mut this = Self::new(trust_domain, required_str(config, "socket_path")?);
+
+        if let Some(x) = optional(config, "data_dir", JsonValue::as_str)? {
+            this = this.data_dir(x);
+        }
+        if let Some(x) = optional(config, "x509_svid_ttl", JsonValue::as_u64)? {
+            this = this.x509_svid_ttl(Duration::from_secs(x));
+        }
+        if let Some(x) = optional(config, "jwt_svid_ttl", JsonValue::as_u64)? {
+            this = this.jwt_svid_ttl(Duration::from_secs(x));
+        }
+        for entry in optional(config, "entries", JsonValue::as_array)?.unwrap_or(&Vec::new()) {
+            this = this.entry(parse_entry(entry)?);
+        }
+
+        this.check()?;
+        Ok(this)
+    }
+
+    /// Persists the CA in `data_dir`, it is generated on every start otherwise.
+    ///
+    /// A persisted CA is valid for a year, after which the agent fails to start until it is
+    /// removed.
+    ///
+    /// Default: not persisted
+    #[must_use]
+    pub fn data_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the lifetime of X.509-SVIDs and WIT-SVIDs, they are rotated at half of it.
+    ///
+    /// Default: an hour
+    #[must_use]
+    pub fn x509_svid_ttl(mut self, ttl: Duration) -> Self {
+        self.x509_svid_ttl = ttl;
+        self
+    }
+
+    /// Sets the lifetime of JWT-SVIDs.
+    ///
+    /// Default: 5 minutes
+    #[must_use]
+    pub fn jwt_svid_ttl(mut self, ttl: Duration) -> Self {
+        self.jwt_svid_ttl = ttl;
+        self
+    }
+
+    /// Adds a registration entry.
+    #[must_use]
+    pub fn entry(mut self, entry: Entry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    #[inline]
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    pub(crate) fn check(&self) -> Result<(), AgentError> {
+        if self.x509_svid_ttl < Duration::from_secs(2) || self.jwt_svid_ttl.is_zero() {
+            return Err(AgentError::Config("SVID lifetimes are too short".into()));
+        }
+
+        match self
+            .entries
+            .iter()
+            .find(|x| x.spiffe_id.trust_domain() != self.trust_domain)
+        {
+            Some(entry) => Err(AgentError::Config(format!(
+                "`{}` is not in trust domain `{}`",
+                entry.spiffe_id, self.trust_domain
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn object<'a>(value: &'a JsonValue, what: &str) -> Result<&'a Map<String, JsonValue>, AgentError> {
+    value
+        .as_object()
+        .ok_or_else(|| AgentError::Config(format!("{what} must be an object")))
+}
+
+fn optional<'a, T>(
+    object: &'a Map<String, JsonValue>,
+    key: &str,
+    cast: impl Fn(&'a JsonValue) -> Option<T>,
+) -> Result<Option<T>, AgentError> {
+    match object.get(key) {
+        None | Some(JsonValue::Null) => Ok(None),
+        Some(value) => cast(value)
+            .map(Some)
+            .ok_or_else(|| AgentError::Config(format!("invalid `{key}`"))),
+    }
+}
+
+fn required_str<'a>(object: &'a Map<String, JsonValue>, key: &str) -> Result<&'a str, AgentError> {
+    optional(object, key, JsonValue::as_str)?
+        .ok_or_else(|| AgentError::Config(format!("`{key}` is required")))
+}
+
+fn parse_entry(value: &JsonValue) -> Result<Entry, AgentError> {
+    let entry = object(value, "an entry")?;
+    let id = |x: u64| u32::try_from(x).ok();
+
+    let spiffe_id = SpiffeId::new(required_str(entry, "spiffe_id")?)
+        .map_err(|e| AgentError::Config(format!("invalid `spiffe_id`: {e}")))?;
+    let mut this = Entry::new(spiffe_id);
+
+    if let Some(x) = optional(entry, "uid", |x| x.as_u64().and_then(id))? {
+        this = this.uid(x);
+    }
+    if let Some(x) = optional(entry, "gid", |x| x.as_u64().and_then(id))? {
+        this = this.gid(x);
+    }
+    if let Some(x) = optional(entry, "exe", JsonValue::as_str)? {
+        this = this.exe(x);
+    }
+
+    Ok(this)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        let config = Config::from_json(
+            r#"{
+                "trust_domain": "example.org",
+                "socket_path": "/tmp/agent.sock",
+                "jwt_svid_ttl": 60,
+                "entries": [
+                    { "spiffe_id": "spiffe://example.org/web", "uid": 1000, "exe": "/usr/bin/web" },
+                    { "spiffe_id": "spiffe://example.org/any" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.trust_domain,
+            TrustDomain::new("example.org").unwrap()
+        );
+        assert_eq!(config.socket_path(), Path::new("/tmp/agent.sock"));
+        assert_eq!(config.jwt_svid_ttl, Duration::from_secs(60));
+        assert_eq!(config.x509_svid_ttl, Duration::from_secs(3_600));
+        assert_eq!(config.entries[0].uid, Some(1000));
+        assert_eq!(
+            config.entries[0].exe.as_deref(),
+            Some(Path::new("/usr/bin/web"))
+        );
+        assert_eq!(
+            config.entries[1].spiffe_id().as_str(),
+            "spiffe://example.org/any"
+        );
+
+        for invalid in [
+            r#"{ "socket_path": "/tmp/agent.sock" }"#,
+            r#"{ "trust_domain": "example.org", "socket_path": 1 }"#,
+            r#"{ "trust_domain": "example.org", "socket_path": "a", "entries": [
+                { "spiffe_id": "spiffe://other.org/web" }
+            ] }"#,
+            r#"{ "trust_domain": "example.org", "socket_path": "a", "entries": [
+                { "spiffe_id": "spiffe://example.org/web", "uid": -1 }
+            ] }"#,
+        ] {
+            assert!(matches!(
+                Config::from_json(invalid),
+                Err(AgentError::Config(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use std::{io::Error as IoError, path::PathBuf};
+
+use spiffe_ca::CaError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    #[error("failed to access `{}`: {error}", path.display())]
+    Io { path: PathBuf, error: IoError },
+
+    #[error("`{}` exists and is not a socket", .0.display())]
+    NotASocket(PathBuf),
+
+    #[error("failed to create the CA: {0}")]
+    Ca(#[from] CaError),
+
+    #[error("invalid CA stored in `{}`: {error}", path.display())]
+    InvalidCa { path: PathBuf, error: CaError },
+
+    #[error("failed to serve the Workload API: {0}")]
+    Transport(#[from] tonic::transport::Error),
+}
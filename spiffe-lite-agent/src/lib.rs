@@ -0,0 +1,450 @@
+//! Single-binary SPIFFE agent for developer machines and CI, standing in for a SPIRE
+//! deployment.
+//!
+//! Callers of the Workload API socket are attested with their Unix credentials and granted the
+//! SPIFFE IDs of the configured [`Entry`]s they match. SVIDs are minted by a local CA, which is
+//! persisted in the data directory when one is configured.
+
+mod config;
+mod error;
+
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    os::unix::fs::{FileTypeExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use rustls_pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use serde_json::{Map, Value as JsonValue};
+use spiffe::{
+    JwtSvid, WitSvid, X509Svid,
+    client::{JwtSvidRequest, WitSvidRequest, X509BundlesContext, X509SvidContext},
+    jose::SigningKey,
+    jwt_svid::{JwtBundles, JwtSvidClaims, parse_jwt_bundles},
+    server::{Caller, IdentityProvider, UnixIncoming, WorkloadApiServer},
+};
+use spiffe_ca::{Ca, CaError, IssueOptions};
+use spiffe_id::{SpiffeId, TrustDomain};
+use tonic::{Result, Status};
+
+pub use self::{
+    config::{Config, Entry},
+    error::AgentError,
+};
+
+const CA_FILE: &str = "ca.der";
+const CA_KEY_FILE: &str = "ca_key.der";
+const JWT_KEY_FILE: &str = "jwt_key.jwk";
+
+/// Lifetime of a generated CA.
+const CA_TTL: Duration = Duration::from_secs(365 * 86_400);
+
+/// Identity provider minting SVIDs for the callers matching the configured entries.
+pub struct Agent {
+    config: Config,
+    ca: Ca,
+    jwt_bundles: JwtBundles,
+    x509_svids: Mutex<HashMap<SpiffeId, X509Svid>>,
+    wit_svids: Mutex<HashMap<SpiffeId, WitSvid>>,
+}
+
+impl Agent {
+    /// Loads the CA from the data directory, generating and storing it on the first start.
+    pub fn new(config: Config) -> Result<Self, AgentError> {
+        config.check()?;
+
+        let ca = match &config.data_dir {
+            Some(dir) => load_or_generate_ca(&config.trust_domain, dir)?,
+            None => generate_ca(&config.trust_domain)?,
+        };
+        let jwt_bundles = parse_jwt_bundles(&HashMap::from([(
+            config.trust_domain.clone(),
+            ca.jwt_bundle(),
+        )]))
+        .expect("the CA emits a valid JWKS");
+
+        Ok(Self {
+            config,
+            ca,
+            jwt_bundles,
+            x509_svids: Mutex::default(),
+            wit_svids: Mutex::default(),
+        })
+    }
+
+    #[inline]
+    pub fn ca(&self) -> &Ca {
+        &self.ca
+    }
+
+    /// Serves the Workload API on the configured socket, replacing a stale one.
+    ///
+    /// Fails if a file other than a socket exists at the path of the socket, the socket is
+    /// removed once the agent stops or the future is dropped. X.509-SVIDs and WIT-SVIDs are
+    /// rotated at half of their lifetime.
+    pub async fn run(self) -> Result<(), AgentError> {
+        let path = self.config.socket_path.clone();
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                fs::remove_file(&path).map_err(io_error(&path))?;
+            }
+            Ok(_) => return Err(AgentError::NotASocket(path)),
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(io_error(&path)(e)),
+            Err(_) => (),
+        }
+        let incoming = UnixIncoming::bind(&path).map_err(io_error(&path))?;
+        let _socket = BoundSocket(path);
+
+        let period = self.config.x509_svid_ttl / 2;
+        let server = WorkloadApiServer::new(self);
+        let agent = server.provider().clone();
+        let notifier = server.notifier();
+
+        let rotation = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                agent.rotate();
+                notifier.notify();
+            }
+        });
+        let result = server.serve_unix(incoming).await;
+        rotation.abort();
+
+        Ok(result?)
+    }
+
+    /// Drops the cached SVIDs, new ones are minted on the next fetch.
+    fn rotate(&self) {
+        self.x509_svids.lock().unwrap().clear();
+        self.wit_svids.lock().unwrap().clear();
+    }
+
+    /// Returns the SPIFFE IDs granted to `caller`, without duplicates.
+    fn spiffe_ids(&self, caller: &Caller) -> Vec<&SpiffeId> {
+        let Some(peer) = caller.peer() else {
+            return Vec::new();
+        };
+
+        let mut ids = Vec::<&SpiffeId>::new();
+        for entry in self.config.entries.iter().filter(|x| x.matches(peer)) {
+            if !ids.contains(&&entry.spiffe_id) {
+                ids.push(&entry.spiffe_id);
+            }
+        }
+
+        ids
+    }
+
+    /// Returns the SPIFFE IDs granted to `caller`, restricted to `requested` if given.
+    fn requested_ids<'a>(
+        &'a self,
+        caller: &Caller,
+        requested: Option<&SpiffeId>,
+    ) -> Vec<&'a SpiffeId> {
+        let mut ids = self.spiffe_ids(caller);
+        if let Some(requested) = requested {
+            ids.retain(|x| *x == requested);
+        }
+
+        ids
+    }
+
+    fn x509_svid(&self, spiffe_id: &SpiffeId) -> Result<X509Svid> {
+        let mut svids = self.x509_svids.lock().unwrap();
+        if let Some(svid) = svids.get(spiffe_id) {
+            return Ok(svid.clone());
+        }
+
+        let now = SystemTime::now();
+        let options = IssueOptions::new().validity(now, now + self.config.x509_svid_ttl);
+        let svid = self
+            .ca
+            .issue_x509_svid(spiffe_id, &options)
+            .map_err(internal)?;
+        svids.insert(spiffe_id.clone(), svid.clone());
+
+        Ok(svid)
+    }
+
+    fn wit_svid(&self, spiffe_id: &SpiffeId) -> Result<WitSvid> {
+        let mut svids = self.wit_svids.lock().unwrap();
+        if let Some(svid) = svids.get(spiffe_id) {
+            return Ok(svid.clone());
+        }
+
+        let expiry = SystemTime::now() + self.config.x509_svid_ttl;
+        let svid = self
+            .ca
+            .issue_wit_svid(spiffe_id, expiry)
+            .map_err(internal)?;
+        svids.insert(spiffe_id.clone(), svid.clone());
+
+        Ok(svid)
+    }
+
+    /// The bundles of the trust domain, for attested callers only.
+    fn bundles<T>(
+        &self,
+        caller: &Caller,
+        bundle: impl FnOnce() -> T,
+    ) -> HashMap<TrustDomain<'static>, T> {
+        if self.spiffe_ids(caller).is_empty() {
+            HashMap::new()
+        } else {
+            HashMap::from([(self.config.trust_domain.clone(), bundle())])
+        }
+    }
+}
+
+impl IdentityProvider for Agent {
+    async fn x509_svid_context(&self, caller: &Caller) -> Result<X509SvidContext> {
+        Ok(X509SvidContext {
+            svids: self
+                .spiffe_ids(caller)
+                .into_iter()
+                .map(|x| self.x509_svid(x))
+                .collect::<Result<_>>()?,
+            ..Default::default()
+        })
+    }
+
+    async fn x509_bundles_context(&self, caller: &Caller) -> Result<X509BundlesContext> {
+        Ok(X509BundlesContext {
+            bundles: self.bundles(caller, || self.ca.x509_bundle()),
+            ..Default::default()
+        })
+    }
+
+    async fn jwt_svids(&self, caller: &Caller, request: &JwtSvidRequest) -> Result<Vec<JwtSvid>> {
+        let audiences = request.audiences().iter().collect::<Vec<_>>();
+        let expiry = SystemTime::now() + self.config.jwt_svid_ttl;
+
+        self.requested_ids(caller, request.spiffe_id())
+            .into_iter()
+            .map(|x| self.ca.issue_jwt_svid(x, &audiences, expiry))
+            .collect::<Result<_, _>>()
+            .map_err(internal)
+    }
+
+    async fn jwt_bundles(&self, caller: &Caller) -> Result<HashMap<TrustDomain<'static>, String>> {
+        Ok(self.bundles(caller, || self.ca.jwt_bundle()))
+    }
+
+    async fn validate_jwt_svid(
+        &self,
+        _: &Caller,
+        audience: &str,
+        svid: &str,
+    ) -> Result<(SpiffeId, Map<String, JsonValue>)> {
+        let claims = JwtSvidClaims::validate(svid, &self.jwt_bundles, audience)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        Ok((claims.spiffe_id().clone(), claims.into_claims()))
+    }
+
+    async fn wit_svids(&self, caller: &Caller, request: &WitSvidRequest) -> Result<Vec<WitSvid>> {
+        self.requested_ids(caller, request.spiffe_id())
+            .into_iter()
+            .map(|x| self.wit_svid(x))
+            .collect()
+    }
+
+    async fn wit_bundles(&self, caller: &Caller) -> Result<HashMap<TrustDomain<'static>, String>> {
+        Ok(self.bundles(caller, || self.ca.wit_bundle()))
+    }
+}
+
+fn internal(error: CaError) -> Status {
+    Status::internal(error.to_string())
+}
+
+fn generate_ca(trust_domain: &TrustDomain<'_>) -> Result<Ca, CaError> {
+    let now = SystemTime::now();
+    let options = IssueOptions::new().validity(now - Duration::from_secs(60), now + CA_TTL);
+
+    Ca::with_options(trust_domain.clone(), &options)
+}
+
+/// Loads the CA stored in `dir`, failing if it has expired rather than replacing it, as its
+/// bundle may be trusted elsewhere.
+fn load_or_generate_ca(trust_domain: &TrustDomain<'_>, dir: &Path) -> Result<Ca, AgentError> {
+    let ca_path = dir.join(CA_FILE);
+    let key_path = dir.join(CA_KEY_FILE);
+    let jwt_key_path = dir.join(JWT_KEY_FILE);
+
+    match fs::read(&ca_path) {
+        Ok(certificate) => {
+            let key = fs::read(&key_path).map_err(io_error(&key_path))?;
+            let jwt_key = fs::read_to_string(&jwt_key_path).map_err(io_error(&jwt_key_path))?;
+            let jwt_key = SigningKey::from_jwk(&jwt_key).map_err(|e| AgentError::InvalidCa {
+                path: jwt_key_path,
+                error: e.into(),
+            })?;
+
+            Ca::load(
+                trust_domain.clone(),
+                CertificateDer::from(certificate),
+                &PrivatePkcs8KeyDer::from(key),
+                jwt_key,
+            )
+            .map_err(|error| AgentError::InvalidCa {
+                path: ca_path,
+                error,
+            })
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let ca = generate_ca(trust_domain)?;
+
+            fs::create_dir_all(dir).map_err(io_error(dir))?;
+            write_file(&key_path, ca.key().secret_pkcs8_der(), 0o600)?;
+            write_file(
+                &jwt_key_path,
+                ca.jwt_key().to_jwk().expose_secret().as_bytes(),
+                0o600,
+            )?;
+            // written last, an interrupted first start is retried from scratch
+            write_file(&ca_path, ca.certificate(), 0o644)?;
+
+            Ok(ca)
+        }
+        Err(e) => Err(io_error(&ca_path)(e)),
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8], mode: u32) -> Result<(), AgentError> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .map_err(io_error(path))
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> AgentError {
+    let path = path.to_owned();
+    move |error| AgentError::Io { path, error }
+}
+
+/// Socket bound by [`Agent::run`], removed on drop.
+struct BoundSocket(PathBuf);
+
+impl Drop for BoundSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, os::unix::fs::MetadataExt, path::PathBuf};
+
+    use futures_util::StreamExt;
+    use spiffe::client::{Audiences, SpiffeWorkloadApiClient};
+    use tonic::Code;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("spiffe-lite-agent-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn spiffe_id(path: &str) -> SpiffeId {
+        SpiffeId::new(format!("spiffe://example.org/{path}")).unwrap()
+    }
+
+    #[test]
+    fn test_persisted_ca() {
+        let dir = temp_dir("ca");
+        let config = Config::new(
+            TrustDomain::new("example.org").unwrap(),
+            dir.join("agent.sock"),
+        )
+        .data_dir(dir.join("data"));
+
+        let first = Agent::new(config.clone()).unwrap();
+        let second = Agent::new(config).unwrap();
+        assert_eq!(first.ca().x509_bundle(), second.ca().x509_bundle());
+        assert_eq!(first.ca().jwt_bundle(), second.ca().jwt_bundle());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_not_a_socket() {
+        let dir = temp_dir("not-a-socket");
+        let path = dir.join("agent.sock");
+        fs::write(&path, "data").unwrap();
+
+        let config = Config::new(TrustDomain::new("example.org").unwrap(), &path);
+        let result = Agent::new(config).unwrap().run().await;
+        assert!(matches!(result, Err(AgentError::NotASocket(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let dir = temp_dir("serve");
+        let socket = dir.join("agent.sock");
+        let uid = fs::metadata(&dir).unwrap().uid();
+        let config = Config::new(TrustDomain::new("example.org").unwrap(), &socket)
+            .entry(
+                Entry::new(spiffe_id("test"))
+                    .uid(uid)
+                    .exe(env::current_exe().unwrap()),
+            )
+            .entry(Entry::new(spiffe_id("other")).uid(uid.wrapping_add(1)));
+        let task = tokio::spawn(Agent::new(config).unwrap().run());
+        while !socket.exists() {
+            tokio::task::yield_now().await;
+        }
+
+        let client = SpiffeWorkloadApiClient::connect(&format!("unix://{}", socket.display()))
+            .await
+            .unwrap();
+
+        let mut svids = client.fetch_x509_svid().await.unwrap();
+        let context = svids.next().await.unwrap();
+        assert_eq!(context.svids.len(), 1);
+        assert_eq!(context.svids[0].spiffe_id(), &spiffe_id("test"));
+
+        let audiences = Audiences::new("db").unwrap();
+        let jwt = client.fetch_jwt_svid(&audiences, None).await.unwrap();
+        let (id, claims) = client.validate_jwt_svid("db", jwt[0].svid()).await.unwrap();
+        assert_eq!(id, spiffe_id("test"));
+        assert_eq!(claims["aud"], serde_json::json!(["db"]));
+
+        let err = client
+            .validate_jwt_svid("web", jwt[0].svid())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+        let err = client
+            .fetch_jwt_svid(&audiences, Some(&spiffe_id("other")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::PermissionDenied);
+
+        let mut wits = client.fetch_wit_svid(None).await.unwrap();
+        let wit = wits.next().await.unwrap();
+        assert_eq!(wit.svids[0].wit().unwrap().spiffe_id(), &spiffe_id("test"));
+
+        task.abort();
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(!socket.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+use std::{env, fs, process::ExitCode};
+
+use spiffe_lite_agent::{Agent, Config};
+use tokio::signal::unix::{SignalKind, signal};
+
+const USAGE: &str = "\
+usage: spiffe-lite-agent --config FILE
+
+Serves the Workload API on a Unix socket, issuing SVIDs from a local CA to the callers
+matching the entries of the JSON configuration FILE.";
+
+fn parse_args() -> Result<String, String> {
+    let mut args = env::args().skip(1);
+    let mut config = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                config = Some(
+                    args.next()
+                        .ok_or_else(|| format!("missing value for `{arg}`"))?,
+                );
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("unknown argument `{arg}`\n\n{USAGE}")),
+        }
+    }
+
+    config.ok_or_else(|| format!("`--config` is required\n\n{USAGE}"))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let path = match parse_args() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let config = fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read `{path}`: {e}"))
+        .and_then(|x| Config::from_json(&x).map_err(|e| e.to_string()));
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("spiffe-lite-agent: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let agent = match Agent::new(config) {
+        Ok(agent) => agent,
+        Err(e) => {
+            eprintln!("spiffe-lite-agent: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (Ok(mut terminate), Ok(mut interrupt)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) else {
+        eprintln!("spiffe-lite-agent: failed to install signal handlers");
+        return ExitCode::FAILURE;
+    };
+
+    let result = tokio::select! {
+        result = agent.run() => result,
+        _ = terminate.recv() => Ok(()),
+        _ = interrupt.recv() => Ok(()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("spiffe-lite-agent: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
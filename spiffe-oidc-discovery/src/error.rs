@@ -0,0 +1,19 @@
+use std::io::Error as IoError;
+
+use spiffe::{SourceError, TokenError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DiscoveryError {
+    #[error("invalid issuer URL: {0}")]
+    InvalidIssuer(String),
+
+    #[error("failed to get the JWT bundle: {0}")]
+    Source(#[from] SourceError),
+
+    #[error("invalid JWT bundle: {0}")]
+    InvalidBundle(#[from] TokenError),
+
+    #[error("failed to serve the discovery documents: {0}")]
+    Io(#[from] IoError),
+}
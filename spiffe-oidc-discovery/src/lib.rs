@@ -0,0 +1,376 @@
+//! OIDC discovery provider, letting cloud IAM and other relying parties validate the JWT-SVIDs
+//! of a trust domain.
+//!
+//! The `/.well-known/openid-configuration` document and the `/keys` JWKS are derived on every
+//! request from a [`JwtBundleSource`], typically a
+//! [`WorkloadJwtSource`](spiffe::source::WorkloadJwtSource) following the `FetchJWTBundles`
+//! stream of the Workload API. Keys which are not for signing are not published.
+
+mod error;
+
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use http::{
+    HeaderValue, Method, Request, Response, StatusCode, Uri,
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    uri::Scheme,
+};
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value as JsonValue, json};
+use spiffe::{
+    jose::{Algorithm, Jwk, JwkSet},
+    source::JwtBundleSource,
+};
+use spiffe_id::TrustDomain;
+use tokio::net::TcpListener;
+
+pub use self::error::DiscoveryError;
+
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+const KEYS_PATH: &str = "/keys";
+
+/// Key uses published in the JWKS, keys without a use are assumed to be for signing.
+const SIGNING_USES: [&str; 2] = ["sig", "jwt-svid"];
+
+/// Algorithms advertised in the discovery document, if a published key supports them.
+const ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// Server of the OIDC discovery documents of a trust domain.
+pub struct DiscoveryProvider<S> {
+    trust_domain: TrustDomain<'static>,
+    issuer: String,
+    path: String,
+    source: S,
+    max_age: Duration,
+}
+
+impl<S: JwtBundleSource + Send + Sync + 'static> DiscoveryProvider<S> {
+    /// Creates a provider of the JWT-SVIDs of `trust_domain` issued by the `https` URL `issuer`.
+    ///
+    /// The issuer is published exactly as given, relying parties compare it to the `iss` claim
+    /// verbatim. The documents are served under the path of the issuer.
+    pub fn new(
+        trust_domain: TrustDomain<'_>,
+        issuer: &str,
+        source: S,
+    ) -> Result<Self, DiscoveryError> {
+        let uri = issuer
+            .parse::<Uri>()
+            .map_err(|e| DiscoveryError::InvalidIssuer(e.to_string()))?;
+        if uri.scheme() != Some(&Scheme::HTTPS) || uri.host().is_none() {
+            return Err(DiscoveryError::InvalidIssuer(
+                "the issuer must be an `https` URL".into(),
+            ));
+        }
+        if uri.query().is_some() || issuer.contains('#') {
+            return Err(DiscoveryError::InvalidIssuer(
+                "the issuer must not have a query or fragment".into(),
+            ));
+        }
+
+        Ok(Self {
+            trust_domain: trust_domain.into_owned(),
+            issuer: issuer.to_owned(),
+            path: uri.path().trim_end_matches('/').to_owned(),
+            source,
+            max_age: Duration::from_secs(300),
+        })
+    }
+
+    /// Sets the `max-age` of the `Cache-Control` header of the documents.
+    ///
+    /// Default: 5 minutes
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Returns the `/.well-known/openid-configuration` document, advertising the algorithms of
+    /// the signing keys of the trust domain.
+    pub fn discovery_document(&self) -> Result<JsonValue, DiscoveryError> {
+        let keys = self.signing_keys()?;
+        let algorithms = ALGORITHMS
+            .iter()
+            .filter(|&&alg| keys.iter().any(|x| x.supports(alg)))
+            .map(Algorithm::as_str)
+            .collect::<Vec<_>>();
+
+        Ok(json!({
+            "issuer": self.issuer,
+            "jwks_uri": format!("{}{KEYS_PATH}", self.issuer.trim_end_matches('/')),
+            "authorization_endpoint": "",
+            "response_types_supported": ["id_token"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": algorithms,
+        }))
+    }
+
+    /// Returns the JWKS of the signing keys of the trust domain.
+    pub fn keys(&self) -> Result<JsonValue, DiscoveryError> {
+        Ok(JwkSet::new(self.signing_keys()?).to_value())
+    }
+
+    /// Serves the documents over plain HTTP on `listener`, until accepting a connection fails.
+    ///
+    /// TLS is expected to be terminated in front of the provider, at the host of the issuer.
+    pub async fn serve(self, listener: TcpListener) -> Result<(), DiscoveryError> {
+        let this = Arc::new(self);
+
+        loop {
+            let (tcp, _) = listener.accept().await?;
+            let this = this.clone();
+
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let response = this.respond(&req);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(tcp), service)
+                    .await;
+            });
+        }
+    }
+
+    fn signing_keys(&self) -> Result<Vec<Jwk>, DiscoveryError> {
+        let jwks = self.source.jwt_bundle(&self.trust_domain)?;
+        let keys = JwkSet::from_json(&jwks)?
+            .into_parts()
+            .into_iter()
+            .filter(|x| x.key_id().is_some())
+            .filter(|x| x.public_use().is_none_or(|x| SIGNING_USES.contains(&x)))
+            .map(|x| x.with_public_use("sig"))
+            .collect();
+
+        Ok(keys)
+    }
+
+    fn respond(&self, req: &Request<Incoming>) -> Response<Full<Bytes>> {
+        let path = req.uri().path();
+        let document = if path.strip_prefix(&*self.path) == Some(DISCOVERY_PATH) {
+            self.discovery_document()
+                .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)
+        } else if path.strip_prefix(&*self.path) == Some(KEYS_PATH) {
+            self.keys().map_err(|_| StatusCode::SERVICE_UNAVAILABLE)
+        } else {
+            Err(StatusCode::NOT_FOUND)
+        };
+        let document = match req.method() {
+            &Method::GET | &Method::HEAD => document,
+            _ => document.and(Err(StatusCode::METHOD_NOT_ALLOWED)),
+        };
+
+        let mut response = Response::new(Full::default());
+        match document {
+            Ok(document) => {
+                let cache_control = format!("public, max-age={}", self.max_age.as_secs());
+                let headers = response.headers_mut();
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                headers.insert(CACHE_CONTROL, HeaderValue::try_from(cache_control).unwrap());
+                if req.method() == Method::GET {
+                    *response.body_mut() = Full::new(document.to_string().into());
+                }
+            }
+            Err(status) => {
+                *response.status_mut() = status;
+                response
+                    .headers_mut()
+                    .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            }
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, net::Ipv4Addr, sync::Arc};
+
+    use http_body_util::{BodyExt, Empty};
+    use hyper::HeaderMap;
+    use spiffe::{SourceError, jose::SigningKey, source::WorkloadJwtSource};
+    use spiffe_proto::JwtBundlesResponse;
+    use spiffe_testkit::{FakeWorkloadApi, Step, TestCa, TestServer};
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    fn response(bundles: &[(&TestCa, &JwkSet)]) -> JwtBundlesResponse {
+        JwtBundlesResponse {
+            bundles: bundles
+                .iter()
+                .map(|(ca, jwks)| {
+                    (
+                        ca.trust_domain().to_string(),
+                        jwks.to_value().to_string().into(),
+                    )
+                })
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    async fn get(port: u16, path: &str) -> (StatusCode, HeaderMap, String) {
+        let tcp = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+            .await
+            .unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(tcp))
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let request = Request::get(path)
+            .header("host", "localhost")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let response = sender.send_request(request).await.unwrap();
+        let (status, headers) = (response.status(), response.headers().clone());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (status, headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let other = TestCa::new(TrustDomain::new("other.org").unwrap());
+        let rotated = TestCa::new(ca.trust_domain().clone());
+
+        let signing = JwkSet::from_json(&ca.jwt_bundle()).unwrap();
+        let mut keys = signing.keys().to_vec();
+        let encryption = rotated.jwt_key().public_key().clone();
+        keys.push(encryption.clone().with_key_id("enc").with_public_use("enc"));
+        let jwks = JwkSet::new(keys);
+
+        let api = Arc::new(FakeWorkloadApi::new());
+        api.jwt_bundles().script([Step::Respond(response(&[
+            (&ca, &jwks),
+            (&other, &JwkSet::from_json(&other.jwt_bundle()).unwrap()),
+        ]))]);
+        let workload_api = TestServer::duplex(api);
+        let source = WorkloadJwtSource::new(workload_api.client()).await.unwrap();
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let provider = DiscoveryProvider::new(
+            ca.trust_domain().clone(),
+            "https://oidc.example.org/",
+            source,
+        )
+        .unwrap()
+        .max_age(Duration::from_secs(60));
+        tokio::spawn(provider.serve(listener));
+
+        let (status, headers, body) = get(port, DISCOVERY_PATH).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CACHE_CONTROL], "public, max-age=60");
+        let document = serde_json::from_str::<JsonValue>(&body).unwrap();
+        assert_eq!(document["issuer"], "https://oidc.example.org/");
+        assert_eq!(document["jwks_uri"], "https://oidc.example.org/keys");
+        assert_eq!(
+            document["id_token_signing_alg_values_supported"],
+            json!([ca.jwt_key().algorithm().as_str()])
+        );
+
+        let (status, headers, body) = get(port, KEYS_PATH).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        let keys = JwkSet::from_json(&body).unwrap();
+        assert_eq!(keys.keys().len(), 1);
+        assert_eq!(keys.keys()[0].public_use(), Some("sig"));
+        assert_eq!(keys.keys()[0].key_id(), signing.keys()[0].key_id());
+
+        // rotated bundles are served as soon as the Workload API streams them
+        let rotated_jwks = JwkSet::from_json(&rotated.jwt_bundle()).unwrap();
+        workload_api
+            .api()
+            .jwt_bundles()
+            .rotate(response(&[(&rotated, &rotated_jwks)]));
+        let expected = rotated_jwks.keys()[0].clone().with_public_use("sig");
+        for _ in 0..100 {
+            let (_, _, body) = get(port, KEYS_PATH).await;
+            if JwkSet::from_json(&body).unwrap().keys() == [expected.clone()] {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let (_, _, body) = get(port, KEYS_PATH).await;
+        assert_eq!(JwkSet::from_json(&body).unwrap().keys(), [expected]);
+
+        assert_eq!(get(port, "/other").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_invalid_issuer() {
+        struct NoBundles;
+
+        impl JwtBundleSource for NoBundles {
+            fn jwt_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<String, SourceError> {
+                Err(SourceError::NoJwtBundle(trust_domain.clone().into_owned()))
+            }
+        }
+
+        let td = TrustDomain::new("example.org").unwrap();
+        for issuer in [
+            "http://oidc.example.org",
+            "oidc.example.org",
+            "https://oidc.example.org/?a=b",
+        ] {
+            assert!(matches!(
+                DiscoveryProvider::new(td.clone(), issuer, NoBundles),
+                Err(DiscoveryError::InvalidIssuer(_))
+            ));
+        }
+
+        let provider = DiscoveryProvider::new(td, "https://example.org/oidc", NoBundles).unwrap();
+        assert_eq!(provider.path, "/oidc");
+        assert!(provider.discovery_document().is_err());
+        assert!(provider.keys().is_err());
+    }
+
+    #[test]
+    fn test_algorithms() {
+        let td = TrustDomain::new("example.org").unwrap();
+        let key = |alg, kid: &str| {
+            SigningKey::generate(alg)
+                .unwrap()
+                .public_key()
+                .clone()
+                .with_key_id(kid)
+        };
+        let encryption = key(Algorithm::ES256, "enc").with_public_use("enc");
+        let jwks = JwkSet::new(vec![
+            key(Algorithm::EdDSA, "a"),
+            key(Algorithm::ES384, "b"),
+            encryption,
+        ]);
+        let bundles = HashMap::from([(td.clone(), jwks.to_value().to_string())]);
+
+        let provider = DiscoveryProvider::new(td, "https://example.org/oidc", bundles).unwrap();
+        let document = provider.discovery_document().unwrap();
+        assert_eq!(document["issuer"], "https://example.org/oidc");
+        assert_eq!(document["jwks_uri"], "https://example.org/oidc/keys");
+        // keys which are not for signing are not advertised
+        assert_eq!(
+            document["id_token_signing_alg_values_supported"],
+            json!(["ES384", "EdDSA"])
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::{env, net::SocketAddr, process::ExitCode, time::Duration};
+
+use spiffe::{client::SpiffeWorkloadApiClient, source::WorkloadJwtSource};
+use spiffe_id::TrustDomain;
+use spiffe_oidc_discovery::DiscoveryProvider;
+use tokio::{
+    net::TcpListener,
+    signal::unix::{SignalKind, signal},
+};
+
+const USAGE: &str = "\
+usage: spiffe-oidc-discovery --trust-domain DOMAIN --issuer URL [--listen ADDR]
+                             [--max-age SECONDS] [--workload-api ADDR]
+
+Serves the OIDC discovery document and the JWKS of the JWT-SVIDs of DOMAIN over plain HTTP,
+from the JWT bundles of the Workload API at ADDR, or SPIFFE_ENDPOINT_SOCKET by default.
+
+  --listen ADDR        address to listen on, 127.0.0.1:8080 by default
+  --max-age SECONDS    max-age of the Cache-Control header, 300 by default";
+
+struct Args {
+    trust_domain: TrustDomain<'static>,
+    issuer: String,
+    listen: SocketAddr,
+    max_age: Duration,
+    workload_api: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args().skip(1);
+    let (mut trust_domain, mut issuer, mut listen, mut max_age, mut workload_api) =
+        (None, None, None, None, None);
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for `{arg}`"))
+        };
+        match arg.as_str() {
+            "--trust-domain" => trust_domain = Some(value()?),
+            "--issuer" => issuer = Some(value()?),
+            "--listen" => listen = Some(value()?),
+            "--max-age" => max_age = Some(value()?),
+            "--workload-api" => workload_api = Some(value()?),
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("unknown argument `{arg}`\n\n{USAGE}")),
+        }
+    }
+
+    let trust_domain = trust_domain
+        .ok_or_else(|| format!("`--trust-domain` is required\n\n{USAGE}"))
+        .and_then(|x| {
+            TrustDomain::new(&x)
+                .map(TrustDomain::into_owned)
+                .map_err(|e| format!("invalid `--trust-domain`: {e}"))
+        })?;
+    let issuer = issuer.ok_or_else(|| format!("`--issuer` is required\n\n{USAGE}"))?;
+    let listen = listen
+        .as_deref()
+        .unwrap_or("127.0.0.1:8080")
+        .parse()
+        .map_err(|e| format!("invalid `--listen`: {e}"))?;
+    let max_age = max_age
+        .as_deref()
+        .unwrap_or("300")
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|e| format!("invalid `--max-age`: {e}"))?;
+
+    Ok(Args {
+        trust_domain,
+        issuer,
+        listen,
+        max_age,
+        workload_api,
+    })
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let client = match &args.workload_api {
+        Some(addr) => SpiffeWorkloadApiClient::connect(addr).await,
+        None => SpiffeWorkloadApiClient::connect_default().await,
+    }
+    .map_err(|e| format!("failed to connect to the Workload API: {e}"))?;
+    let source = WorkloadJwtSource::new(client)
+        .await
+        .map_err(|e| format!("failed to fetch the JWT bundles: {e}"))?;
+
+    let provider = DiscoveryProvider::new(args.trust_domain, &args.issuer, source)
+        .map_err(|e| e.to_string())?
+        .max_age(args.max_age);
+    let listener = TcpListener::bind(args.listen)
+        .await
+        .map_err(|e| format!("failed to listen on `{}`: {e}", args.listen))?;
+
+    provider.serve(listener).await.map_err(|e| e.to_string())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let (Ok(mut terminate), Ok(mut interrupt)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) else {
+        eprintln!("spiffe-oidc-discovery: failed to install signal handlers");
+        return ExitCode::FAILURE;
+    };
+
+    let result = tokio::select! {
+        result = run(args) => result,
+        _ = terminate.recv() => Ok(()),
+        _ = interrupt.recv() => Ok(()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("spiffe-oidc-discovery: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
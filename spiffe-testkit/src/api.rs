@@ -0,0 +1,128 @@
+use futures_util::stream::BoxStream;
+use spiffe_proto::{
+    JwtBundlesRequest, JwtBundlesResponse, JwtSvidRequest, JwtSvidResponse, ValidateJwtSvidRequest,
+    ValidateJwtSvidResponse, WitBundlesRequest, WitBundlesResponse, WitSvidRequest,
+    WitSvidResponse, X509BundlesRequest, X509BundlesResponse, X509SvidRequest, X509SvidResponse,
+    server::SpiffeWorkloadApi,
+};
+use tonic::{Request, Response, Result};
+
+use crate::{Feed, Unary};
+
+type FeedStream<T> = BoxStream<'static, Result<T>>;
+
+/// Workload API serving scripted responses, every method is `Unimplemented` until configured.
+#[derive(Debug, Default)]
+pub struct FakeWorkloadApi {
+    x509_svid: Feed<X509SvidResponse>,
+    x509_bundles: Feed<X509BundlesResponse>,
+    jwt_svid: Unary<JwtSvidResponse>,
+    jwt_bundles: Feed<JwtBundlesResponse>,
+    validate_jwt_svid: Unary<ValidateJwtSvidResponse>,
+    wit_svid: Feed<WitSvidResponse>,
+    wit_bundles: Feed<WitBundlesResponse>,
+}
+
+impl FakeWorkloadApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn x509_svid(&self) -> &Feed<X509SvidResponse> {
+        &self.x509_svid
+    }
+
+    #[inline]
+    pub fn x509_bundles(&self) -> &Feed<X509BundlesResponse> {
+        &self.x509_bundles
+    }
+
+    /// Returns the `FetchJWTSVID` controls, the response does not depend on the request.
+    #[inline]
+    pub fn jwt_svid(&self) -> &Unary<JwtSvidResponse> {
+        &self.jwt_svid
+    }
+
+    #[inline]
+    pub fn jwt_bundles(&self) -> &Feed<JwtBundlesResponse> {
+        &self.jwt_bundles
+    }
+
+    /// Returns the `ValidateJWTSVID` controls, the response does not depend on the request.
+    #[inline]
+    pub fn validate_jwt_svid(&self) -> &Unary<ValidateJwtSvidResponse> {
+        &self.validate_jwt_svid
+    }
+
+    #[inline]
+    pub fn wit_svid(&self) -> &Feed<WitSvidResponse> {
+        &self.wit_svid
+    }
+
+    #[inline]
+    pub fn wit_bundles(&self) -> &Feed<WitBundlesResponse> {
+        &self.wit_bundles
+    }
+}
+
+impl SpiffeWorkloadApi for FakeWorkloadApi {
+    type FetchX509SvidStream = FeedStream<X509SvidResponse>;
+
+    async fn fetch_x509_svid(
+        &self,
+        _: Request<X509SvidRequest>,
+    ) -> Result<Response<Self::FetchX509SvidStream>> {
+        self.x509_svid.open().map(Response::new)
+    }
+
+    type FetchX509BundlesStream = FeedStream<X509BundlesResponse>;
+
+    async fn fetch_x509_bundles(
+        &self,
+        _: Request<X509BundlesRequest>,
+    ) -> Result<Response<Self::FetchX509BundlesStream>> {
+        self.x509_bundles.open().map(Response::new)
+    }
+
+    async fn fetch_jwt_svid(
+        &self,
+        _: Request<JwtSvidRequest>,
+    ) -> Result<Response<JwtSvidResponse>> {
+        self.jwt_svid.call().await.map(Response::new)
+    }
+
+    type FetchJwtBundlesStream = FeedStream<JwtBundlesResponse>;
+
+    async fn fetch_jwt_bundles(
+        &self,
+        _: Request<JwtBundlesRequest>,
+    ) -> Result<Response<Self::FetchJwtBundlesStream>> {
+        self.jwt_bundles.open().map(Response::new)
+    }
+
+    async fn validate_jwt_svid(
+        &self,
+        _: Request<ValidateJwtSvidRequest>,
+    ) -> Result<Response<ValidateJwtSvidResponse>> {
+        self.validate_jwt_svid.call().await.map(Response::new)
+    }
+
+    type FetchWitSvidStream = FeedStream<WitSvidResponse>;
+
+    async fn fetch_wit_svid(
+        &self,
+        _: Request<WitSvidRequest>,
+    ) -> Result<Response<Self::FetchWitSvidStream>> {
+        self.wit_svid.open().map(Response::new)
+    }
+
+    type FetchWitBundlesStream = FeedStream<WitBundlesResponse>;
+
+    async fn fetch_wit_bundles(
+        &self,
+        _: Request<WitBundlesRequest>,
+    ) -> Result<Response<Self::FetchWitBundlesStream>> {
+        self.wit_bundles.open().map(Response::new)
+    }
+}
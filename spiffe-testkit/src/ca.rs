@@ -0,0 +1,279 @@
+use std::time::SystemTime;
+
+use rustls_pki_types::{CertificateDer, CertificateRevocationListDer, PrivatePkcs8KeyDer};
+use spiffe::{JwtSvid, WitSvid, X509Bundle, X509Svid, jose::SigningKey};
+pub use spiffe_ca::KeyType;
+use spiffe_ca::{Ca, LeafTemplate};
+use spiffe_id::{SpiffeId, TrustDomain};
+
+/// Deliberately invalid X.509-SVID leaf, to test that verifiers reject it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Malformation {
+    /// No `subjectAltName` extension.
+    NoSan,
+    /// An additional URI SAN besides the SPIFFE ID.
+    TwoUriSans(SpiffeId),
+    /// A leaf marked as CA, with the `keyCertSign` key usage.
+    CaLeaf,
+}
+
+/// Options of a certificate issued by [`TestCa`].
+#[derive(Clone, Debug, Default)]
+pub struct IssueOptions {
+    options: spiffe_ca::IssueOptions,
+    malformation: Option<Malformation>,
+}
+
+impl IssueOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Default: [`KeyType::P256`]
+    #[must_use]
+    pub fn key_type(mut self, key_type: KeyType) -> Self {
+        self.options = self.options.key_type(key_type);
+        self
+    }
+
+    /// Default: from an hour ago until a day from now
+    #[must_use]
+    pub fn validity(mut self, not_before: SystemTime, not_after: SystemTime) -> Self {
+        self.options = self.options.validity(not_before, not_after);
+        self
+    }
+
+    /// Adds a DNS name to the `subjectAltName` of a leaf, to serve it to WebPKI clients.
+    ///
+    /// Default: none
+    #[must_use]
+    pub fn dns_name(mut self, name: impl Into<String>) -> Self {
+        self.options = self.options.dns_name(name);
+        self
+    }
+
+    /// Issues a malformed leaf, ignored for CAs.
+    ///
+    /// Default: well-formed
+    #[must_use]
+    pub fn malformed(mut self, malformation: Malformation) -> Self {
+        self.malformation = Some(malformation);
+        self
+    }
+}
+
+/// [`Ca`] of a trust domain for tests, panicking instead of returning errors and able to issue
+/// malformed X.509-SVIDs.
+pub struct TestCa {
+    ca: Ca,
+}
+
+impl TestCa {
+    /// Creates a root CA with the default options.
+    pub fn new(trust_domain: TrustDomain<'_>) -> Self {
+        Self::with_options(trust_domain, &IssueOptions::new())
+    }
+
+    /// Creates a root CA.
+    pub fn with_options(trust_domain: TrustDomain<'_>, options: &IssueOptions) -> Self {
+        Self {
+            ca: Ca::with_options(trust_domain, &options.options).expect("failed to create the CA"),
+        }
+    }
+
+    /// Loads a root CA saved with [`certificate`](Self::certificate), [`key`](Self::key) and
+    /// [`jwt_key`](Self::jwt_key).
+    ///
+    /// Returns `None` if the certificate or the key are malformed, or do not match.
+    pub fn load(
+        trust_domain: TrustDomain<'_>,
+        certificate: CertificateDer<'static>,
+        key: &PrivatePkcs8KeyDer<'_>,
+        jwt_key: SigningKey,
+    ) -> Option<Self> {
+        let ca = Ca::load(trust_domain, certificate, key, jwt_key).ok()?;
+
+        Some(Self { ca })
+    }
+
+    /// Creates an intermediate CA signed by this CA.
+    pub fn intermediate(&self, options: &IssueOptions) -> Self {
+        Self {
+            ca: (self.ca)
+                .intermediate(&options.options)
+                .expect("failed to create the CA"),
+        }
+    }
+
+    /// Returns the underlying CA.
+    #[inline]
+    pub fn ca(&self) -> &Ca {
+        &self.ca
+    }
+
+    #[inline]
+    pub fn trust_domain(&self) -> &TrustDomain<'static> {
+        self.ca.trust_domain()
+    }
+
+    /// Returns the certificate of this CA.
+    #[inline]
+    pub fn certificate(&self) -> &CertificateDer<'static> {
+        self.ca.certificate()
+    }
+
+    /// Returns the private key of this CA.
+    #[inline]
+    pub fn key(&self) -> PrivatePkcs8KeyDer<'static> {
+        self.ca.key().clone_key()
+    }
+
+    /// Returns the JWT signing key of the trust domain, also signing the WITs.
+    #[inline]
+    pub fn jwt_key(&self) -> &SigningKey {
+        self.ca.jwt_key()
+    }
+
+    /// Returns the X.509 bundle of the trust domain, holding the root.
+    pub fn x509_bundle(&self) -> X509Bundle {
+        self.ca.x509_bundle()
+    }
+
+    /// Returns the JWT bundle of the trust domain as a JWKS document.
+    pub fn jwt_bundle(&self) -> String {
+        self.ca.jwt_bundle()
+    }
+
+    /// Returns the WIT bundle of the trust domain as a JWKS document.
+    pub fn wit_bundle(&self) -> String {
+        self.ca.wit_bundle()
+    }
+
+    /// Issues a leaf certificate, returning the chain up to, excluding, the root and the key.
+    pub fn issue_x509_chain(
+        &self,
+        spiffe_id: &SpiffeId,
+        options: &IssueOptions,
+    ) -> (Vec<CertificateDer<'static>>, PrivatePkcs8KeyDer<'static>) {
+        let template = match &options.malformation {
+            None => LeafTemplate::new().uri(spiffe_id.as_str()),
+            Some(Malformation::NoSan) => LeafTemplate::new(),
+            Some(Malformation::TwoUriSans(other)) => LeafTemplate::new()
+                .uri(spiffe_id.as_str())
+                .uri(other.as_str()),
+            Some(Malformation::CaLeaf) => LeafTemplate::new().uri(spiffe_id.as_str()).ca(),
+        };
+
+        (self.ca)
+            .issue_leaf(&template, &options.options)
+            .expect("failed to issue the certificate")
+    }
+
+    /// Issues an X.509-SVID with the trust domain's bundle attached.
+    pub fn issue_x509_svid(&self, spiffe_id: &SpiffeId, options: &IssueOptions) -> X509Svid {
+        let (chain, key) = self.issue_x509_chain(spiffe_id, options);
+
+        spiffe_proto::X509Svid {
+            spiffe_id: spiffe_id.to_string(),
+            x509_svid: chain
+                .iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .concat()
+                .into(),
+            x509_svid_key: key.secret_pkcs8_der().to_vec().into(),
+            bundle: self.x509_bundle().bundle()[0].to_vec().into(),
+            hint: String::new(),
+        }
+        .try_into()
+        .expect("the SVID is well-formed")
+    }
+
+    /// Issues a JWT-SVID signed with the trust domain's JWT key.
+    pub fn issue_jwt_svid(
+        &self,
+        spiffe_id: &SpiffeId,
+        audiences: &[&str],
+        expiry: SystemTime,
+    ) -> JwtSvid {
+        (self.ca)
+            .issue_jwt_svid(spiffe_id, audiences, expiry)
+            .expect("the SPIFFE ID is valid")
+    }
+
+    /// Issues a WIT-SVID signed with the trust domain's JWT key, bound to a new ES256 key.
+    pub fn issue_wit_svid(&self, spiffe_id: &SpiffeId, expiry: SystemTime) -> WitSvid {
+        (self.ca)
+            .issue_wit_svid(spiffe_id, expiry)
+            .expect("the SVID is well-formed")
+    }
+
+    /// Issues a CRL revoking `revoked`, valid from a minute ago until a day from now.
+    ///
+    /// # Panics
+    ///
+    /// If a revoked certificate is not valid DER.
+    pub fn crl(&self, revoked: &[&CertificateDer<'_>]) -> CertificateRevocationListDer<'static> {
+        self.ca.crl(revoked).expect("invalid certificate")
+    }
+
+    /// Issues a CRL revoking `revoked` with the given `thisUpdate` and `nextUpdate`.
+    ///
+    /// # Panics
+    ///
+    /// If a revoked certificate is not valid DER.
+    pub fn crl_at(
+        &self,
+        revoked: &[&CertificateDer<'_>],
+        this_update: SystemTime,
+        next_update: SystemTime,
+    ) -> CertificateRevocationListDer<'static> {
+        (self.ca)
+            .crl_at(revoked, this_update, next_update)
+            .expect("invalid certificate")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use rustls_pki_types::{SignatureVerificationAlgorithm, UnixTime};
+    use spiffe::spiffe_id_from_x509_svid_unchecked;
+    use webpki::{EndEntityCert, Error, KeyUsage, anchor_from_trusted_cert, ring};
+
+    use super::*;
+
+    const ALGORITHMS: &[&dyn SignatureVerificationAlgorithm] = &[ring::ECDSA_P256_SHA256];
+
+    #[test]
+    fn test_malformed_x509_svid() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let spiffe_id = SpiffeId::new("spiffe://example.org/workload").unwrap();
+        let issue = |malformation| {
+            let options = IssueOptions::new().malformed(malformation);
+            ca.issue_x509_chain(&spiffe_id, &options).0
+        };
+
+        let chain = issue(Malformation::NoSan);
+        assert!(spiffe_id_from_x509_svid_unchecked(&chain[0]).is_err());
+
+        let other = SpiffeId::new("spiffe://example.org/other").unwrap();
+        let chain = issue(Malformation::TwoUriSans(other));
+        assert!(spiffe_id_from_x509_svid_unchecked(&chain[0]).is_err());
+
+        let chain = issue(Malformation::CaLeaf);
+        let anchors = [anchor_from_trusted_cert(ca.certificate()).unwrap()];
+        let leaf = EndEntityCert::try_from(&chain[0]).unwrap();
+        let result = leaf.verify_for_usage(
+            ALGORITHMS,
+            &anchors,
+            &[],
+            UnixTime::since_unix_epoch(UNIX_EPOCH.elapsed().unwrap()),
+            KeyUsage::server_auth(),
+            None,
+            None,
+        );
+        assert!(matches!(result, Err(Error::CaUsedAsEndEntity)));
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tonic::{Result, Status};
+
+/// Scripted action of a server stream.
+#[derive(Clone, Debug)]
+pub enum Step<T> {
+    /// Sends a response.
+    Respond(T),
+    /// Ends the stream with an error status.
+    Fail(Status),
+    /// Waits before the next step.
+    Delay(Duration),
+    /// Ends the stream without an error, like an agent dropping the stream.
+    Close,
+}
+
+/// Controls a server-streaming method of [`FakeWorkloadApi`](crate::FakeWorkloadApi).
+///
+/// Every new stream plays the script, then follows the steps pushed while it is open. Calls fail
+/// with `Unimplemented` until a script is set.
+#[derive(Debug)]
+pub struct Feed<T> {
+    state: Mutex<FeedState<T>>,
+}
+
+#[derive(Debug)]
+struct FeedState<T> {
+    script: Option<Vec<Step<T>>>,
+    failures: VecDeque<Status>,
+    streams: Vec<UnboundedSender<Step<T>>>,
+}
+
+impl<T> Default for Feed<T> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(FeedState {
+                script: None,
+                failures: VecDeque::new(),
+                streams: Vec::new(),
+            }),
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> Feed<T> {
+    /// Sets the steps played by new streams, open streams are not affected.
+    pub fn script(&self, steps: impl IntoIterator<Item = Step<T>>) {
+        self.state().script = Some(steps.into_iter().collect());
+    }
+
+    /// Sends `response` to the open streams and makes it the only response of new streams, like
+    /// an agent rotating an SVID.
+    pub fn rotate(&self, response: T) {
+        self.script([Step::Respond(response.clone())]);
+        self.push(Step::Respond(response));
+    }
+
+    /// Sends `step` to the open streams only.
+    pub fn push(&self, step: Step<T>) {
+        self.state()
+            .streams
+            .retain(|stream| stream.send(step.clone()).is_ok());
+    }
+
+    /// Fails the next call with `status` instead of opening a stream, e.g.
+    /// [`Status::permission_denied`] or [`Status::unavailable`].
+    pub fn fail_next(&self, status: Status) {
+        self.state().failures.push_back(status);
+    }
+
+    /// Ends the open streams without an error.
+    pub fn close_streams(&self) {
+        self.state().streams.clear();
+    }
+
+    /// Returns the number of open streams.
+    pub fn open_streams(&self) -> usize {
+        let mut state = self.state();
+        state.streams.retain(|stream| !stream.is_closed());
+        state.streams.len()
+    }
+
+    pub(crate) fn open(&self) -> Result<BoxStream<'static, Result<T>>> {
+        let mut state = self.state();
+        if let Some(status) = state.failures.pop_front() {
+            return Err(status);
+        }
+        let Some(script) = &state.script else {
+            return Err(Status::unimplemented("not scripted"));
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        for step in script {
+            // the receiver is still alive
+            let _ = tx.send(step.clone());
+        }
+        state.streams.push(tx);
+
+        let stream = stream::unfold(Some(rx), |rx| async move {
+            let mut rx = rx?;
+            loop {
+                match rx.recv().await? {
+                    Step::Respond(x) => return Some((Ok(x), Some(rx))),
+                    Step::Fail(status) => return Some((Err(status), None)),
+                    Step::Delay(duration) => tokio::time::sleep(duration).await,
+                    Step::Close => return None,
+                }
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+
+    fn state(&self) -> MutexGuard<'_, FeedState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Controls a unary method of [`FakeWorkloadApi`](crate::FakeWorkloadApi).
+///
+/// Calls fail with `Unimplemented` until a response is set.
+#[derive(Debug)]
+pub struct Unary<T> {
+    state: Mutex<UnaryState<T>>,
+}
+
+#[derive(Debug)]
+struct UnaryState<T> {
+    response: Option<T>,
+    failures: VecDeque<Status>,
+    delay: Duration,
+}
+
+impl<T> Default for Unary<T> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(UnaryState {
+                response: None,
+                failures: VecDeque::new(),
+                delay: Duration::ZERO,
+            }),
+        }
+    }
+}
+
+impl<T: Clone> Unary<T> {
+    /// Sets the response of every following call.
+    pub fn respond(&self, response: T) {
+        self.state().response = Some(response);
+    }
+
+    /// Fails the next call with `status`.
+    pub fn fail_next(&self, status: Status) {
+        self.state().failures.push_back(status);
+    }
+
+    /// Delays every following call by `delay`.
+    pub fn delay(&self, delay: Duration) {
+        self.state().delay = delay;
+    }
+
+    pub(crate) async fn call(&self) -> Result<T> {
+        let (response, delay) = {
+            let mut state = self.state();
+            let response = match state.failures.pop_front() {
+                Some(status) => Err(status),
+                None => state
+                    .response
+                    .clone()
+                    .ok_or_else(|| Status::unimplemented("not set")),
+            };
+            (response, state.delay)
+        };
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        response
+    }
+
+    fn state(&self) -> MutexGuard<'_, UnaryState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,137 @@
+//! In-process fake of the SPIFFE Workload API, for testing code built on
+//! [`SpiffeWorkloadApiClient`](spiffe::client::SpiffeWorkloadApiClient) without a SPIRE agent,
+//! and a [`TestCa`] issuing the SVIDs, bundles and CRLs to serve.
+//!
+//! ```no_run
+//! # async fn example(response: spiffe_proto::X509SvidResponse) {
+//! use std::sync::Arc;
+//!
+//! use spiffe_testkit::{FakeWorkloadApi, Step, TestServer};
+//!
+//! let api = Arc::new(FakeWorkloadApi::new());
+//! api.x509_svid().script([Step::Respond(response.clone())]);
+//!
+//! let server = TestServer::duplex(api);
+//! let client = server.client();
+//! let mut svids = client.fetch_x509_svid().await.unwrap();
+//!
+//! // later on
+//! server.api().x509_svid().rotate(response);
+//! # }
+//! ```
+
+mod api;
+mod ca;
+mod feed;
+mod server;
+
+pub use self::{
+    api::FakeWorkloadApi,
+    ca::{IssueOptions, KeyType, Malformation, TestCa},
+    feed::{Feed, Step, Unary},
+    server::TestServer,
+};
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use futures_util::StreamExt;
+    use spiffe::client::Audiences;
+    use spiffe_proto::{JwtSvidResponse, X509Svid as ProtoX509Svid, X509SvidResponse};
+    use tonic::{Code, Status};
+
+    use super::*;
+
+    fn response(cert: &'static [u8]) -> X509SvidResponse {
+        // smallest DER the certificate splitter accepts, the client never parses it
+        let svid = ProtoX509Svid {
+            spiffe_id: "spiffe://example.org/workload".into(),
+            x509_svid: cert.into(),
+            x509_svid_key: b"key".as_slice().into(),
+            bundle: [0x30, 0x00].as_slice().into(),
+            hint: String::new(),
+        };
+
+        X509SvidResponse {
+            svids: vec![svid],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplex_rotation() {
+        let api = Arc::new(FakeWorkloadApi::new());
+        api.x509_svid().script([
+            Step::Delay(Duration::from_millis(10)),
+            Step::Respond(response(&[0x30, 0x00])),
+        ]);
+        let server = TestServer::duplex(api);
+        let client = server.client();
+
+        let mut svids = client.fetch_x509_svid().await.unwrap();
+        let first = svids.next().await.unwrap();
+        assert_eq!(first.svids[0].svid()[0].as_ref(), [0x30, 0x00]);
+
+        server
+            .api()
+            .x509_svid()
+            .rotate(response(&[0x30, 0x01, 0x00]));
+        let second = svids.next().await.unwrap();
+        assert_eq!(second.svids[0].svid()[0].as_ref(), [0x30, 0x01, 0x00]);
+
+        server.api().x509_svid().close_streams();
+        assert!(svids.next().await.is_none());
+
+        // a new stream starts with the rotated SVID
+        let mut svids = client.fetch_x509_svid().await.unwrap();
+        let third = svids.next().await.unwrap();
+        assert_eq!(third.svids, second.svids);
+    }
+
+    #[tokio::test]
+    async fn test_unix_errors() {
+        let api = Arc::new(FakeWorkloadApi::new());
+        let server = TestServer::unix(api).unwrap();
+        let socket = server.socket_path().unwrap().to_owned();
+        assert!(socket.exists());
+
+        let client = server.client();
+        let audiences = Audiences::new("db").unwrap();
+        let err = client.fetch_jwt_svid(&audiences, None).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unimplemented);
+
+        server
+            .api()
+            .jwt_svid()
+            .respond(JwtSvidResponse { svids: vec![] });
+        server
+            .api()
+            .jwt_svid()
+            .fail_next(Status::permission_denied("no identity issued"));
+        let err = client.fetch_jwt_svid(&audiences, None).await.unwrap_err();
+        assert_eq!(err.code(), Code::PermissionDenied);
+        assert!(client.fetch_jwt_svid(&audiences, None).await.is_ok());
+
+        server.api().x509_svid().script([
+            Step::Respond(response(&[0x30, 0x00])),
+            Step::Fail(Status::unavailable("agent restarting")),
+        ]);
+        server
+            .api()
+            .x509_svid()
+            .fail_next(Status::unavailable("agent starting"));
+        let Err(err) = client.fetch_x509_svid().await else {
+            panic!("the call must fail");
+        };
+        assert_eq!(err.code(), Code::Unavailable);
+
+        // the client ends the stream on the error
+        let mut svids = client.fetch_x509_svid().await.unwrap();
+        assert!(svids.next().await.is_some());
+        assert!(svids.next().await.is_none());
+
+        drop(server);
+        assert!(!socket.exists());
+    }
+}
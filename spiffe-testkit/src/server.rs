@@ -0,0 +1,180 @@
+use std::{
+    env,
+    error::Error,
+    fs,
+    future::Future,
+    io::Result as IoResult,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use futures_util::{Stream, stream};
+use http::Uri;
+use hyper_util::rt::TokioIo;
+use spiffe::client::{SpiffeWorkloadApiClient, WorkloadEndpoint};
+use spiffe_proto::server::SpiffeWorkloadApiServer;
+use tokio::{
+    io::{self, AsyncRead, AsyncWrite, DuplexStream},
+    net::UnixListener,
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinHandle,
+};
+use tonic::transport::{Channel, Endpoint, Server, server::Connected};
+use tower_service::Service;
+
+use crate::FakeWorkloadApi;
+
+const DUPLEX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// [`FakeWorkloadApi`] served in the background, stopped on drop.
+#[derive(Debug)]
+pub struct TestServer {
+    api: Arc<FakeWorkloadApi>,
+    transport: Transport,
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+enum Transport {
+    Duplex(UnboundedSender<DuplexStream>),
+    /// Socket inside a directory owned by the server.
+    Unix(PathBuf),
+}
+
+impl TestServer {
+    /// Serves `api` over in-memory connections.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn duplex(api: Arc<FakeWorkloadApi>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let incoming = stream::unfold(rx, |mut rx| async {
+            let conn = rx.recv().await?;
+            Some((Ok::<_, io::Error>(conn), rx))
+        });
+
+        Self {
+            task: spawn(&api, incoming),
+            api,
+            transport: Transport::Duplex(tx),
+        }
+    }
+
+    /// Serves `api` on a Unix domain socket in a new temporary directory.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn unix(api: Arc<FakeWorkloadApi>) -> IoResult<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "spiffe-testkit-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+
+        let socket = dir.join("agent.sock");
+        let listener = UnixListener::bind(&socket)?;
+        let incoming = stream::unfold(listener, |listener| async {
+            let conn = listener.accept().await.map(|(conn, _)| conn);
+            Some((conn, listener))
+        });
+
+        Ok(Self {
+            task: spawn(&api, incoming),
+            api,
+            transport: Transport::Unix(socket),
+        })
+    }
+
+    #[inline]
+    pub fn api(&self) -> &FakeWorkloadApi {
+        &self.api
+    }
+
+    /// Returns the socket path of a server created with [`TestServer::unix`].
+    pub fn socket_path(&self) -> Option<&Path> {
+        match &self.transport {
+            Transport::Duplex(_) => None,
+            Transport::Unix(socket) => Some(socket),
+        }
+    }
+
+    /// Returns the Workload API address, e.g. for `SPIFFE_ENDPOINT_SOCKET`, of a server created
+    /// with [`TestServer::unix`].
+    pub fn endpoint(&self) -> Option<String> {
+        self.socket_path()
+            .map(|socket| format!("unix://{}", socket.display()))
+    }
+
+    /// Returns a client connecting lazily to the server.
+    pub fn client(&self) -> SpiffeWorkloadApiClient<Channel> {
+        let channel = match &self.transport {
+            Transport::Duplex(tx) => Endpoint::from_static("http://localhost")
+                .connect_with_connector_lazy(DuplexConnector { tx: tx.clone() }),
+            Transport::Unix(socket) => WorkloadEndpoint::Unix(socket.clone())
+                .connect_lazy()
+                .expect("Unix endpoints are always valid"),
+        };
+
+        SpiffeWorkloadApiClient::new(channel)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        if let Transport::Unix(socket) = &self.transport
+            && let Some(dir) = socket.parent()
+        {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+fn spawn<S, IO, E>(api: &Arc<FakeWorkloadApi>, incoming: S) -> JoinHandle<()>
+where
+    S: Stream<Item = Result<IO, E>> + Send + 'static,
+    IO: AsyncRead + AsyncWrite + Connected + Unpin + Send + 'static,
+    E: Into<Box<dyn Error + Send + Sync>> + 'static,
+{
+    let service = SpiffeWorkloadApiServer::from_arc(api.clone()).into_service();
+    let server = Server::builder()
+        .add_service(service)
+        .serve_with_incoming(incoming);
+
+    tokio::spawn(async move {
+        // the server only stops on errors of the listener, which tests notice as failed calls
+        let _ = server.await;
+    })
+}
+
+#[derive(Clone, Debug)]
+struct DuplexConnector {
+    tx: UnboundedSender<DuplexStream>,
+}
+
+impl Service<Uri> for DuplexConnector {
+    type Response = TokioIo<DuplexStream>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = IoResult<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Uri) -> Self::Future {
+        let (client, server) = io::duplex(DUPLEX_BUFFER_SIZE);
+        let result = match self.tx.send(server) {
+            Ok(()) => Ok(TokioIo::new(client)),
+            Err(_) => Err(io::ErrorKind::ConnectionRefused.into()),
+        };
+
+        Box::pin(async move { result })
+    }
+}
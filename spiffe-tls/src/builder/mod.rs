@@ -153,11 +153,10 @@ where
                 )
             })?;
 
-        let (spiffe_id, cert_chain, key, local_bundle) = identity.into_parts();
-        let mut bundles = svid_context.federated_bundles.clone();
-        bundles.insert(spiffe_id.trust_domain().into_owned(), local_bundle);
+        let bundles = svid_context.bundle_set(&identity);
+        let (_, cert_chain, key, _) = identity.into_parts();
 
-        let trust_anchors = trust_anchors_from_bundles(bundles)?;
+        let trust_anchors = trust_anchors_from_bundles(bundles.into_inner())?;
         let parsed_crls = build_certificate_revocation_list(svid_context.crl.clone())?;
         let certified_key = build_certified_key(cert_chain, key, &crypto_provider)?;
 
@@ -184,7 +183,9 @@ where
     }))
 }
 
-fn trust_anchors_from_bundle(bundle: X509Bundle) -> Result<Vec<TrustAnchor<'static>>, Error> {
+pub(crate) fn trust_anchors_from_bundle(
+    bundle: X509Bundle,
+) -> Result<Vec<TrustAnchor<'static>>, Error> {
     bundle
         .into_parts()
         .into_iter()
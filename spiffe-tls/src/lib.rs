@@ -5,4 +5,7 @@ mod policy;
 mod resolver;
 mod verifier;
 
-pub use self::builder::{ClientConfigBuilder, ServerConfigBuilder};
+pub use self::{
+    builder::{ClientConfigBuilder, ServerConfigBuilder},
+    verifier::server_cert_verifier,
+};
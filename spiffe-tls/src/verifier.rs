@@ -1,7 +1,7 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use rustls_pki_types::SignatureVerificationAlgorithm;
-use spiffe::spiffe_id_from_x509_svid_unchecked;
+use spiffe::{X509Bundle, spiffe_id_from_x509_svid_unchecked};
 use spiffe_id::SpiffeId;
 use tokio_rustls::rustls::{
     CertificateError, DigitallySignedStruct, DistinguishedName, Error, PeerMisbehaved,
@@ -14,11 +14,47 @@ use tokio_rustls::rustls::{
 use upstre::Upstre;
 use webpki::{EndEntityCert, KeyUsage, RevocationOptionsBuilder, UnknownStatusPolicy};
 
-use crate::{error::pki_error, material::TlsMaterial, policy::PeerAuthorizePolicy};
+use crate::{
+    builder::trust_anchors_from_bundle, error::pki_error, material::TlsMaterial,
+    policy::PeerAuthorizePolicy,
+};
+
+/// Creates a verifier of servers presenting an X.509-SVID for exactly `spiffe_id`, issued by
+/// `bundle`, the bundle of its trust domain.
+///
+/// The trust anchors are fixed rather than following the Workload API, e.g. to authenticate the
+/// bundle endpoint of another trust domain.
+pub fn server_cert_verifier(
+    spiffe_id: SpiffeId,
+    bundle: X509Bundle,
+    crypto_provider: Arc<CryptoProvider>,
+) -> Result<Arc<dyn ServerCertVerifier>, Error> {
+    let material = TlsMaterial {
+        trust_anchors: HashMap::from([(
+            spiffe_id.trust_domain().into_owned(),
+            trust_anchors_from_bundle(bundle)?,
+        )]),
+        crls: Vec::new(),
+        certified_key: None,
+    };
+
+    Ok(Arc::new(SpiffeCertVerifier {
+        material: Material::Static(Arc::new(material)),
+        supported_schemes: crypto_provider.signature_verification_algorithms,
+        peer_spiffe_id_verifier: PeerAuthorizePolicy::Exact(spiffe_id),
+        require_client_cert: false,
+    }))
+}
+
+#[derive(Debug)]
+enum Material {
+    Stream(Upstre<TlsMaterial>),
+    Static(Arc<TlsMaterial>),
+}
 
 #[derive(Debug)]
 pub(crate) struct SpiffeCertVerifier {
-    material: Upstre<TlsMaterial>,
+    material: Material,
     supported_schemes: WebPkiSupportedAlgorithms,
     peer_spiffe_id_verifier: PeerAuthorizePolicy,
     require_client_cert: bool,
@@ -32,12 +68,38 @@ impl SpiffeCertVerifier {
         require_client_cert: bool,
     ) -> Self {
         Self {
-            material,
+            material: Material::Stream(material),
             supported_schemes: crypto_provider.signature_verification_algorithms,
             peer_spiffe_id_verifier,
             require_client_cert,
         }
     }
+
+    fn verify_peer(
+        &self,
+        key_usage: KeyUsage,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<SpiffeId, Error> {
+        let guard;
+        let material = match &self.material {
+            Material::Stream(material) => {
+                guard = material.value();
+                &**guard
+            }
+            Material::Static(material) => &**material,
+        };
+
+        verify_cert(
+            material,
+            &self.supported_schemes,
+            key_usage,
+            end_entity,
+            intermediates,
+            now,
+        )
+    }
 }
 
 impl ClientCertVerifier for SpiffeCertVerifier {
@@ -51,14 +113,7 @@ impl ClientCertVerifier for SpiffeCertVerifier {
         intermediates: &[CertificateDer<'_>],
         now: UnixTime,
     ) -> Result<ClientCertVerified, Error> {
-        let id = verify_cert(
-            &self.material.value(),
-            &self.supported_schemes,
-            KeyUsage::client_auth(),
-            end_entity,
-            intermediates,
-            now,
-        )?;
+        let id = self.verify_peer(KeyUsage::client_auth(), end_entity, intermediates, now)?;
 
         if !self.peer_spiffe_id_verifier.matches(&id) {
             return Err(Error::InvalidCertificate(
@@ -109,14 +164,7 @@ impl ServerCertVerifier for SpiffeCertVerifier {
         _: &[u8],
         now: UnixTime,
     ) -> Result<ServerCertVerified, Error> {
-        let peer_id = verify_cert(
-            &self.material.value(),
-            &self.supported_schemes,
-            KeyUsage::server_auth(),
-            end_entity,
-            intermediates,
-            now,
-        )?;
+        let peer_id = self.verify_peer(KeyUsage::server_auth(), end_entity, intermediates, now)?;
 
         if !self.peer_spiffe_id_verifier.matches(&peer_id) {
             return Err(Error::InvalidCertificate(
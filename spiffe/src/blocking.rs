@@ -0,0 +1,328 @@
+//! Synchronous facade over [`SpiffeWorkloadApiClient`] for programs without an async runtime.
+//!
+//! [`BlockingClient`] owns a current-thread tokio runtime, every call blocks the calling thread
+//! until the Workload API answers or the timeout elapses.
+
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use spiffe_id::{SpiffeId, TrustDomain};
+use tokio::runtime::{Builder, Runtime};
+use tonic::transport::Channel;
+
+use crate::{
+    BlockingError, JwtSvid,
+    client::{
+        Audiences, SpiffeWorkloadApiClient, WorkloadEndpoint, X509SvidContext,
+        X509SvidContextStream, next,
+    },
+};
+
+/// Default timeout of a single call.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Blocking Workload API client.
+#[derive(Debug)]
+pub struct BlockingClient {
+    // dropped before the runtime the channel tasks run on
+    client: SpiffeWorkloadApiClient<Channel>,
+    timeout: Duration,
+    runtime: Runtime,
+}
+
+impl BlockingClient {
+    /// Connects to the Workload API endpoint advertised by the `SPIFFE_ENDPOINT_SOCKET`
+    /// environment variable.
+    pub fn connect_default() -> Result<Self, BlockingError> {
+        let endpoint = WorkloadEndpoint::from_env().map_err(crate::ConnectError::from)?;
+
+        Self::connect_endpoint(&endpoint)
+    }
+
+    /// Connects to the Workload API endpoint at `addr`, e.g. `unix:///tmp/spire-agent/public/api.sock`
+    /// or `tcp://127.0.0.1:8081`.
+    pub fn connect(addr: &str) -> Result<Self, BlockingError> {
+        let endpoint = addr
+            .parse::<WorkloadEndpoint>()
+            .map_err(crate::ConnectError::from)?;
+
+        Self::connect_endpoint(&endpoint)
+    }
+
+    pub fn connect_endpoint(endpoint: &WorkloadEndpoint) -> Result<Self, BlockingError> {
+        Self::connect_endpoint_with_timeout(endpoint, DEFAULT_TIMEOUT)
+    }
+
+    /// Connects to `endpoint`, `timeout` applies to the connection and to every later call.
+    pub fn connect_endpoint_with_timeout(
+        endpoint: &WorkloadEndpoint,
+        timeout: Duration,
+    ) -> Result<Self, BlockingError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(BlockingError::Runtime)?;
+
+        let channel = block_on_timeout(&runtime, timeout, endpoint.connect())?
+            .map_err(crate::ConnectError::from)?;
+
+        Ok(Self {
+            client: SpiffeWorkloadApiClient::new(channel),
+            timeout,
+            runtime,
+        })
+    }
+
+    /// Sets the timeout of every later call.
+    ///
+    /// Default: `10s`
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Fetches the current X.509-SVIDs, CRLs and federated bundles.
+    pub fn fetch_x509_context(&self) -> Result<X509SvidContext, BlockingError> {
+        self.block_on(async {
+            let mut stream = self.client.fetch_x509_svid().await?;
+
+            next(&mut stream).await.ok_or(BlockingError::Closed)
+        })?
+    }
+
+    /// Fetches JWT-SVIDs for `audiences`, optionally only the one identified by `spiffe_id`.
+    pub fn fetch_jwt_svid(
+        &self,
+        audiences: &Audiences,
+        spiffe_id: Option<&SpiffeId>,
+    ) -> Result<Vec<JwtSvid>, BlockingError> {
+        self.block_on(self.client.fetch_jwt_svid(audiences, spiffe_id))?
+            .map_err(Into::into)
+    }
+
+    /// Fetches the current JWT bundles, as JWKS documents keyed by trust domain.
+    pub fn fetch_jwt_bundles(
+        &self,
+    ) -> Result<HashMap<TrustDomain<'static>, String>, BlockingError> {
+        self.block_on(async {
+            let mut stream = self.client.fetch_jwt_bundles().await?;
+
+            next(&mut stream).await.ok_or(BlockingError::Closed)
+        })?
+    }
+
+    /// Watches X.509 context updates, starting with the current one.
+    ///
+    /// The timeout only applies to opening the stream, use [`X509ContextIter::timeout`] to bound
+    /// the wait between updates.
+    pub fn watch_x509_context(&self) -> Result<X509ContextIter<'_>, BlockingError> {
+        let stream = self.block_on(self.client.fetch_x509_svid())??;
+
+        Ok(X509ContextIter {
+            runtime: &self.runtime,
+            stream: Some(stream),
+            timeout: None,
+        })
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> Result<F::Output, BlockingError> {
+        block_on_timeout(&self.runtime, self.timeout, future)
+    }
+}
+
+/// Blocking iterator over X.509 context updates, see [`BlockingClient::watch_x509_context`].
+///
+/// The iterator ends once the stream is closed, a timeout is yielded at most once before it ends.
+pub struct X509ContextIter<'a> {
+    runtime: &'a Runtime,
+    stream: Option<X509SvidContextStream>,
+    timeout: Option<Duration>,
+}
+
+impl X509ContextIter<'_> {
+    /// Limits the wait for the next update.
+    ///
+    /// Default: no limit
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl Iterator for X509ContextIter<'_> {
+    type Item = Result<X509SvidContext, BlockingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let stream = self.stream.as_mut()?;
+
+        let item = match self.timeout {
+            Some(timeout) => block_on_timeout(self.runtime, timeout, next(stream)),
+            None => Ok(self.runtime.block_on(next(stream))),
+        };
+
+        match item {
+            Ok(Some(context)) => Some(Ok(context)),
+            Ok(None) => {
+                self.stream = None;
+                None
+            }
+            Err(e) => {
+                self.stream = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn block_on_timeout<F: Future>(
+    runtime: &Runtime,
+    timeout: Duration,
+    future: F,
+) -> Result<F::Output, BlockingError> {
+    runtime
+        .block_on(async { tokio::time::timeout(timeout, future).await })
+        .map_err(|_| BlockingError::Timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, TcpListener},
+        sync::{Arc, mpsc},
+        thread,
+    };
+
+    use spiffe_proto::{
+        JwtBundlesResponse, JwtSvid as ProtoJwtSvid, JwtSvidResponse, X509Svid as ProtoX509Svid,
+        X509SvidResponse,
+    };
+    use spiffe_testkit::{FakeWorkloadApi, Step, TestServer};
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    /// Serves `api` on a runtime of its own until the returned sender is dropped, a blocking
+    /// client cannot run inside one.
+    fn serve(api: Arc<FakeWorkloadApi>) -> (String, oneshot::Sender<()>) {
+        let (endpoint_tx, endpoint_rx) = mpsc::channel();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+
+        thread::spawn(move || {
+            let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                let server = TestServer::unix(api).unwrap();
+                endpoint_tx.send(server.endpoint().unwrap()).unwrap();
+                let _ = stop_rx.await;
+            });
+        });
+
+        (endpoint_rx.recv().unwrap(), stop_tx)
+    }
+
+    fn x509_response(cert: &'static [u8]) -> X509SvidResponse {
+        // smallest DER the certificate splitter accepts, the client never parses it
+        let svid = ProtoX509Svid {
+            spiffe_id: "spiffe://example.org/workload".into(),
+            x509_svid: cert.into(),
+            x509_svid_key: b"key".as_slice().into(),
+            bundle: [0x30, 0x00].as_slice().into(),
+            hint: String::new(),
+        };
+
+        X509SvidResponse {
+            svids: vec![svid],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fetch() {
+        let api = Arc::new(FakeWorkloadApi::new());
+        api.x509_svid()
+            .script([Step::Respond(x509_response(&[0x30, 0x00]))]);
+        api.jwt_svid().respond(JwtSvidResponse {
+            svids: vec![ProtoJwtSvid {
+                spiffe_id: "spiffe://example.org/workload".into(),
+                svid: "header.claims.signature".into(),
+                hint: String::new(),
+            }],
+        });
+        api.jwt_bundles().script([Step::Respond(JwtBundlesResponse {
+            bundles: HashMap::from([(
+                "spiffe://example.org".into(),
+                br#"{"keys":[]}"#.as_slice().into(),
+            )]),
+        })]);
+        let (endpoint, _stop) = serve(api);
+        let client = BlockingClient::connect(&endpoint).unwrap();
+
+        let context = client.fetch_x509_context().unwrap();
+        assert_eq!(context.svids.len(), 1);
+        assert_eq!(context.svids[0].svid()[0].as_ref(), [0x30, 0x00]);
+
+        let audiences = Audiences::new("db").unwrap();
+        let svids = client.fetch_jwt_svid(&audiences, None).unwrap();
+        assert_eq!(svids.len(), 1);
+        assert_eq!(
+            svids[0].spiffe_id().as_str(),
+            "spiffe://example.org/workload"
+        );
+
+        let bundles = client.fetch_jwt_bundles().unwrap();
+        let td = TrustDomain::new("example.org").unwrap();
+        assert_eq!(bundles[&td], r#"{"keys":[]}"#);
+    }
+
+    #[test]
+    fn test_watch() {
+        let api = Arc::new(FakeWorkloadApi::new());
+        api.x509_svid()
+            .script([Step::Respond(x509_response(&[0x30, 0x00]))]);
+        let (endpoint, _stop) = serve(api.clone());
+        let client = BlockingClient::connect(&endpoint).unwrap();
+
+        let mut updates = client
+            .watch_x509_context()
+            .unwrap()
+            .timeout(Duration::from_secs(10));
+        let first = updates.next().unwrap().unwrap();
+        assert_eq!(first.svids[0].svid()[0].as_ref(), [0x30, 0x00]);
+
+        api.x509_svid()
+            .push(Step::Respond(x509_response(&[0x30, 0x01, 0x00])));
+        let second = updates.next().unwrap().unwrap();
+        assert_eq!(second.svids[0].svid()[0].as_ref(), [0x30, 0x01, 0x00]);
+
+        api.x509_svid().close_streams();
+        assert!(updates.next().is_none());
+        assert!(updates.next().is_none());
+
+        // a timeout ends the iterator too
+        let mut updates = client
+            .watch_x509_context()
+            .unwrap()
+            .timeout(Duration::from_millis(50));
+        assert!(updates.next().unwrap().is_ok());
+        assert!(matches!(updates.next(), Some(Err(BlockingError::Timeout))));
+        assert!(updates.next().is_none());
+    }
+
+    #[test]
+    fn test_connect_error() {
+        // bind then drop to get a port nobody listens on
+        let addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        assert!(matches!(
+            BlockingClient::connect(&format!("tcp://{addr}")),
+            Err(BlockingError::Connect(crate::ConnectError::Transport(_)))
+        ));
+        assert!(matches!(
+            BlockingClient::connect("http://127.0.0.1:1"),
+            Err(BlockingError::Connect(crate::ConnectError::Endpoint(_)))
+        ));
+    }
+}
@@ -0,0 +1,385 @@
+//! The SPIFFE bundle format, a JWKS document holding the X.509 and JWT authorities of a trust
+//! domain, exchanged between federated trust domains.
+//!
+//! # References
+//!
+//! - [The SPIFFE Trust Domain and Bundle](https://github.com/spiffe/spiffe/blob/main/standards/SPIFFE_Trust_Domain_and_Bundle.md)
+
+use std::time::Duration;
+
+use base64ct::{Base64, Encoding};
+use rustls_pki_types::CertificateDer;
+use serde_json::{Map, Value as JsonValue, json};
+use spiffe_id::TrustDomain;
+
+use crate::{
+    BundleError, InvalidDerError, TokenError, X509Bundle,
+    der::spki_from_certificate,
+    jose::{Jwk, JwkSet},
+};
+
+const X509_SVID_USE: &str = "x509-svid";
+const JWT_SVID_USE: &str = "jwt-svid";
+
+/// Authorities of a trust domain, with the metadata of the SPIFFE bundle format.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TrustDomainBundle {
+    trust_domain: TrustDomain<'static>,
+    x509_authorities: Vec<CertificateDer<'static>>,
+    jwt_authorities: Vec<Jwk>,
+    sequence: Option<u64>,
+    refresh_hint: Option<Duration>,
+}
+
+impl TrustDomainBundle {
+    /// Creates a bundle without authorities.
+    pub fn new(trust_domain: TrustDomain<'_>) -> Self {
+        Self {
+            trust_domain: trust_domain.into_owned(),
+            x509_authorities: Vec::new(),
+            jwt_authorities: Vec::new(),
+            sequence: None,
+            refresh_hint: None,
+        }
+    }
+
+    /// Creates a bundle from the X.509 bundle and the JWT bundle of the Workload API.
+    pub fn from_bundles(
+        trust_domain: TrustDomain<'_>,
+        x509_bundle: Option<X509Bundle>,
+        jwt_bundle: JwkSet,
+    ) -> Result<Self, BundleError> {
+        let mut bundle = Self::new(trust_domain);
+        if let Some(x509_bundle) = x509_bundle {
+            bundle.x509_authorities = x509_bundle.into_parts();
+        }
+        for jwk in jwt_bundle.into_parts() {
+            bundle.add_jwt_authority(jwk)?;
+        }
+
+        Ok(bundle)
+    }
+
+    /// Parses a bundle document of `trust_domain`.
+    ///
+    /// Keys with an unknown `use`, or of a type this crate does not support, are skipped.
+    /// Everything else must be well-formed: `x509-svid` keys carry exactly one certificate
+    /// matching the key, and `jwt-svid` keys have unique key IDs.
+    pub fn from_json(trust_domain: TrustDomain<'_>, json: &str) -> Result<Self, BundleError> {
+        let value = serde_json::from_str(json).map_err(|_| BundleError::Malformed)?;
+
+        Self::from_value(trust_domain, &value)
+    }
+
+    /// Parses a bundle document of `trust_domain` from an already decoded JSON value.
+    pub fn from_value(
+        trust_domain: TrustDomain<'_>,
+        value: &JsonValue,
+    ) -> Result<Self, BundleError> {
+        let JsonValue::Object(document) = value else {
+            return Err(BundleError::Malformed);
+        };
+        let Some(JsonValue::Array(keys)) = document.get("keys") else {
+            return Err(BundleError::Malformed);
+        };
+
+        let mut bundle = Self::new(trust_domain);
+        bundle.sequence = u64_member(document, "spiffe_sequence")?;
+        bundle.refresh_hint = u64_member(document, "spiffe_refresh_hint")?.map(Duration::from_secs);
+
+        for key in keys {
+            let JsonValue::Object(jwk) = key else {
+                return Err(BundleError::Malformed);
+            };
+
+            match jwk.get("use").ok_or(BundleError::InvalidMember("use"))? {
+                JsonValue::String(x) if x == X509_SVID_USE => {
+                    if let Some(cert) = parse_x509_authority(key, jwk)? {
+                        bundle.x509_authorities.push(cert);
+                    }
+                }
+                JsonValue::String(x) if x == JWT_SVID_USE => match Jwk::from_value(key) {
+                    Ok(jwk) => bundle.add_jwt_authority(jwk)?,
+                    Err(TokenError::UnsupportedAlgorithm) => continue,
+                    Err(e) => return Err(e.into()),
+                },
+                JsonValue::String(_) => continue,
+                _ => return Err(BundleError::InvalidMember("use")),
+            }
+        }
+
+        Ok(bundle)
+    }
+
+    /// Serializes the bundle to a document in the SPIFFE bundle format.
+    pub fn to_json(&self) -> Result<String, BundleError> {
+        self.to_value().map(|x| x.to_string())
+    }
+
+    /// Serializes the bundle to a document in the SPIFFE bundle format.
+    ///
+    /// Fails if an X.509 authority is not a certificate with a supported key type.
+    pub fn to_value(&self) -> Result<JsonValue, BundleError> {
+        let mut keys = Vec::with_capacity(self.x509_authorities.len() + self.jwt_authorities.len());
+
+        for cert in &self.x509_authorities {
+            let spki = spki_from_certificate(cert).ok_or(InvalidDerError)?;
+            let mut jwk = Jwk::from_spki(spki)?
+                .with_public_use(X509_SVID_USE)
+                .to_value();
+            jwk["x5c"] = json!([Base64::encode_string(cert)]);
+            keys.push(jwk);
+        }
+        for jwk in &self.jwt_authorities {
+            keys.push(jwk.to_value());
+        }
+
+        let mut document = json!({ "keys": keys });
+        if let Some(sequence) = self.sequence {
+            document["spiffe_sequence"] = sequence.into();
+        }
+        if let Some(refresh_hint) = self.refresh_hint {
+            document["spiffe_refresh_hint"] = refresh_hint.as_secs().into();
+        }
+
+        Ok(document)
+    }
+
+    #[inline]
+    pub fn trust_domain(&self) -> &TrustDomain<'static> {
+        &self.trust_domain
+    }
+
+    #[inline]
+    pub fn x509_authorities(&self) -> &[CertificateDer<'static>] {
+        &self.x509_authorities
+    }
+
+    #[inline]
+    pub fn jwt_authorities(&self) -> &[Jwk] {
+        &self.jwt_authorities
+    }
+
+    /// The `spiffe_sequence` member, increased by the issuer on every change of the bundle.
+    #[inline]
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /// The `spiffe_refresh_hint` member, how often the bundle should be fetched again.
+    #[inline]
+    pub fn refresh_hint(&self) -> Option<Duration> {
+        self.refresh_hint
+    }
+
+    pub fn add_x509_authority(&mut self, cert: CertificateDer<'static>) {
+        self.x509_authorities.push(cert);
+    }
+
+    /// Adds a JWT authority, which must have a key ID not used by another one.
+    ///
+    /// Its `use` member is set to `jwt-svid`.
+    pub fn add_jwt_authority(&mut self, jwk: Jwk) -> Result<(), BundleError> {
+        let key_id = jwk.key_id().ok_or(BundleError::MissingKeyId)?;
+        if key_id.is_empty() {
+            return Err(BundleError::MissingKeyId);
+        }
+        if self
+            .jwt_authorities
+            .iter()
+            .any(|x| x.key_id() == Some(key_id))
+        {
+            return Err(BundleError::DuplicateKeyId(key_id.into()));
+        }
+
+        self.jwt_authorities.push(jwk.with_public_use(JWT_SVID_USE));
+        Ok(())
+    }
+
+    pub fn set_sequence(&mut self, sequence: Option<u64>) {
+        self.sequence = sequence;
+    }
+
+    pub fn set_refresh_hint(&mut self, refresh_hint: Option<Duration>) {
+        self.refresh_hint = refresh_hint;
+    }
+
+    /// Returns the X.509 authorities as an [`X509Bundle`], `None` if there are none.
+    pub fn x509_bundle(&self) -> Option<X509Bundle> {
+        if self.x509_authorities.is_empty() {
+            None
+        } else {
+            Some(X509Bundle {
+                bundle: self.x509_authorities.clone().into(),
+            })
+        }
+    }
+
+    /// Returns the JWT authorities as the JWKS of the Workload API JWT bundles.
+    pub fn jwt_bundle(&self) -> JwkSet {
+        JwkSet::new(self.jwt_authorities.clone())
+    }
+}
+
+/// A context holding only the X.509 authorities of the bundle, e.g. for `spiffe-tls`.
+#[cfg(feature = "client")]
+impl From<TrustDomainBundle> for crate::client::X509BundlesContext {
+    fn from(bundle: TrustDomainBundle) -> Self {
+        let mut bundles = std::collections::HashMap::new();
+        if let Some(x509_bundle) = bundle.x509_bundle() {
+            bundles.insert(bundle.trust_domain, x509_bundle);
+        }
+
+        Self {
+            crl: Vec::new(),
+            bundles,
+        }
+    }
+}
+
+fn u64_member(
+    document: &Map<String, JsonValue>,
+    name: &'static str,
+) -> Result<Option<u64>, BundleError> {
+    match document.get(name) {
+        None => Ok(None),
+        Some(x) => x.as_u64().map(Some).ok_or(BundleError::InvalidMember(name)),
+    }
+}
+
+/// Parses an `x509-svid` key, `None` if its key type is not supported.
+fn parse_x509_authority(
+    key: &JsonValue,
+    jwk: &Map<String, JsonValue>,
+) -> Result<Option<CertificateDer<'static>>, BundleError> {
+    let Some(JsonValue::Array(x5c)) = jwk.get("x5c") else {
+        return Err(BundleError::InvalidMember("x5c"));
+    };
+    let [JsonValue::String(cert)] = x5c.as_slice() else {
+        return Err(BundleError::CertificateCount);
+    };
+    let cert = Base64::decode_vec(cert).map_err(|_| BundleError::InvalidMember("x5c"))?;
+
+    let key = match Jwk::from_value(key) {
+        Ok(key) => key,
+        Err(TokenError::UnsupportedAlgorithm) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let spki = spki_from_certificate(&cert).ok_or(InvalidDerError)?;
+    match Jwk::from_spki(spki) {
+        Ok(x) if x.same_key(&key) => Ok(Some(cert.into())),
+        Ok(_) | Err(TokenError::UnsupportedAlgorithm) => Err(BundleError::KeyMismatch),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spiffe_testkit::TestCa;
+
+    use super::*;
+    use crate::jose::{Algorithm, SigningKey};
+
+    const TD: TrustDomain = TrustDomain::const_new("example.org");
+
+    fn x509_authority() -> CertificateDer<'static> {
+        TestCa::new(TD).certificate().clone()
+    }
+
+    fn jwt_authority(key_id: &str) -> Jwk {
+        SigningKey::generate(Algorithm::ES256)
+            .unwrap()
+            .public_key()
+            .clone()
+            .with_key_id(key_id)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cert = x509_authority();
+        let mut bundle = TrustDomainBundle::new(TD);
+        bundle.add_x509_authority(cert.clone());
+        bundle.add_jwt_authority(jwt_authority("a")).unwrap();
+        bundle.set_sequence(Some(42));
+        bundle.set_refresh_hint(Some(Duration::from_secs(300)));
+
+        let value = bundle.to_value().unwrap();
+        assert_eq!(value["spiffe_sequence"], 42);
+        assert_eq!(value["spiffe_refresh_hint"], 300);
+        assert_eq!(value["keys"][0]["use"], "x509-svid");
+        assert_eq!(value["keys"][0]["kty"], "EC");
+        assert_eq!(value["keys"][1]["use"], "jwt-svid");
+
+        let parsed = TrustDomainBundle::from_json(TD, &bundle.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, bundle);
+        assert_eq!(parsed.x509_bundle().unwrap().bundle(), [cert]);
+        assert!(
+            bundle.add_jwt_authority(jwt_authority("a")).is_err(),
+            "key IDs must be unique"
+        );
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        let x509 = TrustDomainBundle::from_bundles(
+            TD,
+            Some(X509Bundle::from_der(&x509_authority()).unwrap()),
+            JwkSet::default(),
+        )
+        .unwrap()
+        .to_value()
+        .unwrap()["keys"][0]
+            .clone();
+        let jwt = jwt_authority("a").with_public_use("jwt-svid").to_value();
+        let parse = |keys: JsonValue| TrustDomainBundle::from_value(TD, &json!({ "keys": keys }));
+
+        // unknown uses are ignored
+        let mut unknown = jwt.clone();
+        unknown["use"] = "wit-svid".into();
+        let bundle = parse(json!([x509, unknown])).unwrap();
+        assert_eq!(bundle.x509_authorities().len(), 1);
+        assert!(bundle.jwt_authorities().is_empty());
+
+        let mut missing_use = jwt.clone();
+        missing_use.as_object_mut().unwrap().remove("use");
+        assert!(matches!(
+            parse(json!([missing_use])),
+            Err(BundleError::InvalidMember("use"))
+        ));
+
+        let mut two_certs = x509.clone();
+        two_certs["x5c"] = json!([x509["x5c"][0], x509["x5c"][0]]);
+        assert!(matches!(
+            parse(json!([two_certs])),
+            Err(BundleError::CertificateCount)
+        ));
+
+        let mut mismatch = jwt.clone();
+        mismatch["use"] = "x509-svid".into();
+        mismatch["x5c"] = x509["x5c"].clone();
+        assert!(matches!(
+            parse(json!([mismatch])),
+            Err(BundleError::KeyMismatch)
+        ));
+
+        let mut no_kid = jwt.clone();
+        no_kid.as_object_mut().unwrap().remove("kid");
+        assert!(matches!(
+            parse(json!([no_kid])),
+            Err(BundleError::MissingKeyId)
+        ));
+        assert!(matches!(
+            parse(json!([jwt, jwt])),
+            Err(BundleError::DuplicateKeyId(_))
+        ));
+
+        assert!(matches!(
+            TrustDomainBundle::from_value(TD, &json!({ "keys": [], "spiffe_sequence": -1 })),
+            Err(BundleError::InvalidMember("spiffe_sequence"))
+        ));
+        assert!(matches!(
+            TrustDomainBundle::from_json(TD, r#"{ "spiffe_sequence": 1 }"#),
+            Err(BundleError::Malformed)
+        ));
+    }
+}
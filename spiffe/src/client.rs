@@ -1,20 +1,32 @@
+mod context;
+mod convert;
+mod endpoint;
+mod events;
+mod request;
 mod stream;
+#[cfg(feature = "transport")]
+mod transport;
 mod types;
 
-use std::iter;
-
 use http::Uri;
 use http_body::Body;
 use prost::bytes::Bytes;
+use spiffe_id::SpiffeId;
 use tonic::{Result, Status, body::Body as TonicBody, client::GrpcService};
 
+#[cfg(feature = "transport")]
+pub(crate) use self::stream::next;
 use self::types::JwtSvidContext;
 pub use self::{
+    context::{X509Context, X509ContextStream, X509ContextUpdate},
+    endpoint::{SPIFFE_ENDPOINT_SOCKET, WorkloadEndpoint},
+    events::{Authority, ChangeDetector, ChangeEvent, Diff},
+    request::{Audiences, JwtSvidRequest, WitSvidRequest},
     stream::{
         JwtBundlesStream, WitBundlesStream, WitSvidContextStream, X509BundlesContextStream,
         X509SvidContextStream,
     },
-    types::{WitSvidContext, X509BundlesContext, X509SvidContext},
+    types::{BundleSet, WitSvidContext, X509BundlesContext, X509SvidContext},
 };
 use crate::{JwtSvid, SpiffeError, StdError};
 
@@ -29,6 +41,27 @@ pub struct SpiffeWorkloadApiClient<T> {
     client: spiffe_proto::client::SpiffeWorkloadApiClient<T>,
 }
 
+#[cfg(feature = "transport")]
+impl SpiffeWorkloadApiClient<tonic::transport::Channel> {
+    /// Connects to the Workload API endpoint advertised by the `SPIFFE_ENDPOINT_SOCKET`
+    /// environment variable.
+    pub async fn connect_default() -> Result<Self, crate::ConnectError> {
+        Self::connect_endpoint(&WorkloadEndpoint::from_env()?).await
+    }
+
+    /// Connects to the Workload API endpoint at `addr`, e.g. `unix:///tmp/spire-agent/public/api.sock`
+    /// or `tcp://127.0.0.1:8081`.
+    pub async fn connect(addr: &str) -> Result<Self, crate::ConnectError> {
+        Self::connect_endpoint(&addr.parse()?).await
+    }
+
+    pub async fn connect_endpoint(
+        endpoint: &WorkloadEndpoint,
+    ) -> Result<Self, crate::ConnectError> {
+        Ok(Self::new(endpoint.connect().await?))
+    }
+}
+
 impl<T> SpiffeWorkloadApiClient<T>
 where
     T: GrpcService<TonicBody> + Clone,
@@ -83,18 +116,33 @@ where
         Ok(X509BundlesContextStream(response.into_inner()))
     }
 
+    /// Watches both `FetchX509SVID` and `FetchX509Bundles`, yielding merged snapshots.
+    ///
+    /// Unlike [`X509SvidContext::federated_bundles`], which is limited to the trust domains the
+    /// registration entry federates with, the snapshots hold every bundle the workload trusts.
+    pub async fn fetch_x509_context(&self) -> Result<X509ContextStream> {
+        let svids = self.fetch_x509_svid().await?;
+        let bundles = self.fetch_x509_bundles().await?;
+
+        Ok(X509ContextStream::new(svids, bundles))
+    }
+
+    /// Fetches JWT-SVIDs for `audiences`, optionally only the one identified by `spiffe_id`.
     pub async fn fetch_jwt_svid(
         &self,
-        audience: impl Into<String>,
-        more_audiences: impl Into<Vec<String>>,
-        spiffe_id: Option<String>,
+        audiences: &Audiences,
+        spiffe_id: Option<&SpiffeId>,
     ) -> Result<Vec<JwtSvid>> {
-        let request = spiffe_proto::JwtSvidRequest {
-            audience: iter::once(audience.into())
-                .chain(more_audiences.into())
-                .collect(),
-            spiffe_id: spiffe_id.unwrap_or_default(),
-        };
+        let mut request = JwtSvidRequest::new(audiences.clone());
+        if let Some(spiffe_id) = spiffe_id {
+            request = request.with_spiffe_id(spiffe_id.clone());
+        }
+
+        self.fetch_jwt_svid_with(request).await
+    }
+
+    pub async fn fetch_jwt_svid_with(&self, request: JwtSvidRequest) -> Result<Vec<JwtSvid>> {
+        let request = spiffe_proto::JwtSvidRequest::from(request);
         let response = self.client.clone().fetch_jwt_svid(request).await?;
 
         response
@@ -111,10 +159,24 @@ where
         Ok(JwtBundlesStream(response.into_inner()))
     }
 
-    pub async fn fetch_wit_svid(&self, spiffe_id: Option<String>) -> Result<WitSvidContextStream> {
-        let request = spiffe_proto::WitSvidRequest {
-            spiffe_id: spiffe_id.unwrap_or_default(),
-        };
+    /// Fetches WIT-SVIDs, optionally only the one identified by `spiffe_id`.
+    pub async fn fetch_wit_svid(
+        &self,
+        spiffe_id: Option<&SpiffeId>,
+    ) -> Result<WitSvidContextStream> {
+        let mut request = WitSvidRequest::new();
+        if let Some(spiffe_id) = spiffe_id {
+            request = request.with_spiffe_id(spiffe_id.clone());
+        }
+
+        self.fetch_wit_svid_with(request).await
+    }
+
+    pub async fn fetch_wit_svid_with(
+        &self,
+        request: WitSvidRequest,
+    ) -> Result<WitSvidContextStream> {
+        let request = spiffe_proto::WitSvidRequest::from(request);
         let response = self.client.clone().fetch_wit_svid(request).await?;
 
         Ok(WitSvidContextStream(response.into_inner()))
@@ -132,7 +194,7 @@ where
         &self,
         audience: impl Into<String>,
         svid: impl Into<String>,
-    ) -> Result<(spiffe_id::SpiffeId, serde_json::Value)> {
+    ) -> Result<(SpiffeId, serde_json::Value)> {
         let request = spiffe_proto::ValidateJwtSvidRequest {
             audience: audience.into(),
             svid: svid.into(),
@@ -140,40 +202,15 @@ where
         let response = self.client.clone().validate_jwt_svid(request).await?;
 
         let spiffe_proto::ValidateJwtSvidResponse { spiffe_id, claims } = response.into_inner();
-        let spiffe_id = spiffe_id::SpiffeId::new(spiffe_id).map_err(SpiffeError::SpiffeId)?;
+        let spiffe_id = SpiffeId::new(spiffe_id).map_err(SpiffeError::SpiffeId)?;
 
-        Ok((
-            spiffe_id,
-            claims.map_or(serde_json::Value::Null, |v| {
-                claims_from_prost_value(prost_types::Value {
-                    kind: Some(prost_types::value::Kind::StructValue(v)),
-                })
-            }),
-        ))
-    }
-}
+        let claims = match claims {
+            Some(claims) => crate::json::json_from_struct(claims)
+                .map(serde_json::Value::Object)
+                .map_err(SpiffeError::Json)?,
+            None => serde_json::Value::Null,
+        };
 
-// blocked by https://github.com/tokio-rs/prost/issues/852
-#[cfg(feature = "jwt")]
-fn claims_from_prost_value(i: prost_types::Value) -> serde_json::Value {
-    use prost_types::value::Kind;
-    use serde_json::{Number, Value};
-
-    let i = match i.kind {
-        Some(i) => i,
-        None => return Value::Null,
-    };
-
-    match i {
-        Kind::NullValue(_) => Value::Null,
-        Kind::NumberValue(n) => Number::from_f64(n).map_or(Value::Null, Value::Number),
-        Kind::StringValue(s) => Value::String(s),
-        Kind::BoolValue(b) => Value::Bool(b),
-        Kind::StructValue(x) => x
-            .fields
-            .into_iter()
-            .map(|(k, v)| (k, claims_from_prost_value(v)))
-            .collect(),
-        Kind::ListValue(x) => x.values.into_iter().map(claims_from_prost_value).collect(),
+        Ok((spiffe_id, claims))
     }
 }
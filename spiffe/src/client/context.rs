@@ -0,0 +1,303 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use rustls_pki_types::CertificateRevocationListDer;
+use spiffe_id::TrustDomain;
+
+use super::{
+    X509BundlesContextStream, X509SvidContextStream,
+    events::{ChangeDetector, ChangeEvent},
+    types::{BundleSet, X509BundlesContext, X509SvidContext},
+};
+use crate::{X509Bundle, X509Svid};
+
+/// Snapshot merging the latest `FetchX509SVID` and `FetchX509Bundles` responses.
+///
+/// Bundles are merged in this order, later ones win for the same trust domain:
+/// 1. the federated bundles of the SVID response
+/// 2. the bundles of the SVIDs' own trust domains
+/// 3. the bundles of the bundles response, which covers every trust domain the workload trusts
+///
+/// CRLs of both responses are combined.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct X509Context {
+    pub svids: Vec<X509Svid>,
+    pub crl: Vec<CertificateRevocationListDer<'static>>,
+    pub bundles: HashMap<TrustDomain<'static>, X509Bundle>,
+}
+
+impl X509Context {
+    pub fn merge(svid_context: &X509SvidContext, bundles_context: &X509BundlesContext) -> Self {
+        let mut bundles = svid_context.merged_bundles(&svid_context.svids);
+        bundles.extend(
+            bundles_context
+                .bundles
+                .iter()
+                .map(|(td, bundle)| (td.clone(), bundle.clone())),
+        );
+
+        let mut crl = bundles_context.crl.clone();
+        for x in &svid_context.crl {
+            if !crl.contains(x) {
+                crl.push(x.clone());
+            }
+        }
+
+        Self {
+            svids: svid_context.svids.clone(),
+            crl,
+            bundles,
+        }
+    }
+
+    /// Returns the default SVID, the first one sent by the Workload API.
+    #[inline]
+    pub fn default_svid(&self) -> Option<&X509Svid> {
+        self.svids.first()
+    }
+
+    pub fn bundle_set(&self) -> BundleSet {
+        self.bundles.clone().into()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct X509ContextUpdate {
+    pub context: X509Context,
+    /// Changes from the previous snapshot, the first one is compared against an empty context.
+    pub events: Vec<ChangeEvent>,
+}
+
+/// Stream of merged [`X509Context`] snapshots, see
+/// [`SpiffeWorkloadApiClient::fetch_x509_context`](super::SpiffeWorkloadApiClient::fetch_x509_context).
+///
+/// The first snapshot is yielded once both streams have answered, later ones whenever either
+/// stream sends a change, re-sent responses are skipped. The stream ends when either stream ends.
+pub struct X509ContextStream {
+    svids: X509SvidContextStream,
+    bundles: X509BundlesContextStream,
+    svid_context: Option<X509SvidContext>,
+    bundles_context: Option<X509BundlesContext>,
+    detector: ChangeDetector<X509Context>,
+    // set once either stream ends, an update received in the same poll is still yielded
+    ended: bool,
+}
+
+impl X509ContextStream {
+    pub(super) fn new(svids: X509SvidContextStream, bundles: X509BundlesContextStream) -> Self {
+        Self {
+            svids,
+            bundles,
+            svid_context: None,
+            bundles_context: None,
+            detector: ChangeDetector::new(),
+            ended: false,
+        }
+    }
+}
+
+impl Stream for X509ContextStream {
+    type Item = X509ContextUpdate;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if this.ended {
+                return Poll::Ready(None);
+            }
+
+            let mut updated = false;
+
+            match Pin::new(&mut this.svids).poll_next(cx) {
+                Poll::Ready(Some(x)) => {
+                    this.svid_context = Some(x);
+                    updated = true;
+                }
+                Poll::Ready(None) => this.ended = true,
+                Poll::Pending => {}
+            }
+            if !this.ended {
+                match Pin::new(&mut this.bundles).poll_next(cx) {
+                    Poll::Ready(Some(x)) => {
+                        this.bundles_context = Some(x);
+                        updated = true;
+                    }
+                    Poll::Ready(None) => this.ended = true,
+                    Poll::Pending => {}
+                }
+            }
+
+            if !updated {
+                // both streams are pending and will wake the task, unless one ended
+                return if this.ended {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Pending
+                };
+            }
+
+            let (Some(svid_context), Some(bundles_context)) =
+                (&this.svid_context, &this.bundles_context)
+            else {
+                continue;
+            };
+
+            let context = X509Context::merge(svid_context, bundles_context);
+            let events = this.detector.update(context.clone());
+            if events.is_empty() {
+                continue;
+            }
+
+            return Poll::Ready(Some(X509ContextUpdate { context, events }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Authority, Diff};
+
+    fn bundle(der: &[u8]) -> X509Bundle {
+        X509Bundle::from_der(der).unwrap()
+    }
+
+    fn td(td: &str) -> TrustDomain<'static> {
+        TrustDomain::new(td).unwrap().into_owned()
+    }
+
+    #[test]
+    fn test_merge_precedence() {
+        let svid_context = X509SvidContext {
+            svids: vec![],
+            crl: vec![vec![1].into()],
+            federated_bundles: HashMap::from([
+                (td("example.com"), bundle(&[0x30, 0x00])),
+                (td("example.net"), bundle(&[0x30, 0x00])),
+            ]),
+        };
+        let bundles_context = X509BundlesContext {
+            crl: vec![vec![1].into(), vec![2].into()],
+            bundles: HashMap::from([(td("example.com"), bundle(&[0x30, 0x01, 0x00]))]),
+        };
+
+        let context = X509Context::merge(&svid_context, &bundles_context);
+        assert_eq!(context.bundles.len(), 2);
+        assert_eq!(
+            context.bundles[&td("example.com")],
+            bundle(&[0x30, 0x01, 0x00])
+        );
+        assert_eq!(context.crl.len(), 2);
+    }
+
+    #[test]
+    fn test_events() {
+        let old = X509Context {
+            svids: vec![],
+            crl: vec![],
+            bundles: HashMap::from([
+                (td("example.com"), bundle(&[0x30, 0x00])),
+                (td("example.net"), bundle(&[0x30, 0x00])),
+            ]),
+        };
+        let mut new = old.clone();
+        new.bundles.remove(&td("example.net"));
+        new.bundles
+            .insert(td("example.com"), bundle(&[0x30, 0x01, 0x00]));
+        new.bundles.insert(td("example.org"), bundle(&[0x30, 0x00]));
+
+        let authority = |der: &[u8]| Authority::X509(der.to_vec().into());
+        assert_eq!(
+            old.diff(&new),
+            [
+                ChangeEvent::BundleAuthorityAdded {
+                    td: td("example.com"),
+                    authority: authority(&[0x30, 0x01, 0x00]),
+                },
+                ChangeEvent::BundleAuthorityRemoved {
+                    td: td("example.com"),
+                    authority: authority(&[0x30, 0x00]),
+                },
+                ChangeEvent::TrustDomainAdded {
+                    td: td("example.org")
+                },
+                ChangeEvent::BundleAuthorityAdded {
+                    td: td("example.org"),
+                    authority: authority(&[0x30, 0x00]),
+                },
+                ChangeEvent::BundleAuthorityRemoved {
+                    td: td("example.net"),
+                    authority: authority(&[0x30, 0x00]),
+                },
+                ChangeEvent::TrustDomainRemoved {
+                    td: td("example.net")
+                },
+            ]
+        );
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[cfg(feature = "transport")]
+    #[tokio::test]
+    async fn test_stream_end() {
+        use std::{sync::Arc, time::Duration};
+
+        use prost::bytes::Bytes;
+        use spiffe_proto::{X509BundlesResponse, X509Svid as ProtoX509Svid, X509SvidResponse};
+        use spiffe_testkit::{FakeWorkloadApi, Step, TestServer};
+
+        use crate::client::{SpiffeWorkloadApiClient, next};
+
+        fn svid_response(cert: &'static [u8]) -> X509SvidResponse {
+            X509SvidResponse {
+                svids: vec![ProtoX509Svid {
+                    spiffe_id: "spiffe://example.org/workload".into(),
+                    x509_svid: Bytes::from_static(cert),
+                    x509_svid_key: Bytes::from_static(b"key"),
+                    bundle: Bytes::from_static(&[0x30, 0x00]),
+                    hint: String::new(),
+                }],
+                ..Default::default()
+            }
+        }
+
+        let api = Arc::new(FakeWorkloadApi::new());
+        api.x509_svid()
+            .script([Step::Respond(svid_response(&[0x30, 0x00]))]);
+        api.x509_bundles()
+            .script([Step::Respond(X509BundlesResponse {
+                crl: vec![],
+                bundles: [(
+                    "spiffe://example.com".into(),
+                    Bytes::from_static(&[0x30, 0x00]),
+                )]
+                .into(),
+            })]);
+        let server = TestServer::unix(api.clone()).unwrap();
+        let client = SpiffeWorkloadApiClient::connect(&server.endpoint().unwrap())
+            .await
+            .unwrap();
+
+        let mut stream = client.fetch_x509_context().await.unwrap();
+        let first = next(&mut stream).await.unwrap();
+        assert_eq!(first.context.bundles.len(), 2);
+
+        // a rotation arriving along with the end of the bundles stream
+        api.x509_svid()
+            .push(Step::Respond(svid_response(&[0x30, 0x01, 0x00])));
+        api.x509_bundles().close_streams();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let second = next(&mut stream).await.unwrap();
+        assert_eq!(
+            second.context.svids[0].svid()[0].as_ref(),
+            [0x30, 0x01, 0x00]
+        );
+        assert!(next(&mut stream).await.is_none());
+    }
+}
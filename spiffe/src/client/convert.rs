@@ -0,0 +1,94 @@
+//! Conversions from the Workload API messages into the domain types.
+
+use prost::bytes::Bytes;
+use rustls_pki_types::CertificateDer;
+use spiffe_id::SpiffeId;
+use spiffe_proto::{JwtSvid as ProtoJwtSvid, WitSvid as ProtoWitSvid, X509Svid as ProtoX509Svid};
+
+use crate::{
+    InvalidDerError, JwtSvid, Secret, SpiffeError, WitSvid, X509Bundle, X509Svid,
+    split_certificates,
+};
+
+fn parse_hint(hint: String) -> Option<Box<str>> {
+    if hint.is_empty() {
+        None
+    } else {
+        Some(hint.into())
+    }
+}
+
+impl TryFrom<ProtoJwtSvid> for JwtSvid {
+    type Error = SpiffeError;
+
+    fn try_from(
+        ProtoJwtSvid {
+            spiffe_id,
+            svid,
+            hint,
+        }: ProtoJwtSvid,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            spiffe_id: SpiffeId::new(spiffe_id)?,
+            svid: Secret::new(svid),
+            hint: parse_hint(hint),
+        })
+    }
+}
+
+impl TryFrom<ProtoWitSvid> for WitSvid {
+    type Error = SpiffeError;
+
+    fn try_from(
+        ProtoWitSvid {
+            spiffe_id,
+            wit_svid,
+            wit_svid_key,
+            hint,
+        }: ProtoWitSvid,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            spiffe_id: SpiffeId::new(spiffe_id)?,
+            svid: Secret::new(wit_svid),
+            key: Secret::new(wit_svid_key),
+            hint: parse_hint(hint),
+        })
+    }
+}
+
+impl TryFrom<Bytes> for X509Bundle {
+    type Error = SpiffeError;
+
+    fn try_from(value: Bytes) -> Result<Self, Self::Error> {
+        Self::from_der(&value)
+    }
+}
+
+impl TryFrom<ProtoX509Svid> for X509Svid {
+    type Error = SpiffeError;
+
+    fn try_from(
+        ProtoX509Svid {
+            spiffe_id,
+            x509_svid,
+            x509_svid_key,
+            bundle,
+            hint,
+        }: ProtoX509Svid,
+    ) -> Result<Self, Self::Error> {
+        if x509_svid.is_empty() || x509_svid_key.is_empty() || bundle.is_empty() {
+            return Err(SpiffeError::InvalidDer(InvalidDerError));
+        }
+
+        Ok(Self {
+            spiffe_id: SpiffeId::new(spiffe_id)?,
+            svid: split_certificates(&x509_svid)
+                .map(|x| x.map(CertificateDer::into_owned))
+                .collect::<Result<_, _>>()?,
+            // avoids a copy when the buffer is not shared
+            key: Secret::new(x509_svid_key.into()),
+            bundle: X509Bundle::from_der(&bundle)?,
+            hint: parse_hint(hint),
+        })
+    }
+}
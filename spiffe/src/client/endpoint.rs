@@ -0,0 +1,201 @@
+use std::{
+    env,
+    fmt::{Display, Formatter, Result as FmtResult},
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::EndpointError;
+
+/// Name of the environment variable holding the Workload API endpoint address.
+pub const SPIFFE_ENDPOINT_SOCKET: &str = "SPIFFE_ENDPOINT_SOCKET";
+
+/// Address of a SPIFFE Workload API endpoint.
+///
+/// Parsed from the formats described in the SPIFFE Workload Endpoint standard:
+///
+/// - `unix:///path/to/socket` or `unix:path/to/socket` for Unix domain sockets
+/// - `tcp://192.0.2.1:8000` for TCP, where the host MUST be an IP address
+///
+/// <https://github.com/spiffe/spiffe/blob/main/standards/SPIFFE_Workload_Endpoint.md#4-locating-the-endpoint>
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum WorkloadEndpoint {
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl WorkloadEndpoint {
+    /// Reads the endpoint address from the `SPIFFE_ENDPOINT_SOCKET` environment variable.
+    pub fn from_env() -> Result<Self, EndpointError> {
+        let addr = env::var(SPIFFE_ENDPOINT_SOCKET).map_err(|_| EndpointError::NotConfigured)?;
+
+        addr.parse()
+    }
+}
+
+impl FromStr for WorkloadEndpoint {
+    type Err = EndpointError;
+
+    fn from_str(addr: &str) -> Result<Self, Self::Err> {
+        let (scheme, rem) = addr.split_once(':').ok_or(EndpointError::Scheme)?;
+
+        if rem.contains('?') {
+            return Err(EndpointError::Query);
+        }
+        if rem.contains('#') {
+            return Err(EndpointError::Fragment);
+        }
+
+        match scheme {
+            "unix" => parse_unix(rem),
+            "tcp" => parse_tcp(rem),
+            _ => Err(EndpointError::Scheme),
+        }
+    }
+}
+
+impl Display for WorkloadEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Unix(path) if path.is_absolute() => write!(f, "unix://{}", path.display()),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+            Self::Tcp(addr) => write!(f, "tcp://{addr}"),
+        }
+    }
+}
+
+fn parse_unix(rem: &str) -> Result<WorkloadEndpoint, EndpointError> {
+    let path = match rem.strip_prefix("//") {
+        // `unix:///path`, the authority component must be empty
+        Some(hier) => match hier.find('/') {
+            Some(0) => hier,
+            Some(_) => return Err(EndpointError::Authority),
+            None if hier.is_empty() => return Err(EndpointError::EmptyPath),
+            None => return Err(EndpointError::Authority),
+        },
+        // `unix:path`, opaque form
+        None => rem,
+    };
+
+    if path.is_empty() {
+        return Err(EndpointError::EmptyPath);
+    }
+
+    Ok(WorkloadEndpoint::Unix(path.into()))
+}
+
+fn parse_tcp(rem: &str) -> Result<WorkloadEndpoint, EndpointError> {
+    let Some(authority) = rem.strip_prefix("//") else {
+        return Err(EndpointError::Opaque);
+    };
+
+    if authority.contains('/') {
+        return Err(EndpointError::Path);
+    }
+    if authority.contains('@') {
+        return Err(EndpointError::UserInfo);
+    }
+
+    // `SocketAddr` accepts only an IP address with a port, which is exactly what the standard requires
+    authority
+        .parse()
+        .map(WorkloadEndpoint::Tcp)
+        .map_err(|_| EndpointError::Host)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, path::Path};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_unix() {
+        assert_eq!(
+            "unix:///tmp/agent.sock"
+                .parse::<WorkloadEndpoint>()
+                .unwrap(),
+            WorkloadEndpoint::Unix(PathBuf::from("/tmp/agent.sock"))
+        );
+        assert_eq!(
+            "unix:agent.sock".parse::<WorkloadEndpoint>().unwrap(),
+            WorkloadEndpoint::Unix(PathBuf::from("agent.sock"))
+        );
+        assert_eq!(
+            "unix:/tmp/agent.sock".parse::<WorkloadEndpoint>().unwrap(),
+            WorkloadEndpoint::Unix(Path::new("/tmp/agent.sock").into())
+        );
+
+        assert!(matches!(
+            "unix://host/agent.sock".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Authority)
+        ));
+        assert!(matches!(
+            "unix://".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::EmptyPath)
+        ));
+        assert!(matches!(
+            "unix:".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::EmptyPath)
+        ));
+        assert!(matches!(
+            "unix:///tmp/agent.sock?x=1".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Query)
+        ));
+        assert!(matches!(
+            "unix:///tmp/agent.sock#x".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Fragment)
+        ));
+    }
+
+    #[test]
+    fn test_parse_tcp() {
+        assert_eq!(
+            "tcp://127.0.0.1:8000".parse::<WorkloadEndpoint>().unwrap(),
+            WorkloadEndpoint::Tcp((Ipv4Addr::LOCALHOST, 8000).into())
+        );
+        assert!("tcp://[::1]:8000".parse::<WorkloadEndpoint>().is_ok());
+
+        assert!(matches!(
+            "tcp://localhost:8000".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Host)
+        ));
+        assert!(matches!(
+            "tcp://127.0.0.1".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Host)
+        ));
+        assert!(matches!(
+            "tcp://127.0.0.1:8000/path".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Path)
+        ));
+        assert!(matches!(
+            "tcp://user@127.0.0.1:8000".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::UserInfo)
+        ));
+        assert!(matches!(
+            "tcp:127.0.0.1:8000".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Opaque)
+        ));
+        assert!(matches!(
+            "http://127.0.0.1:8000".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Scheme)
+        ));
+        assert!(matches!(
+            "/tmp/agent.sock".parse::<WorkloadEndpoint>(),
+            Err(EndpointError::Scheme)
+        ));
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for addr in [
+            "unix:///tmp/agent.sock",
+            "unix:agent.sock",
+            "tcp://127.0.0.1:8000",
+        ] {
+            let endpoint = addr.parse::<WorkloadEndpoint>().unwrap();
+            assert_eq!(endpoint.to_string(), addr);
+        }
+    }
+}
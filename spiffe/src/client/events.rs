@@ -0,0 +1,351 @@
+//! Change detection between consecutive Workload API responses, e.g. for audit logs and metrics.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::SystemTime,
+};
+
+use rustls_pki_types::{CertificateDer, CertificateRevocationListDer};
+use spiffe_id::{SpiffeId, TrustDomain};
+
+use super::{X509BundlesContext, X509Context, X509SvidContext};
+use crate::{X509Bundle, X509Svid};
+
+/// Signing authority of a bundle.
+///
+/// Non-exhaustive, as the JWT variant depends on the `json` feature.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Authority {
+    X509(CertificateDer<'static>),
+
+    /// JSON Web Key, as re-serialized by `serde_json`.
+    #[cfg(feature = "json")]
+    Jwt(String),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum ChangeEvent {
+    SvidAdded {
+        id: SpiffeId,
+    },
+    SvidRemoved {
+        id: SpiffeId,
+    },
+    SvidRotated {
+        id: SpiffeId,
+        old_expiry: Option<SystemTime>,
+        new_expiry: Option<SystemTime>,
+    },
+    TrustDomainAdded {
+        td: TrustDomain<'static>,
+    },
+    TrustDomainRemoved {
+        td: TrustDomain<'static>,
+    },
+    BundleAuthorityAdded {
+        td: TrustDomain<'static>,
+        authority: Authority,
+    },
+    BundleAuthorityRemoved {
+        td: TrustDomain<'static>,
+        authority: Authority,
+    },
+    CrlUpdated,
+}
+
+/// Values whose changes can be described by [`ChangeEvent`]s.
+pub trait Diff {
+    /// Returns the events that lead from `self` to `new`, in a stable order.
+    fn diff(&self, new: &Self) -> Vec<ChangeEvent>;
+}
+
+impl Diff for X509SvidContext {
+    fn diff(&self, new: &Self) -> Vec<ChangeEvent> {
+        let mut events = svid_events(&self.svids, &new.svids);
+        events.extend(bundle_events(
+            &x509_authorities(&self.merged_bundles(&self.svids)),
+            &x509_authorities(&new.merged_bundles(&new.svids)),
+        ));
+        events.extend(crl_events(&self.crl, &new.crl));
+        events
+    }
+}
+
+impl Diff for X509BundlesContext {
+    fn diff(&self, new: &Self) -> Vec<ChangeEvent> {
+        let mut events = bundle_events(
+            &x509_authorities(&self.bundles),
+            &x509_authorities(&new.bundles),
+        );
+        events.extend(crl_events(&self.crl, &new.crl));
+        events
+    }
+}
+
+impl Diff for X509Context {
+    fn diff(&self, new: &Self) -> Vec<ChangeEvent> {
+        let mut events = svid_events(&self.svids, &new.svids);
+        events.extend(bundle_events(
+            &x509_authorities(&self.bundles),
+            &x509_authorities(&new.bundles),
+        ));
+        events.extend(crl_events(&self.crl, &new.crl));
+        events
+    }
+}
+
+/// JWT bundles as JWKS documents, invalid documents have no authorities.
+#[cfg(feature = "json")]
+impl Diff for HashMap<TrustDomain<'static>, String> {
+    fn diff(&self, new: &Self) -> Vec<ChangeEvent> {
+        bundle_events(&jwt_authorities(self), &jwt_authorities(new))
+    }
+}
+
+/// Keeps the last value and reports the changes of every update.
+#[derive(Clone, Debug, Default)]
+pub struct ChangeDetector<T> {
+    last: T,
+}
+
+impl<T: Diff + Default> ChangeDetector<T> {
+    pub fn new() -> Self {
+        Self { last: T::default() }
+    }
+
+    /// Replaces the last value with `new`, the first update is compared against an empty value.
+    pub fn update(&mut self, new: T) -> Vec<ChangeEvent> {
+        let events = self.last.diff(&new);
+        self.last = new;
+        events
+    }
+
+    #[inline]
+    pub fn last(&self) -> &T {
+        &self.last
+    }
+}
+
+fn svid_events(old: &[X509Svid], new: &[X509Svid]) -> Vec<ChangeEvent> {
+    let (old, new) = (svids_by_id(old), svids_by_id(new));
+
+    let mut events = Vec::new();
+    for (id, svid) in &new {
+        match old.get(id) {
+            None => events.push(ChangeEvent::SvidAdded { id: (*id).clone() }),
+            Some(old) if old.svid() != svid.svid() => events.push(ChangeEvent::SvidRotated {
+                id: (*id).clone(),
+                old_expiry: old.expiry(),
+                new_expiry: svid.expiry(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for id in old.keys().filter(|x| !new.contains_key(*x)) {
+        events.push(ChangeEvent::SvidRemoved { id: (*id).clone() });
+    }
+
+    events
+}
+
+/// The first SVID wins for the same SPIFFE ID.
+fn svids_by_id(svids: &[X509Svid]) -> BTreeMap<&SpiffeId, &X509Svid> {
+    let mut map = BTreeMap::new();
+    for svid in svids {
+        map.entry(svid.spiffe_id()).or_insert(svid);
+    }
+    map
+}
+
+type Authorities = BTreeMap<TrustDomain<'static>, Vec<Authority>>;
+
+fn x509_authorities(bundles: &HashMap<TrustDomain<'static>, X509Bundle>) -> Authorities {
+    bundles
+        .iter()
+        .map(|(td, bundle)| {
+            let authorities = bundle.bundle().iter().cloned().map(Authority::X509);
+            (td.clone(), authorities.collect())
+        })
+        .collect()
+}
+
+#[cfg(feature = "json")]
+fn jwt_authorities(bundles: &HashMap<TrustDomain<'static>, String>) -> Authorities {
+    use serde_json::Value as JsonValue;
+
+    bundles
+        .iter()
+        .map(|(td, jwks)| {
+            let keys = match serde_json::from_str::<JsonValue>(jwks) {
+                Ok(JsonValue::Object(mut jwks)) => match jwks.remove("keys") {
+                    Some(JsonValue::Array(keys)) => keys,
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            };
+            let authorities = keys.iter().map(|x| Authority::Jwt(x.to_string()));
+
+            (td.clone(), authorities.collect())
+        })
+        .collect()
+}
+
+fn bundle_events(old: &Authorities, new: &Authorities) -> Vec<ChangeEvent> {
+    let mut events = Vec::new();
+
+    for (td, authorities) in new {
+        let old_authorities = match old.get(td) {
+            Some(x) => x.as_slice(),
+            None => {
+                events.push(ChangeEvent::TrustDomainAdded { td: td.clone() });
+                &[]
+            }
+        };
+
+        for x in authorities.iter().filter(|x| !old_authorities.contains(x)) {
+            events.push(ChangeEvent::BundleAuthorityAdded {
+                td: td.clone(),
+                authority: x.clone(),
+            });
+        }
+        for x in old_authorities.iter().filter(|x| !authorities.contains(x)) {
+            events.push(ChangeEvent::BundleAuthorityRemoved {
+                td: td.clone(),
+                authority: x.clone(),
+            });
+        }
+    }
+
+    for (td, authorities) in old.iter().filter(|(x, _)| !new.contains_key(*x)) {
+        for x in authorities {
+            events.push(ChangeEvent::BundleAuthorityRemoved {
+                td: td.clone(),
+                authority: x.clone(),
+            });
+        }
+        events.push(ChangeEvent::TrustDomainRemoved { td: td.clone() });
+    }
+
+    events
+}
+
+fn crl_events(
+    old: &[CertificateRevocationListDer<'_>],
+    new: &[CertificateRevocationListDer<'_>],
+) -> Option<ChangeEvent> {
+    let changed = old.len() != new.len() || old.iter().any(|x| !new.contains(x));
+
+    changed.then_some(ChangeEvent::CrlUpdated)
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::bytes::Bytes;
+
+    use super::*;
+
+    fn td(td: &str) -> TrustDomain<'static> {
+        TrustDomain::new(td).unwrap().into_owned()
+    }
+
+    fn svid(cert: &'static [u8]) -> X509Svid {
+        spiffe_proto::X509Svid {
+            spiffe_id: "spiffe://example.org/workload".into(),
+            x509_svid: Bytes::from_static(cert),
+            x509_svid_key: Bytes::from_static(b"key"),
+            bundle: Bytes::from_static(&[0x30, 0x00]),
+            hint: String::new(),
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_x509_svid_context_events() {
+        let mut detector = ChangeDetector::<X509SvidContext>::new();
+
+        let first = X509SvidContext {
+            svids: vec![svid(&[0x30, 0x00])],
+            ..X509SvidContext::default()
+        };
+        let id = SpiffeId::new("spiffe://example.org/workload").unwrap();
+        let authority = Authority::X509(CertificateDer::from(vec![0x30, 0x00]));
+        assert_eq!(
+            detector.update(first.clone()),
+            [
+                ChangeEvent::SvidAdded { id: id.clone() },
+                ChangeEvent::TrustDomainAdded {
+                    td: td("example.org")
+                },
+                ChangeEvent::BundleAuthorityAdded {
+                    td: td("example.org"),
+                    authority: authority.clone(),
+                },
+            ]
+        );
+        // a re-sent response
+        assert!(detector.update(first).is_empty());
+
+        let second = X509SvidContext {
+            svids: vec![svid(&[0x30, 0x01, 0x00])],
+            crl: vec![vec![1].into()],
+            federated_bundles: HashMap::from([(
+                td("example.com"),
+                X509Bundle::from_der(&[0x30, 0x00]).unwrap(),
+            )]),
+        };
+        assert_eq!(
+            detector.update(second),
+            [
+                ChangeEvent::SvidRotated {
+                    id,
+                    old_expiry: None,
+                    new_expiry: None,
+                },
+                ChangeEvent::TrustDomainAdded {
+                    td: td("example.com")
+                },
+                ChangeEvent::BundleAuthorityAdded {
+                    td: td("example.com"),
+                    authority,
+                },
+                ChangeEvent::CrlUpdated,
+            ]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_jwt_bundle_events() {
+        let old = HashMap::from([
+            (
+                td("example.org"),
+                r#"{"keys":[{"kid":"a"},{"kid":"b"}]}"#.into(),
+            ),
+            (td("example.com"), r#"{"keys":[]}"#.into()),
+        ]);
+        let new = HashMap::from([(
+            td("example.org"),
+            r#"{"keys":[{"kid":"b"},{"kid":"c"}]}"#.into(),
+        )]);
+
+        assert_eq!(
+            old.diff(&new),
+            [
+                ChangeEvent::BundleAuthorityAdded {
+                    td: td("example.org"),
+                    authority: Authority::Jwt(r#"{"kid":"c"}"#.into()),
+                },
+                ChangeEvent::BundleAuthorityRemoved {
+                    td: td("example.org"),
+                    authority: Authority::Jwt(r#"{"kid":"a"}"#.into()),
+                },
+                ChangeEvent::TrustDomainRemoved {
+                    td: td("example.com")
+                },
+            ]
+        );
+    }
+}
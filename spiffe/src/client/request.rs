@@ -0,0 +1,189 @@
+use spiffe_id::SpiffeId;
+
+use crate::SpiffeError;
+
+/// A non-empty list of non-empty JWT audiences.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Audiences {
+    audiences: Box<[Box<str>]>,
+}
+
+impl Audiences {
+    /// Creates an audience list with a single audience.
+    pub fn new(audience: impl Into<Box<str>>) -> Result<Self, SpiffeError> {
+        Self::try_from_iter([audience])
+    }
+
+    /// Creates an audience list from an iterator, it must yield at least one audience.
+    pub fn try_from_iter<I>(audiences: I) -> Result<Self, SpiffeError>
+    where
+        I: IntoIterator,
+        I::Item: Into<Box<str>>,
+    {
+        let audiences = audiences
+            .into_iter()
+            .map(Into::into)
+            .map(|x: Box<str>| {
+                if x.is_empty() {
+                    Err(SpiffeError::InvalidAudience)
+                } else {
+                    Ok(x)
+                }
+            })
+            .collect::<Result<Box<[_]>, _>>()?;
+
+        if audiences.is_empty() {
+            return Err(SpiffeError::InvalidAudience);
+        }
+
+        Ok(Self { audiences })
+    }
+
+    #[inline]
+    pub fn first(&self) -> &str {
+        &self.audiences[0]
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.audiences.iter().map(AsRef::as_ref)
+    }
+}
+
+impl TryFrom<&str> for Audiences {
+    type Error = SpiffeError;
+
+    fn try_from(audience: &str) -> Result<Self, Self::Error> {
+        Self::new(audience)
+    }
+}
+
+impl TryFrom<String> for Audiences {
+    type Error = SpiffeError;
+
+    fn try_from(audience: String) -> Result<Self, Self::Error> {
+        Self::new(audience)
+    }
+}
+
+impl TryFrom<Vec<String>> for Audiences {
+    type Error = SpiffeError;
+
+    fn try_from(audiences: Vec<String>) -> Result<Self, Self::Error> {
+        Self::try_from_iter(audiences)
+    }
+}
+
+impl From<Audiences> for Vec<String> {
+    fn from(audiences: Audiences) -> Self {
+        audiences.audiences.into_iter().map(Into::into).collect()
+    }
+}
+
+/// Parameters of a `FetchJWTSVID` call.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct JwtSvidRequest {
+    audiences: Audiences,
+    spiffe_id: Option<SpiffeId>,
+}
+
+impl JwtSvidRequest {
+    pub fn new(audiences: Audiences) -> Self {
+        Self {
+            audiences,
+            spiffe_id: None,
+        }
+    }
+
+    /// Only requests the JWT-SVID for the given SPIFFE ID.
+    #[must_use]
+    pub fn with_spiffe_id(mut self, spiffe_id: SpiffeId) -> Self {
+        self.spiffe_id = Some(spiffe_id);
+        self
+    }
+
+    #[inline]
+    pub fn audiences(&self) -> &Audiences {
+        &self.audiences
+    }
+
+    #[inline]
+    pub fn spiffe_id(&self) -> Option<&SpiffeId> {
+        self.spiffe_id.as_ref()
+    }
+}
+
+impl From<JwtSvidRequest> for spiffe_proto::JwtSvidRequest {
+    fn from(
+        JwtSvidRequest {
+            audiences,
+            spiffe_id,
+        }: JwtSvidRequest,
+    ) -> Self {
+        Self {
+            audience: audiences.into(),
+            spiffe_id: spiffe_id.map(Into::into).unwrap_or_default(),
+        }
+    }
+}
+
+/// Parameters of a `FetchWITSVID` call.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct WitSvidRequest {
+    spiffe_id: Option<SpiffeId>,
+}
+
+impl WitSvidRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only requests the WIT-SVID for the given SPIFFE ID.
+    #[must_use]
+    pub fn with_spiffe_id(mut self, spiffe_id: SpiffeId) -> Self {
+        self.spiffe_id = Some(spiffe_id);
+        self
+    }
+
+    #[inline]
+    pub fn spiffe_id(&self) -> Option<&SpiffeId> {
+        self.spiffe_id.as_ref()
+    }
+}
+
+impl From<WitSvidRequest> for spiffe_proto::WitSvidRequest {
+    fn from(WitSvidRequest { spiffe_id }: WitSvidRequest) -> Self {
+        Self {
+            spiffe_id: spiffe_id.map(Into::into).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audiences() {
+        let audiences = Audiences::try_from_iter(["a", "b"]).unwrap();
+        assert_eq!(audiences.first(), "a");
+        assert_eq!(audiences.iter().collect::<Vec<_>>(), ["a", "b"]);
+
+        assert!(Audiences::new("").is_err());
+        assert!(Audiences::try_from_iter(["a", ""]).is_err());
+        assert!(Audiences::try_from(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_jwt_svid_request_into_proto() {
+        let id = SpiffeId::new("spiffe://example.org/service").unwrap();
+        let request = JwtSvidRequest::new(Audiences::new("aud").unwrap()).with_spiffe_id(id);
+
+        let proto = spiffe_proto::JwtSvidRequest::from(request);
+        assert_eq!(proto.audience, ["aud"]);
+        assert_eq!(proto.spiffe_id, "spiffe://example.org/service");
+
+        let proto = spiffe_proto::WitSvidRequest::from(WitSvidRequest::new());
+        assert_eq!(proto.spiffe_id, "");
+    }
+}
@@ -80,3 +80,8 @@ impl_stream!(
     spiffe_proto::WitBundlesResponse,
     WitBundlesContext => HashMap<TrustDomain<'static>, String>
 );
+
+#[cfg(feature = "transport")]
+pub(crate) async fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+    core::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+}
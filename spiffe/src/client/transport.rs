@@ -0,0 +1,76 @@
+use std::{
+    future::Future,
+    io::Result as IoResult,
+    net::SocketAddr,
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::Uri;
+use hyper_util::rt::TokioIo;
+use tokio::net::UnixStream;
+use tonic::transport::{Channel, Endpoint, Error};
+use tower_service::Service;
+
+use super::WorkloadEndpoint;
+
+// Placeholder, the connector ignores the URI of the endpoint. The HTTP/2 `:authority` comes
+// from the origin of the client instead, see `SpiffeWorkloadApiClient::with_origin`.
+const UNIX_ORIGIN: &str = "http://localhost";
+
+#[derive(Clone, Debug)]
+struct UnixConnector {
+    path: Arc<Path>,
+}
+
+impl UnixConnector {
+    fn new(path: &Path) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Service<Uri> for UnixConnector {
+    type Response = TokioIo<UnixStream>;
+    type Error = std::io::Error;
+    type Future = Pin<Box<dyn Future<Output = IoResult<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Uri) -> Self::Future {
+        let path = self.path.clone();
+
+        Box::pin(async move { UnixStream::connect(path).await.map(TokioIo::new) })
+    }
+}
+
+impl WorkloadEndpoint {
+    /// Connects to the endpoint and returns a ready-to-use [`Channel`].
+    pub async fn connect(&self) -> Result<Channel, Error> {
+        match self {
+            Self::Unix(path) => {
+                Endpoint::from_static(UNIX_ORIGIN)
+                    .connect_with_connector(UnixConnector::new(path))
+                    .await
+            }
+            Self::Tcp(addr) => tcp_endpoint(addr)?.connect().await,
+        }
+    }
+
+    /// Returns a [`Channel`] connecting to the endpoint on first use, e.g. to create a client
+    /// before the agent is up.
+    pub fn connect_lazy(&self) -> Result<Channel, Error> {
+        match self {
+            Self::Unix(path) => Ok(Endpoint::from_static(UNIX_ORIGIN)
+                .connect_with_connector_lazy(UnixConnector::new(path))),
+            Self::Tcp(addr) => Ok(tcp_endpoint(addr)?.connect_lazy()),
+        }
+    }
+}
+
+fn tcp_endpoint(addr: &SocketAddr) -> Result<Endpoint, Error> {
+    Endpoint::from_shared(format!("http://{addr}"))
+}
@@ -1,10 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, hash_map};
 
 use prost::bytes::Bytes;
 use rustls_pki_types::CertificateRevocationListDer;
-use spiffe_id::TrustDomain;
+use spiffe_id::{SpiffeId, TrustDomain};
 
-use crate::{JwtSvid, SpiffeError, WitSvid, X509Bundle, X509Svid};
+use crate::{CrlSet, InvalidDerError, JwtSvid, SpiffeError, WitSvid, X509Bundle, X509Svid};
 
 fn paese_x509_crls(crls: Vec<Bytes>) -> Vec<CertificateRevocationListDer<'static>> {
     crls.into_iter()
@@ -27,7 +27,7 @@ fn parse_x509_bundles(
         .collect()
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct X509SvidContext {
     pub svids: Vec<X509Svid>,
     pub crl: Vec<CertificateRevocationListDer<'static>>,
@@ -59,12 +59,141 @@ impl TryFrom<spiffe_proto::X509SvidResponse> for X509SvidContext {
     }
 }
 
-#[derive(Clone, Debug)]
+impl X509SvidContext {
+    /// Parses the CRLs of the context.
+    pub fn crl_set(&self) -> Result<CrlSet, InvalidDerError> {
+        CrlSet::from_der(&self.crl)
+    }
+
+    /// Returns the default SVID, the first one sent by the Workload API.
+    #[inline]
+    pub fn default_svid(&self) -> Option<&X509Svid> {
+        self.svids.first()
+    }
+
+    pub fn svid_by_id(&self, spiffe_id: &SpiffeId) -> Option<&X509Svid> {
+        self.svids.iter().find(|x| x.spiffe_id() == spiffe_id)
+    }
+
+    pub fn svid_by_hint(&self, hint: &str) -> Option<&X509Svid> {
+        self.svids.iter().find(|x| x.hint() == Some(hint))
+    }
+
+    /// Returns the SVID whose leaf certificate expires last, the first one on ties.
+    ///
+    /// SVIDs with an unparsable expiry are only picked if no other is left.
+    pub fn latest_expiring_svid(&self) -> Option<&X509Svid> {
+        self.svids.iter().rev().max_by_key(|x| x.expiry())
+    }
+
+    /// Returns the bundles needed to authenticate peers as `svid`: the federated bundles
+    /// together with the bundle of the SVID's own trust domain.
+    ///
+    /// The SVID's bundle takes precedence over a federated bundle of the same trust domain.
+    pub fn bundle_set(&self, svid: &X509Svid) -> BundleSet {
+        BundleSet {
+            bundles: self.merged_bundles([svid]),
+        }
+    }
+
+    /// Returns the federated bundles together with the bundles of the trust domains of `svids`,
+    /// which take precedence.
+    pub(crate) fn merged_bundles<'a>(
+        &self,
+        svids: impl IntoIterator<Item = &'a X509Svid>,
+    ) -> HashMap<TrustDomain<'static>, X509Bundle> {
+        let mut bundles = self.federated_bundles.clone();
+        for svid in svids {
+            bundles.insert(
+                svid.spiffe_id().trust_domain().into_owned(),
+                svid.bundle().clone(),
+            );
+        }
+
+        bundles
+    }
+}
+
+/// X.509 bundles keyed by trust domain.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BundleSet {
+    bundles: HashMap<TrustDomain<'static>, X509Bundle>,
+}
+
+impl BundleSet {
+    #[inline]
+    pub fn get(&self, trust_domain: &TrustDomain<'_>) -> Option<&X509Bundle> {
+        // the map is keyed by owned trust domains, a borrowed key would tie the result to it
+        self.bundles.get(&trust_domain.clone().into_owned())
+    }
+
+    /// Returns the bundle of the trust domain of `spiffe_id`.
+    #[inline]
+    pub fn get_for_id(&self, spiffe_id: &SpiffeId) -> Option<&X509Bundle> {
+        self.get(&spiffe_id.trust_domain())
+    }
+
+    #[inline]
+    pub fn iter(&self) -> hash_map::Iter<'_, TrustDomain<'static>, X509Bundle> {
+        self.bundles.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> HashMap<TrustDomain<'static>, X509Bundle> {
+        self.bundles
+    }
+}
+
+impl From<HashMap<TrustDomain<'static>, X509Bundle>> for BundleSet {
+    #[inline]
+    fn from(bundles: HashMap<TrustDomain<'static>, X509Bundle>) -> Self {
+        Self { bundles }
+    }
+}
+
+impl IntoIterator for BundleSet {
+    type Item = (TrustDomain<'static>, X509Bundle);
+    type IntoIter = hash_map::IntoIter<TrustDomain<'static>, X509Bundle>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.bundles.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a BundleSet {
+    type Item = (&'a TrustDomain<'static>, &'a X509Bundle);
+    type IntoIter = hash_map::Iter<'a, TrustDomain<'static>, X509Bundle>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.bundles.iter()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct X509BundlesContext {
     pub crl: Vec<CertificateRevocationListDer<'static>>,
     pub bundles: HashMap<TrustDomain<'static>, X509Bundle>,
 }
 
+impl X509BundlesContext {
+    /// Parses the CRLs of the context.
+    pub fn crl_set(&self) -> Result<CrlSet, InvalidDerError> {
+        CrlSet::from_der(&self.crl)
+    }
+}
+
 impl TryFrom<spiffe_proto::X509BundlesResponse> for X509BundlesContext {
     type Error = SpiffeError;
 
@@ -177,3 +306,86 @@ impl From<WitBundlesContext> for HashMap<TrustDomain<'static>, String> {
         context.bundles
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use rustls_pki_types::CertificateDer;
+    use spiffe_testkit::{IssueOptions, TestCa};
+
+    use super::*;
+
+    fn svid(ca: &TestCa, spiffe_id: &str, lifetime: u64, hint: &str) -> spiffe_proto::X509Svid {
+        let now = SystemTime::now();
+        let options = IssueOptions::new().validity(now, now + Duration::from_secs(lifetime));
+        let (chain, _) = ca.issue_x509_chain(&SpiffeId::new(spiffe_id).unwrap(), &options);
+
+        spiffe_proto::X509Svid {
+            spiffe_id: spiffe_id.into(),
+            x509_svid: chain[0].to_vec().into(),
+            x509_svid_key: Bytes::from_static(b"key"),
+            bundle: chain[0].to_vec().into(),
+            hint: hint.into(),
+        }
+    }
+
+    #[test]
+    fn test_svid_selection() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let context: X509SvidContext = spiffe_proto::X509SvidResponse {
+            svids: vec![
+                svid(&ca, "spiffe://example.org/early", 3600, "internal"),
+                svid(&ca, "spiffe://example.org/late", 7200, "external"),
+            ],
+            crl: vec![],
+            federated_bundles: HashMap::new(),
+        }
+        .try_into()
+        .unwrap();
+        let early = SpiffeId::new("spiffe://example.org/early").unwrap();
+        let late = SpiffeId::new("spiffe://example.org/late").unwrap();
+
+        assert_eq!(context.default_svid().unwrap().spiffe_id(), &early);
+        assert_eq!(context.svid_by_id(&late).unwrap().hint(), Some("external"));
+        assert_eq!(
+            context.svid_by_hint("internal").unwrap().spiffe_id(),
+            &early
+        );
+        assert!(context.svid_by_hint("other").is_none());
+        assert_eq!(context.latest_expiring_svid().unwrap().spiffe_id(), &late);
+    }
+
+    #[test]
+    fn test_bundle_set() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let own = svid(&ca, "spiffe://example.org/workload", 3600, "");
+        let federated = svid(&ca, "spiffe://example.org/other", 3600, "").bundle;
+        let context: X509SvidContext = spiffe_proto::X509SvidResponse {
+            svids: vec![own.clone()],
+            crl: vec![],
+            federated_bundles: HashMap::from([
+                ("spiffe://example.org".into(), federated.clone()),
+                ("spiffe://example.com".into(), federated.clone()),
+            ]),
+        }
+        .try_into()
+        .unwrap();
+        let svid = context.default_svid().unwrap();
+        let bundles = context.bundle_set(svid);
+
+        assert_eq!(bundles.len(), 2);
+        // the SVID's own bundle wins over the federated one
+        assert_eq!(
+            bundles.get_for_id(svid.spiffe_id()).unwrap().bundle(),
+            [CertificateDer::from(own.bundle.to_vec())]
+        );
+        assert_eq!(
+            bundles
+                .get(&TrustDomain::new("example.com").unwrap())
+                .unwrap()
+                .bundle(),
+            [CertificateDer::from(federated.to_vec())]
+        );
+    }
+}
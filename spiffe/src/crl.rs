@@ -0,0 +1,351 @@
+//! Offline revocation checking with the CRLs delivered by the Workload API.
+//!
+//! CRL signatures are not verified, the lists are trusted as delivered over the Workload API.
+//! Issuer names alone are not unique, so a CRL only applies to the CA whose subject key
+//! identifier matches its authority key identifier, and CRLs without one are ignored.
+
+use std::time::SystemTime;
+
+use rustls_pki_types::{CertificateDer, CertificateRevocationListDer};
+
+use crate::{
+    InvalidDerError, X509Bundle,
+    der::{find_extension, parse_time, read_der_tlv, tbs_fields},
+};
+
+const SUBJECT_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x0e];
+const AUTHORITY_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x23];
+
+/// Parsed X.509 certificate revocation list.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Crl {
+    // DER-encoded `Name`
+    issuer: Box<[u8]>,
+    authority_key_id: Option<Box<[u8]>>,
+    this_update: SystemTime,
+    next_update: Option<SystemTime>,
+    // contents of the `serialNumber` INTEGERs
+    revoked: Box<[Box<[u8]>]>,
+}
+
+impl Crl {
+    pub fn from_der(der: &[u8]) -> Result<Self, InvalidDerError> {
+        parse_crl(der).ok_or(InvalidDerError)
+    }
+
+    /// Returns the DER-encoded `Name` of the issuer.
+    #[inline]
+    pub fn issuer(&self) -> &[u8] {
+        &self.issuer
+    }
+
+    /// Returns the `keyIdentifier` of the authority key identifier extension.
+    #[inline]
+    pub fn authority_key_id(&self) -> Option<&[u8]> {
+        self.authority_key_id.as_deref()
+    }
+
+    #[inline]
+    pub fn this_update(&self) -> SystemTime {
+        self.this_update
+    }
+
+    #[inline]
+    pub fn next_update(&self) -> Option<SystemTime> {
+        self.next_update
+    }
+
+    /// Returns the big-endian serial numbers of the revoked certificates.
+    pub fn revoked_serials(&self) -> impl Iterator<Item = &[u8]> {
+        self.revoked.iter().map(AsRef::as_ref)
+    }
+
+    /// Returns whether `nextUpdate` has passed at `now`, a CRL without one never gets stale.
+    pub fn is_stale_at(&self, now: SystemTime) -> bool {
+        self.next_update.is_some_and(|x| x < now)
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.is_stale_at(SystemTime::now())
+    }
+
+    /// Returns whether `ca` is the issuer of the CRL: its subject must be the issuer and its
+    /// subject key identifier the authority key identifier of the CRL.
+    pub fn is_issued_by(&self, ca: &CertificateDer<'_>) -> bool {
+        let Some(authority_key_id) = self.authority_key_id() else {
+            return false;
+        };
+
+        tbs_fields(ca).is_some_and(|x| {
+            x.subject == &*self.issuer && subject_key_id(x.extensions) == Some(authority_key_id)
+        })
+    }
+
+    /// Returns whether `cert` was issued by the issuer of the CRL and is listed as revoked.
+    ///
+    /// The authority key identifier of `cert`, if any, must match the one of the CRL.
+    pub fn is_revoked(&self, cert: &CertificateDer<'_>) -> Result<bool, InvalidDerError> {
+        let fields = tbs_fields(cert).ok_or(InvalidDerError)?;
+        let (_, (_, serial)) = read_der_tlv(fields.serial_number).ok_or(InvalidDerError)?;
+        let same_key =
+            authority_key_id(fields.extensions).is_none_or(|x| Some(x) == self.authority_key_id());
+
+        Ok(fields.issuer == &*self.issuer
+            && same_key
+            && self.revoked_serials().any(|x| x == serial))
+    }
+}
+
+impl TryFrom<&CertificateRevocationListDer<'_>> for Crl {
+    type Error = InvalidDerError;
+
+    fn try_from(der: &CertificateRevocationListDer<'_>) -> Result<Self, Self::Error> {
+        Self::from_der(der)
+    }
+}
+
+/// CRLs of a Workload API response.
+///
+/// Issuer names are not unique across trust domains, so every check is scoped to the bundle of
+/// the trust domain the certificate chain belongs to.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct CrlSet {
+    crls: Vec<Crl>,
+}
+
+impl CrlSet {
+    pub fn new(crls: Vec<Crl>) -> Self {
+        Self { crls }
+    }
+
+    pub fn from_der<'a>(
+        crls: impl IntoIterator<Item = &'a CertificateRevocationListDer<'a>>,
+    ) -> Result<Self, InvalidDerError> {
+        crls.into_iter()
+            .map(Crl::try_from)
+            .collect::<Result<_, _>>()
+            .map(Self::new)
+    }
+
+    #[inline]
+    pub fn crls(&self) -> &[Crl] {
+        &self.crls
+    }
+
+    /// Returns the CRLs issued by a CA of `bundle`.
+    pub fn for_bundle<'a>(&'a self, bundle: &'a X509Bundle) -> impl Iterator<Item = &'a Crl> {
+        self.crls
+            .iter()
+            .filter(|crl| bundle.bundle().iter().any(|ca| crl.is_issued_by(ca)))
+    }
+
+    /// Returns whether any certificate of `chain`, leaf first, is revoked.
+    ///
+    /// Only CRLs issued by a CA of `bundle`, the bundle of the chain's trust domain, or by an
+    /// intermediate of `chain` are considered. Unparsable certificates count as revoked.
+    pub fn is_revoked(&self, chain: &[CertificateDer<'_>], bundle: &X509Bundle) -> bool {
+        let intermediates = chain.get(1..).unwrap_or_default();
+        let applicable = self.crls.iter().filter(|crl| {
+            bundle
+                .bundle()
+                .iter()
+                .chain(intermediates)
+                .any(|ca| crl.is_issued_by(ca))
+        });
+
+        applicable
+            .flat_map(|crl| chain.iter().map(|cert| crl.is_revoked(cert)))
+            .any(|x| x.unwrap_or(true))
+    }
+
+    /// Returns the CRLs whose `nextUpdate` has passed at `now`.
+    pub fn stale_at(&self, now: SystemTime) -> impl Iterator<Item = &Crl> {
+        self.crls.iter().filter(move |x| x.is_stale_at(now))
+    }
+}
+
+fn parse_crl(der: &[u8]) -> Option<Crl> {
+    let ([], (0x30, crl)) = read_der_tlv(der)? else {
+        return None;
+    };
+    let (_, (0x30, tbs_cert_list)) = read_der_tlv(crl)? else {
+        return None;
+    };
+
+    // skip the optional `version`
+    let mut rem = match read_der_tlv(tbs_cert_list)? {
+        (r, (0x02, _)) => r,
+        _ => tbs_cert_list,
+    };
+
+    // skip `signature`
+    (rem, _) = read_der_tlv(rem)?;
+
+    let (r, (0x30, _)) = read_der_tlv(rem)? else {
+        return None;
+    };
+    let issuer = rem.get(..rem.len() - r.len())?.into();
+
+    let (mut rem, this_update) = read_der_tlv(r)?;
+    let this_update = parse_time(this_update)?;
+
+    let next_update = match read_der_tlv(rem) {
+        Some((r, time @ (0x17 | 0x18, _))) => {
+            rem = r;
+            Some(parse_time(time)?)
+        }
+        _ => None,
+    };
+
+    let mut revoked = Vec::new();
+    if let Some((r, (0x30, mut entries))) = read_der_tlv(rem) {
+        rem = r;
+        while !entries.is_empty() {
+            let (r, (0x30, entry)) = read_der_tlv(entries)? else {
+                return None;
+            };
+            let (_, (0x02, serial)) = read_der_tlv(entry)? else {
+                return None;
+            };
+
+            revoked.push(serial.into());
+            entries = r;
+        }
+    }
+
+    let authority_key_id = match read_der_tlv(rem) {
+        Some((_, (0xa0, extensions))) => {
+            let ([], (0x30, extensions)) = read_der_tlv(extensions)? else {
+                return None;
+            };
+            authority_key_id(extensions).map(Into::into)
+        }
+        _ => None,
+    };
+
+    Some(Crl {
+        issuer,
+        authority_key_id,
+        this_update,
+        next_update,
+        revoked: revoked.into(),
+    })
+}
+
+/// Returns the `keyIdentifier` of the subject key identifier extension.
+fn subject_key_id(extensions: &[u8]) -> Option<&[u8]> {
+    let ([], (0x04, key_id)) = read_der_tlv(find_extension(extensions, SUBJECT_KEY_IDENTIFIER)?)?
+    else {
+        return None;
+    };
+
+    Some(key_id)
+}
+
+/// Returns the `keyIdentifier` of the authority key identifier extension.
+fn authority_key_id(extensions: &[u8]) -> Option<&[u8]> {
+    let ([], (0x30, aki)) = read_der_tlv(find_extension(extensions, AUTHORITY_KEY_IDENTIFIER)?)?
+    else {
+        return None;
+    };
+    let (_, (0x80, key_id)) = read_der_tlv(aki)? else {
+        return None;
+    };
+
+    Some(key_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{slice, time::Duration};
+    use std::time::UNIX_EPOCH;
+
+    use spiffe_id::{SpiffeId, TrustDomain};
+    use spiffe_testkit::{IssueOptions, TestCa};
+
+    use super::*;
+
+    fn issue(ca: &TestCa) -> CertificateDer<'static> {
+        let spiffe_id = SpiffeId::new("spiffe://example.org/workload").unwrap();
+        let (mut chain, _) = ca.issue_x509_chain(&spiffe_id, &IssueOptions::new());
+        chain.remove(0)
+    }
+
+    fn serial<'a>(cert: &'a CertificateDer<'_>) -> &'a [u8] {
+        tbs_fields(cert).unwrap().serial_number
+    }
+
+    #[test]
+    fn test_parse_crl() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let (good, revoked) = (issue(&ca), issue(&ca));
+        // 2026-10-19T00:09:34Z and 2126-09-25T00:09:34Z
+        let this_update = UNIX_EPOCH + Duration::from_secs(1_792_368_574);
+        let next_update = UNIX_EPOCH + Duration::from_secs(4_945_968_574);
+        let der = ca.crl_at(&[&revoked], this_update, next_update);
+        let crl = Crl::try_from(&der).unwrap();
+
+        assert_eq!(crl.this_update(), this_update);
+        assert_eq!(crl.next_update(), Some(next_update));
+        assert_eq!(crl.revoked_serials().count(), 1);
+        assert!(crl.authority_key_id().is_some());
+
+        assert!(!crl.is_stale_at(next_update));
+        assert!(crl.is_stale_at(next_update + Duration::from_secs(1)));
+
+        assert!(crl.is_issued_by(ca.certificate()));
+        assert!(!crl.is_issued_by(&good));
+        assert!(!crl.is_revoked(&good).unwrap());
+        assert!(crl.is_revoked(&revoked).unwrap());
+
+        // a CA with the same name and serials, but another key
+        let other = TestCa::new(ca.trust_domain().clone());
+        let (_, other_revoked) = (issue(&other), issue(&other));
+        assert_eq!(serial(&other_revoked), serial(&revoked));
+        assert!(!crl.is_issued_by(other.certificate()));
+        assert!(!crl.is_revoked(&other_revoked).unwrap());
+
+        assert!(Crl::from_der(&der[1..]).is_err());
+    }
+
+    #[test]
+    fn test_crl_set_scoped_to_bundle() {
+        let ca = TestCa::new(TrustDomain::new("example.org").unwrap());
+        let (good, revoked) = (issue(&ca), issue(&ca));
+        let crls = CrlSet::from_der([&ca.crl(&[&revoked])]).unwrap();
+        let bundle = X509Bundle::from_der(ca.certificate()).unwrap();
+        // the bundle of another trust domain, with the same CA name
+        let other =
+            X509Bundle::from_der(TestCa::new(ca.trust_domain().clone()).certificate()).unwrap();
+
+        assert!(crls.is_revoked(slice::from_ref(&revoked), &bundle));
+        assert!(!crls.is_revoked(&[good], &bundle));
+        assert!(!crls.is_revoked(&[revoked], &other));
+
+        assert_eq!(crls.for_bundle(&bundle).count(), 1);
+        assert_eq!(crls.for_bundle(&other).count(), 0);
+        assert_eq!(crls.stale_at(UNIX_EPOCH).count(), 0);
+    }
+
+    #[test]
+    fn test_invalid_times() {
+        // a CRL with an empty `signature` and `issuer`, and a `thisUpdate` only
+        let crl = |time: &[u8; 15]| {
+            let mut der = vec![0x30, 0x17, 0x30, 0x15, 0x30, 0x00, 0x30, 0x00, 0x18, 0x0f];
+            der.extend_from_slice(time);
+            der
+        };
+
+        assert!(Crl::from_der(&crl(b"20261019000934Z")).is_ok());
+        for time in [
+            b"00000101000000Z",
+            b"00000301000000Z",
+            b"20261019240000Z",
+            b"20261019006000Z",
+            b"20261019000060Z",
+        ] {
+            let result = Crl::from_der(&crl(time));
+            assert!(result.is_err(), "{}", str::from_utf8(time).unwrap());
+        }
+    }
+}
@@ -1,13 +1,17 @@
 use core::str;
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rustls_pki_types::CertificateDer;
 use spiffe_id::SpiffeId;
 
 use super::{InvalidDerError, SpiffeError};
 
-type Tlv<'a> = (u8, &'a [u8]);
+pub(crate) type Tlv<'a> = (u8, &'a [u8]);
 
-const fn read_der_tlv(der: &[u8]) -> Option<(&[u8], Tlv<'_>)> {
+pub(crate) const fn read_der_tlv(der: &[u8]) -> Option<(&[u8], Tlv<'_>)> {
     let [tag, first_len_byte, rem @ ..] = der else {
         return None;
     };
@@ -96,6 +100,173 @@ pub fn split_certificates(der: &[u8]) -> CertificateIter<'_> {
     CertificateIter { der }
 }
 
+/// Fields of a `TBSCertificate`, each one a complete TLV.
+#[cfg(feature = "std")]
+pub(crate) struct TbsFields<'a> {
+    pub(crate) serial_number: &'a [u8],
+    pub(crate) issuer: &'a [u8],
+    pub(crate) validity: &'a [u8],
+    pub(crate) subject: &'a [u8],
+    #[cfg_attr(not(any(feature = "pem", feature = "bundle")), expect(dead_code))]
+    pub(crate) spki: &'a [u8],
+    /// Contents of `Extensions`, empty if absent.
+    pub(crate) extensions: &'a [u8],
+}
+
+/// Splits the leading fields of the `TBSCertificate` of a certificate.
+#[cfg(feature = "std")]
+pub(crate) fn tbs_fields(cert: &[u8]) -> Option<TbsFields<'_>> {
+    let ([], (0x30, cert)) = read_der_tlv(cert)? else {
+        return None;
+    };
+    let (_, (0x30, tbs_certificate)) = read_der_tlv(cert)? else {
+        return None;
+    };
+
+    // skip the optional `version`
+    let mut rem = match read_der_tlv(tbs_certificate)? {
+        (r, (0xa0, _)) => r,
+        _ => tbs_certificate,
+    };
+
+    let mut fields = [[].as_slice(); 6];
+    for field in &mut fields {
+        let r;
+        (r, _) = read_der_tlv(rem)?;
+        *field = rem.get(..rem.len() - r.len())?;
+        rem = r;
+    }
+
+    // `signature` is not needed
+    let [serial_number, _, issuer, validity, subject, spki] = fields;
+    let tags = [serial_number, issuer, validity, subject, spki].map(|x| x[0]);
+    if tags != [0x02, 0x30, 0x30, 0x30, 0x30] {
+        return None;
+    }
+
+    // skip the optional unique identifiers
+    let mut extensions = [].as_slice();
+    while let Some((r, (tag, value))) = read_der_tlv(rem) {
+        if tag == 0xa3 {
+            let ([], (0x30, value)) = read_der_tlv(value)? else {
+                return None;
+            };
+            extensions = value;
+        }
+        rem = r;
+    }
+
+    Some(TbsFields {
+        serial_number,
+        issuer,
+        validity,
+        subject,
+        spki,
+        extensions,
+    })
+}
+
+/// Returns the contents of the `extnValue` of the extension `oid`, given the contents of
+/// `Extensions`.
+#[cfg(feature = "std")]
+pub(crate) fn find_extension<'a>(mut extensions: &'a [u8], oid: &[u8]) -> Option<&'a [u8]> {
+    while !extensions.is_empty() {
+        let (r, (0x30, extension)) = read_der_tlv(extensions)? else {
+            return None;
+        };
+        let (rem, (0x06, extn_id)) = read_der_tlv(extension)? else {
+            return None;
+        };
+
+        if extn_id == oid {
+            // skip the optional `critical`
+            let rem = match read_der_tlv(rem)? {
+                (r, (0x01, _)) => r,
+                _ => rem,
+            };
+            let ([], (0x04, value)) = read_der_tlv(rem)? else {
+                return None;
+            };
+            return Some(value);
+        }
+        extensions = r;
+    }
+
+    None
+}
+
+/// Returns the DER-encoded `SubjectPublicKeyInfo` of a certificate.
+#[cfg(any(feature = "pem", feature = "bundle"))]
+pub(crate) fn spki_from_certificate(cert: &[u8]) -> Option<&[u8]> {
+    tbs_fields(cert).map(|x| x.spki)
+}
+
+/// Returns the `notAfter` time of a certificate.
+#[cfg(feature = "std")]
+pub(crate) fn not_after_from_certificate(cert: &[u8]) -> Option<SystemTime> {
+    let (_, (_, validity)) = read_der_tlv(tbs_fields(cert)?.validity)?;
+
+    // skip `notBefore`
+    let (rem, _) = read_der_tlv(validity)?;
+    let (_, time) = read_der_tlv(rem)?;
+
+    parse_time(time)
+}
+
+/// Parses an X.509 `Time`, either `UTCTime` or `GeneralizedTime`.
+#[cfg(feature = "std")]
+pub(crate) fn parse_time((tag, time): Tlv<'_>) -> Option<SystemTime> {
+    // RFC5280 4.1.2.5: both forms are in UTC and include seconds
+    let (year, time) = match (tag, time) {
+        (0x17, [y @ .., b'Z']) if y.len() == 12 => {
+            let (year, time) = y.split_at(2);
+            let year = read_digits(year)?;
+            (if year < 50 { 2000 + year } else { 1900 + year }, time)
+        }
+        (0x18, [y @ .., b'Z']) if y.len() == 14 => {
+            let (year, time) = y.split_at(4);
+            (read_digits(year)?, time)
+        }
+        _ => return None,
+    };
+
+    let [month, day, hour, minute, second] = [0, 2, 4, 6, 8].map(|i| read_digits(&time[i..i + 2]));
+    let (month, day, hour, minute, second) = (month?, day?, hour?, minute?, second?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // days from civil, http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    let seconds = days
+        .checked_mul(86_400)?
+        .checked_add(hour * 3_600 + minute * 60 + second)?;
+
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+#[cfg(feature = "std")]
+fn read_digits(digits: &[u8]) -> Option<u64> {
+    digits.iter().try_fold(0, |acc, x| {
+        x.is_ascii_digit().then(|| acc * 10 + u64::from(x - b'0'))
+    })
+}
+
 const SAN_OID_ASN1_BYTES: [u8; 5] = [0x06, 0x03, 0x55, 0x1D, 0x11];
 
 /// Extracts SPIFFE ID from a trusted X.509 SVID
@@ -243,4 +414,15 @@ SQ==
             SpiffeId::new("spiffe://example.org/zkonge").unwrap()
         );
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_not_after_from_certificate() {
+        // 2024-11-06T10:40:26Z
+        assert_eq!(
+            not_after_from_certificate(CERT),
+            Some(UNIX_EPOCH + Duration::from_secs(1_730_889_626))
+        );
+        assert_eq!(not_after_from_certificate(&CERT[1..]), None);
+    }
 }
@@ -1,5 +1,7 @@
 use core::fmt::{Display, Formatter, Result as FmtResult};
 
+#[cfg(any(feature = "client", feature = "_jose"))]
+use spiffe_id::TrustDomain;
 use spiffe_id::{SpiffeIdError, TrustDomainError};
 use thiserror::Error;
 
@@ -17,8 +19,15 @@ pub enum SpiffeError {
     #[error("JWT bundle is invalid")]
     InvalidJwtBundle,
 
+    #[error("JWT audience must be a non-empty list of non-empty strings")]
+    InvalidAudience,
+
     #[error("invalid DER data")]
     InvalidDer(#[from] InvalidDerError),
+
+    #[cfg(any(feature = "json", all(feature = "client", feature = "jwt")))]
+    #[error("invalid JSON value: {0}")]
+    Json(#[from] JsonError),
 }
 
 #[derive(Error, Debug)]
@@ -29,3 +38,193 @@ impl Display for InvalidDerError {
         f.write_str("invalid DER data")
     }
 }
+
+#[cfg(any(feature = "json", all(feature = "client", feature = "jwt")))]
+#[derive(Error, Debug)]
+pub enum JsonError {
+    #[error("number is NaN or infinite")]
+    NonFiniteNumber,
+
+    #[error("integer can not be represented exactly as a double")]
+    IntegerOutOfRange,
+}
+
+#[cfg(feature = "_jose")]
+#[derive(Error, Debug)]
+pub enum TokenError {
+    #[error("token is not a well-formed compact JWS")]
+    Malformed,
+
+    #[error("unsupported signature algorithm or key type")]
+    UnsupportedAlgorithm,
+
+    #[error("invalid JSON Web Key")]
+    InvalidKey,
+
+    #[error("token signature is invalid")]
+    InvalidSignature,
+
+    #[error("no key in the bundle matches the token")]
+    UnknownKey,
+
+    #[error("no bundle for trust domain `{0}`")]
+    UnknownTrustDomain(TrustDomain<'static>),
+
+    #[error("unexpected token type")]
+    Type,
+
+    #[error("missing claim `{0}`")]
+    MissingClaim(&'static str),
+
+    #[error("invalid claim `{0}`")]
+    InvalidClaim(&'static str),
+
+    #[error("token is expired")]
+    Expired,
+
+    #[error("token is not yet valid")]
+    NotYetValid,
+
+    #[error("token audience does not match")]
+    Audience,
+
+    #[error("invalid SPIFFE ID: {0}")]
+    SpiffeId(#[from] SpiffeIdError),
+}
+
+#[cfg(feature = "bundle")]
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error("bundle is not a well-formed JWKS document")]
+    Malformed,
+
+    #[error("invalid member `{0}`")]
+    InvalidMember(&'static str),
+
+    #[error("`x509-svid` key must carry exactly one certificate")]
+    CertificateCount,
+
+    #[error("certificate does not match the key")]
+    KeyMismatch,
+
+    #[error("`jwt-svid` key has no key ID")]
+    MissingKeyId,
+
+    #[error("duplicate key ID `{0}`")]
+    DuplicateKeyId(String),
+
+    #[error("invalid certificate: {0}")]
+    Certificate(#[from] InvalidDerError),
+
+    #[error("invalid key: {0}")]
+    Key(#[from] TokenError),
+}
+
+#[cfg(feature = "pem")]
+#[derive(Error, Debug)]
+pub enum PemError {
+    #[error("failed to read PEM file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid PEM: {0}")]
+    Pem(#[from] rustls_pki_types::pem::Error),
+
+    #[error("no certificate found in PEM")]
+    NoCertificate,
+
+    #[error("no PKCS#8 private key found in PEM")]
+    NoPrivateKey,
+
+    #[error("unsupported private key type")]
+    UnsupportedKey,
+
+    #[error("private key does not match the leaf certificate")]
+    KeyMismatch,
+
+    #[error("invalid X.509-SVID: {0}")]
+    Svid(#[from] SpiffeError),
+}
+
+#[cfg(feature = "client")]
+#[derive(Error, Debug)]
+pub enum SourceError {
+    #[error("no X.509 bundle for trust domain `{0}`")]
+    NoX509Bundle(TrustDomain<'static>),
+
+    #[error("no JWT bundle for trust domain `{0}`")]
+    NoJwtBundle(TrustDomain<'static>),
+
+    #[error("no suitable SVID")]
+    NoSvid,
+
+    #[error("invalid audience")]
+    InvalidAudience,
+
+    #[error("source is closed")]
+    Closed,
+
+    #[error("workload API call failed: {0}")]
+    Status(#[from] tonic::Status),
+}
+
+#[derive(Error, Debug)]
+pub enum EndpointError {
+    #[error("environment variable `SPIFFE_ENDPOINT_SOCKET` is not set")]
+    NotConfigured,
+
+    #[error("endpoint scheme must be `unix` or `tcp`")]
+    Scheme,
+
+    #[error("unix endpoint must not include an authority")]
+    Authority,
+
+    #[error("unix endpoint path is empty")]
+    EmptyPath,
+
+    #[error("tcp endpoint must be in the form `tcp://ip:port`")]
+    Opaque,
+
+    #[error("tcp endpoint host must be an IP address with a port")]
+    Host,
+
+    #[error("tcp endpoint must not include a path")]
+    Path,
+
+    #[error("endpoint must not include user info")]
+    UserInfo,
+
+    #[error("endpoint must not include a query")]
+    Query,
+
+    #[error("endpoint must not include a fragment")]
+    Fragment,
+}
+
+#[cfg(feature = "transport")]
+#[derive(Error, Debug)]
+pub enum ConnectError {
+    #[error("invalid workload endpoint: {0}")]
+    Endpoint(#[from] EndpointError),
+
+    #[error("failed to connect to workload endpoint: {0}")]
+    Transport(#[from] tonic::transport::Error),
+}
+
+#[cfg(feature = "blocking")]
+#[derive(Error, Debug)]
+pub enum BlockingError {
+    #[error("{0}")]
+    Connect(#[from] ConnectError),
+
+    #[error("workload API call failed: {0}")]
+    Status(#[from] tonic::Status),
+
+    #[error("workload API call timed out")]
+    Timeout,
+
+    #[error("workload API stream closed without a response")]
+    Closed,
+
+    #[error("failed to create the runtime: {0}")]
+    Runtime(std::io::Error),
+}
@@ -0,0 +1,16 @@
+//! Tower middleware carrying JWT-SVIDs in the `Authorization: Bearer` header of HTTP requests.
+//!
+//! [`JwtSvidAuthLayer`] authenticates incoming requests, inserting the [`SpiffeId`] and the
+//! [`JwtSvidClaims`] of the caller into the request extensions. [`JwtSvidBearerLayer`] attaches
+//! JWT-SVIDs to outgoing requests, with an audience chosen per destination.
+//!
+//! [`SpiffeId`]: spiffe_id::SpiffeId
+//! [`JwtSvidClaims`]: crate::jwt_svid::JwtSvidClaims
+
+mod auth;
+mod bearer;
+
+pub use self::{
+    auth::{JwtSvidAuth, JwtSvidAuthLayer, ValidationMode},
+    bearer::{JwtSvidBearer, JwtSvidBearerLayer, JwtSvidCache, NoRetry, Replay, RetryUnauthorized},
+};
@@ -0,0 +1,388 @@
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_core::future::BoxFuture;
+use http::{
+    HeaderMap, HeaderValue, Request, Response, StatusCode,
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+};
+#[cfg(feature = "transport")]
+use serde_json::{Map, Value as JsonValue};
+use spiffe_id::SpiffeId;
+#[cfg(feature = "transport")]
+use tonic::{Code, transport::Channel};
+use tower_layer::Layer;
+use tower_service::Service;
+
+#[cfg(feature = "transport")]
+use crate::client::SpiffeWorkloadApiClient;
+use crate::{
+    TokenError,
+    jose::{CompactJws, JwkSet, subject},
+    jwt_svid::{JwtBundles, JwtSvidClaims},
+    source::JwtBundleSource,
+};
+
+type Authorizer = Arc<dyn Fn(&SpiffeId) -> bool + Send + Sync>;
+
+/// How [`JwtSvidAuthLayer`] validates JWT-SVIDs.
+#[derive(Clone)]
+pub enum ValidationMode {
+    /// Locally, against the JWT bundle of the token's trust domain held by a source, e.g. a
+    /// [`WorkloadJwtSource`](crate::source::WorkloadJwtSource).
+    Local(Arc<dyn JwtBundleSource + Send + Sync>),
+
+    /// With the `ValidateJWTSVID` call of the Workload API.
+    #[cfg(feature = "transport")]
+    WorkloadApi(SpiffeWorkloadApiClient<Channel>),
+}
+
+impl ValidationMode {
+    async fn validate(&self, token: &str, audience: &str) -> Result<JwtSvidClaims, Rejection> {
+        let invalid = |e: TokenError| Rejection::InvalidToken(e.to_string());
+
+        match self {
+            Self::Local(source) => validate_locally(&**source, token, audience).map_err(invalid),
+            #[cfg(feature = "transport")]
+            Self::WorkloadApi(client) => {
+                let (spiffe_id, claims) =
+                    client
+                        .validate_jwt_svid(audience, token)
+                        .await
+                        .map_err(|e| match e.code() {
+                            Code::InvalidArgument => {
+                                Rejection::InvalidToken(e.message().to_owned())
+                            }
+                            _ => Rejection::Unavailable,
+                        })?;
+                let claims = match claims {
+                    JsonValue::Object(claims) => claims,
+                    _ => Map::new(),
+                };
+
+                JwtSvidClaims::from_validated(spiffe_id, claims).map_err(invalid)
+            }
+        }
+    }
+}
+
+/// Layer authenticating requests with the JWT-SVID of their `Authorization: Bearer` header.
+///
+/// Authenticated requests carry the [`SpiffeId`] and the [`JwtSvidClaims`] of the caller in
+/// their extensions. Others are answered with `401 Unauthorized`, or `403 Forbidden` when the
+/// caller is not authorized, along with a `WWW-Authenticate` challenge.
+#[derive(Clone)]
+pub struct JwtSvidAuthLayer {
+    mode: ValidationMode,
+    audience: Arc<str>,
+    authorize: Authorizer,
+}
+
+impl JwtSvidAuthLayer {
+    /// Creates a layer accepting JWT-SVIDs presented for `audience`.
+    pub fn new(mode: ValidationMode, audience: impl Into<String>) -> Self {
+        Self {
+            mode,
+            audience: audience.into().into(),
+            authorize: Arc::new(|_| true),
+        }
+    }
+
+    /// Only accepts the callers whose SPIFFE ID `authorize` returns `true` for.
+    ///
+    /// Default: any SPIFFE ID
+    #[must_use]
+    pub fn authorize(
+        mut self,
+        authorize: impl Fn(&SpiffeId) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.authorize = Arc::new(authorize);
+        self
+    }
+
+    async fn authenticate(&self, token: Option<String>) -> Result<JwtSvidClaims, Rejection> {
+        let token = token.ok_or(Rejection::Missing)?;
+        let claims = self.mode.validate(&token, &self.audience).await?;
+
+        if (self.authorize)(claims.spiffe_id()) {
+            Ok(claims)
+        } else {
+            Err(Rejection::Forbidden)
+        }
+    }
+}
+
+impl<S> Layer<S> for JwtSvidAuthLayer {
+    type Service = JwtSvidAuth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JwtSvidAuth {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service created by [`JwtSvidAuthLayer`].
+#[derive(Clone)]
+pub struct JwtSvidAuth<S> {
+    inner: S,
+    layer: JwtSvidAuthLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for JwtSvidAuth<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // the ready service handles this request, its clone the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+        let token = bearer_token(req.headers()).map(str::to_owned);
+
+        Box::pin(async move {
+            match layer.authenticate(token).await {
+                Ok(claims) => {
+                    req.extensions_mut().insert(claims.spiffe_id().clone());
+                    req.extensions_mut().insert(claims);
+                    inner.call(req).await
+                }
+                Err(rejection) => Ok(rejection.into_response()),
+            }
+        })
+    }
+}
+
+enum Rejection {
+    Missing,
+    InvalidToken(String),
+    Forbidden,
+    #[cfg(feature = "transport")]
+    Unavailable,
+}
+
+impl Rejection {
+    fn into_response<B: Default>(self) -> Response<B> {
+        let (status, challenge) = match self {
+            Self::Missing => (StatusCode::UNAUTHORIZED, Some("Bearer".into())),
+            Self::InvalidToken(description) => {
+                // quoted-string of RFC 6750, without quotes and backslashes to escape
+                let description = description.replace(['"', '\\'], "'");
+                let challenge =
+                    format!(r#"Bearer error="invalid_token", error_description="{description}""#);
+                (StatusCode::UNAUTHORIZED, Some(challenge))
+            }
+            Self::Forbidden => (
+                StatusCode::FORBIDDEN,
+                Some(r#"Bearer error="insufficient_scope""#.into()),
+            ),
+            #[cfg(feature = "transport")]
+            Self::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, None),
+        };
+
+        let mut response = Response::new(B::default());
+        *response.status_mut() = status;
+        if let Some(challenge) = challenge {
+            let challenge = HeaderValue::try_from(challenge)
+                .unwrap_or(HeaderValue::from_static(r#"Bearer error="invalid_token""#));
+            response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+        }
+
+        response
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let (scheme, token) = headers.get(AUTHORIZATION)?.to_str().ok()?.split_once(' ')?;
+
+    Some(token.trim()).filter(|x| scheme.eq_ignore_ascii_case("bearer") && !x.is_empty())
+}
+
+fn validate_locally(
+    source: &dyn JwtBundleSource,
+    token: &str,
+    audience: &str,
+) -> Result<JwtSvidClaims, TokenError> {
+    let trust_domain = subject(&CompactJws::decode(token)?.claims)?
+        .trust_domain()
+        .into_owned();
+    let jwks = source
+        .jwt_bundle(&trust_domain)
+        .map_err(|_| TokenError::UnknownTrustDomain(trust_domain.clone()))?;
+    let bundles = JwtBundles::from([(trust_domain, JwkSet::from_json(&jwks)?)]);
+
+    JwtSvidClaims::validate(token, &bundles, audience)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        convert::Infallible,
+        future::{Ready, ready},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use serde_json::json;
+    use spiffe_id::TrustDomain;
+
+    use super::*;
+    use crate::jose::{Algorithm, SigningKey, encode_compact};
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Request<()>) -> Self::Future {
+            let id = req.extensions().get::<SpiffeId>().unwrap();
+            let claims = req.extensions().get::<JwtSvidClaims>().unwrap();
+            assert_eq!(claims.spiffe_id(), id);
+
+            ready(Ok(Response::new(id.to_string())))
+        }
+    }
+
+    async fn call(
+        service: &mut impl Service<Request<()>, Response = Response<String>, Error = Infallible>,
+        authorization: Option<&str>,
+    ) -> Response<String> {
+        let mut req = Request::new(());
+        if let Some(authorization) = authorization {
+            req.headers_mut()
+                .insert(AUTHORIZATION, authorization.parse().unwrap());
+        }
+
+        service.call(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_local_validation() {
+        let authority = SigningKey::generate(Algorithm::ES256)
+            .unwrap()
+            .with_key_id("authority");
+        let bundles = HashMap::from([(
+            TrustDomain::new("example.org").unwrap(),
+            JwkSet::new(vec![authority.public_key().clone()])
+                .to_value()
+                .to_string(),
+        )]);
+        let exp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 300;
+        let token = |sub: &str, aud: &str| {
+            let claims = json!({ "sub": sub, "aud": aud, "exp": exp });
+            format!("Bearer {}", encode_compact("JWT", &claims, &authority))
+        };
+
+        let mut service = JwtSvidAuthLayer::new(ValidationMode::Local(Arc::new(bundles)), "api")
+            .authorize(|id| id.as_str() == "spiffe://example.org/web")
+            .layer(Echo);
+
+        let response = call(
+            &mut service,
+            Some(&token("spiffe://example.org/web", "api")),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "spiffe://example.org/web");
+
+        let response = call(&mut service, None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+
+        let response = call(&mut service, Some("Basic dXNlcjpwYXNz")).await;
+        assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+
+        let response = call(&mut service, Some(&token("spiffe://example.org/web", "db"))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer error="invalid_token", error_description="token audience does not match""#
+        );
+
+        let response = call(&mut service, Some(&token("spiffe://other.org/web", "api"))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = call(&mut service, Some(&token("spiffe://example.org/db", "api"))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer error="insufficient_scope""#
+        );
+    }
+
+    #[cfg(feature = "transport")]
+    #[tokio::test]
+    async fn test_workload_api_validation() {
+        use spiffe_proto::ValidateJwtSvidResponse;
+        use spiffe_testkit::{FakeWorkloadApi, TestServer};
+        use tonic::Status;
+
+        use crate::json::struct_from_json;
+
+        let server = TestServer::unix(Arc::new(FakeWorkloadApi::new())).unwrap();
+        let client = SpiffeWorkloadApiClient::connect(&server.endpoint().unwrap())
+            .await
+            .unwrap();
+        let claims =
+            json!({ "sub": "spiffe://example.org/web", "aud": ["api"], "exp": 4102444800u64 });
+        server
+            .api()
+            .validate_jwt_svid()
+            .respond(ValidateJwtSvidResponse {
+                spiffe_id: "spiffe://example.org/web".into(),
+                claims: Some(struct_from_json(claims.as_object().unwrap().clone()).unwrap()),
+            });
+
+        let mut service = JwtSvidAuthLayer::new(ValidationMode::WorkloadApi(client), "api")
+            .authorize(|id| id.as_str() == "spiffe://example.org/web")
+            .layer(Echo);
+
+        let response = call(&mut service, Some("Bearer token")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "spiffe://example.org/web");
+
+        server
+            .api()
+            .validate_jwt_svid()
+            .fail_next(Status::invalid_argument("token has expired"));
+        let response = call(&mut service, Some("Bearer token")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer error="invalid_token", error_description="token has expired""#
+        );
+
+        server
+            .api()
+            .validate_jwt_svid()
+            .fail_next(Status::unavailable("agent is restarting"));
+        let response = call(&mut service, Some("Bearer token")).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!response.headers().contains_key(WWW_AUTHENTICATE));
+    }
+}
@@ -0,0 +1,478 @@
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::SystemTime,
+};
+
+use futures_core::future::BoxFuture;
+use futures_util::lock::Mutex as AsyncMutex;
+use http::{
+    HeaderMap, HeaderValue, Request, Response, StatusCode, Uri,
+    header::{AUTHORIZATION, HOST},
+    uri::Authority,
+};
+use spiffe_id::SpiffeId;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{JwtSvid, SourceError, StdError, client::Audiences, source::JwtSvidSource};
+
+type AudienceFn = Arc<dyn Fn(&Uri, &HeaderMap) -> Option<String> + Send + Sync>;
+
+/// Cached JWT-SVID of an audience, locked while it is being fetched.
+type Slot = Arc<AsyncMutex<Option<CachedSvid>>>;
+
+#[derive(Clone)]
+struct CachedSvid {
+    svid: JwtSvid,
+    refresh_at: SystemTime,
+}
+
+/// JWT-SVIDs per audience, fetched from a source such as
+/// [`WorkloadJwtSource`](crate::source::WorkloadJwtSource).
+///
+/// A JWT-SVID is fetched again once half of its lifetime, counted from when it was fetched, has
+/// elapsed. Concurrent requests for the same audience share a single fetch.
+#[derive(Clone)]
+pub struct JwtSvidCache {
+    source: Arc<dyn JwtSvidSource + Send + Sync>,
+    spiffe_id: Option<SpiffeId>,
+    svids: Arc<Mutex<HashMap<String, Slot>>>,
+}
+
+impl JwtSvidCache {
+    pub fn new(source: Arc<dyn JwtSvidSource + Send + Sync>) -> Self {
+        Self {
+            source,
+            spiffe_id: None,
+            svids: Arc::default(),
+        }
+    }
+
+    /// Requests the JWT-SVIDs of `spiffe_id`, for workloads holding several identities.
+    ///
+    /// Default: the first SVID of the source
+    #[must_use]
+    pub fn spiffe_id(mut self, spiffe_id: SpiffeId) -> Self {
+        self.spiffe_id = Some(spiffe_id);
+        self
+    }
+
+    /// Returns the cached JWT-SVID for `audience`, fetching one if it is missing or due for
+    /// refresh.
+    pub async fn get(&self, audience: &str) -> Result<JwtSvid, SourceError> {
+        let slot = self.slot(audience);
+        let mut cached = slot.lock().await;
+        match &*cached {
+            Some(cached) if SystemTime::now() < cached.refresh_at => Ok(cached.svid.clone()),
+            _ => self.fetch(audience, &mut cached).await,
+        }
+    }
+
+    /// Fetches a new JWT-SVID for `audience`, replacing the cached one.
+    pub async fn refresh(&self, audience: &str) -> Result<JwtSvid, SourceError> {
+        let slot = self.slot(audience);
+        let mut cached = slot.lock().await;
+        self.fetch(audience, &mut cached).await
+    }
+
+    /// Replaces `rejected`, unless a concurrent request already did.
+    async fn replace(&self, audience: &str, rejected: &JwtSvid) -> Result<JwtSvid, SourceError> {
+        let slot = self.slot(audience);
+        let mut cached = slot.lock().await;
+        match &*cached {
+            Some(cached) if cached.svid != *rejected => Ok(cached.svid.clone()),
+            _ => self.fetch(audience, &mut cached).await,
+        }
+    }
+
+    fn slot(&self, audience: &str) -> Slot {
+        let mut svids = self.svids.lock().unwrap();
+        svids.entry(audience.to_owned()).or_default().clone()
+    }
+
+    async fn fetch(
+        &self,
+        audience: &str,
+        cached: &mut Option<CachedSvid>,
+    ) -> Result<JwtSvid, SourceError> {
+        let audiences = Audiences::new(audience).map_err(|_| SourceError::InvalidAudience)?;
+        let fetched_at = SystemTime::now();
+        let svid = self
+            .source
+            .jwt_svid(&audiences, self.spiffe_id.as_ref())
+            .await?;
+
+        // tokens without a readable expiry are fetched for every request
+        let refresh_at = svid
+            .expiry()
+            .ok()
+            .and_then(|x| x.duration_since(fetched_at).ok())
+            .map_or(fetched_at, |x| fetched_at + x / 2);
+        *cached = Some(CachedSvid {
+            svid: svid.clone(),
+            refresh_at,
+        });
+
+        Ok(svid)
+    }
+}
+
+/// Retry policy sending every request once.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoRetry;
+
+/// Retry policy sending a request answered with `401 Unauthorized` once more, with a freshly
+/// fetched JWT-SVID. The request body is cloned before the first attempt.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryUnauthorized;
+
+/// Copies the requests a retry policy may send twice.
+pub trait Replay<B> {
+    /// Returns the copy of `req` to send if it is answered with `401 Unauthorized`, `None` not to
+    /// retry it.
+    fn replay(req: &Request<B>) -> Option<Request<B>>;
+}
+
+impl<B> Replay<B> for NoRetry {
+    fn replay(_: &Request<B>) -> Option<Request<B>> {
+        None
+    }
+}
+
+impl<B: Clone> Replay<B> for RetryUnauthorized {
+    fn replay(req: &Request<B>) -> Option<Request<B>> {
+        Some(clone_request(req))
+    }
+}
+
+/// Layer attaching a JWT-SVID of a [`JwtSvidCache`] to the `Authorization: Bearer` header of
+/// outgoing requests.
+///
+/// A request answered with `401 Unauthorized` is sent once more with a freshly fetched
+/// JWT-SVID, request bodies must therefore be [`Clone`]. See [`send_once`](Self::send_once) for
+/// streaming bodies.
+#[derive(Clone)]
+pub struct JwtSvidBearerLayer<R = RetryUnauthorized> {
+    cache: JwtSvidCache,
+    audience: AudienceFn,
+    retry: PhantomData<fn() -> R>,
+}
+
+impl JwtSvidBearerLayer {
+    pub fn new(cache: JwtSvidCache) -> Self {
+        Self {
+            cache,
+            audience: Arc::new(host),
+            retry: PhantomData,
+        }
+    }
+}
+
+impl<R> JwtSvidBearerLayer<R> {
+    /// Sends every request once, rejected ones are not retried. Supports request bodies that
+    /// are not [`Clone`].
+    #[must_use]
+    pub fn send_once(self) -> JwtSvidBearerLayer<NoRetry> {
+        JwtSvidBearerLayer {
+            cache: self.cache,
+            audience: self.audience,
+            retry: PhantomData,
+        }
+    }
+
+    /// Chooses the audience of each request, requests without one are sent without a token.
+    ///
+    /// Default: the host of the request URI or of its `Host` header
+    #[must_use]
+    pub fn audience(
+        mut self,
+        audience: impl Fn(&Uri, &HeaderMap) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.audience = Arc::new(audience);
+        self
+    }
+}
+
+impl<S, R: Clone> Layer<S> for JwtSvidBearerLayer<R> {
+    type Service = JwtSvidBearer<S, R>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JwtSvidBearer {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service created by [`JwtSvidBearerLayer`].
+#[derive(Clone)]
+pub struct JwtSvidBearer<S, R = RetryUnauthorized> {
+    inner: S,
+    layer: JwtSvidBearerLayer<R>,
+}
+
+impl<S, R, ReqBody, ResBody> Service<Request<ReqBody>> for JwtSvidBearer<S, R>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<StdError>,
+    R: Replay<ReqBody>,
+    ReqBody: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = StdError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // the ready service handles this request, its clone the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let cache = self.layer.cache.clone();
+        let audience = (self.layer.audience)(req.uri(), req.headers());
+
+        Box::pin(async move {
+            let Some(audience) = audience else {
+                return inner.call(req).await.map_err(Into::into);
+            };
+
+            let retry = R::replay(&req);
+            let svid = cache.get(&audience).await?;
+            set_bearer(&mut req, &svid)?;
+            // scoped, response bodies need not be `Send`
+            let mut retry = {
+                let response = inner.call(req).await.map_err(Into::into)?;
+                match retry {
+                    Some(retry) if response.status() == StatusCode::UNAUTHORIZED => retry,
+                    _ => return Ok(response),
+                }
+            };
+
+            set_bearer(&mut retry, &cache.replace(&audience, &svid).await?)?;
+            poll_fn(|cx| inner.poll_ready(cx))
+                .await
+                .map_err(Into::into)?;
+            inner.call(retry).await.map_err(Into::into)
+        })
+    }
+}
+
+fn host(uri: &Uri, headers: &HeaderMap) -> Option<String> {
+    match uri.host() {
+        Some(host) => Some(host.to_owned()),
+        None => headers
+            .get(HOST)?
+            .to_str()
+            .ok()?
+            .parse::<Authority>()
+            .ok()
+            .map(|x| x.host().to_owned()),
+    }
+}
+
+fn clone_request<B: Clone>(req: &Request<B>) -> Request<B> {
+    let mut clone = Request::new(req.body().clone());
+    *clone.method_mut() = req.method().clone();
+    *clone.uri_mut() = req.uri().clone();
+    *clone.version_mut() = req.version();
+    *clone.headers_mut() = req.headers().clone();
+    *clone.extensions_mut() = req.extensions().clone();
+    clone
+}
+
+fn set_bearer<B>(req: &mut Request<B>, svid: &JwtSvid) -> Result<(), StdError> {
+    let mut value = HeaderValue::try_from(format!("Bearer {}", svid.svid()))?;
+    value.set_sensitive(true);
+    req.headers_mut().insert(AUTHORIZATION, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        future::{Ready, ready},
+        sync::atomic::{AtomicU64, Ordering},
+        time::UNIX_EPOCH,
+    };
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::jose::{Algorithm, CompactJws, SigningKey, encode_compact};
+
+    /// Mints a JWT-SVID valid for `ttl` seconds on every call, with a counter as `jti`.
+    struct Minter {
+        key: SigningKey,
+        ttl: i64,
+        minted: AtomicU64,
+    }
+
+    impl Minter {
+        fn new(ttl: i64) -> Arc<Self> {
+            Arc::new(Self {
+                key: SigningKey::generate(Algorithm::ES256).unwrap(),
+                ttl,
+                minted: AtomicU64::new(0),
+            })
+        }
+    }
+
+    impl JwtSvidSource for Minter {
+        fn jwt_svid<'a>(
+            &'a self,
+            audiences: &'a Audiences,
+            _: Option<&'a SpiffeId>,
+        ) -> BoxFuture<'a, Result<JwtSvid, SourceError>> {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            let claims = json!({
+                "sub": "spiffe://example.org/client",
+                "aud": audiences.iter().collect::<Vec<_>>(),
+                "exp": now.as_secs() as i64 + self.ttl,
+                "jti": self.minted.fetch_add(1, Ordering::SeqCst).to_string(),
+            });
+            let svid = JwtSvid {
+                spiffe_id: SpiffeId::new("spiffe://example.org/client").unwrap(),
+                svid: encode_compact("JWT", &claims, &self.key).into(),
+                hint: None,
+            };
+
+            Box::pin(async move {
+                // lets concurrent requests run into the pending fetch
+                tokio::task::yield_now().await;
+                Ok(svid)
+            })
+        }
+    }
+
+    /// Rejects the tokens minted first, answering with the `aud` and `jti` of accepted ones.
+    #[derive(Clone)]
+    struct Server {
+        rejected: u64,
+    }
+
+    impl<B> Service<Request<B>> for Server {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Request<B>) -> Self::Future {
+            let Some(value) = req.headers().get(AUTHORIZATION) else {
+                return ready(Ok(Response::new("anonymous".into())));
+            };
+            let token = value.to_str().unwrap().strip_prefix("Bearer ").unwrap();
+            let claims = CompactJws::decode(token).unwrap().claims;
+            let jti = claims["jti"].as_str().unwrap().parse::<u64>().unwrap();
+
+            let mut response = Response::new(format!("{} {jti}", claims["aud"][0]));
+            if jti < self.rejected {
+                *response.status_mut() = StatusCode::UNAUTHORIZED;
+            }
+            ready(Ok(response))
+        }
+    }
+
+    async fn call<R: Replay<()>>(
+        service: &mut JwtSvidBearer<Server, R>,
+        uri: &str,
+    ) -> Response<String> {
+        let req = Request::get(uri).body(()).unwrap();
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        service.call(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cached_per_audience() {
+        let minter = Minter::new(300);
+        let mut service = JwtSvidBearerLayer::new(JwtSvidCache::new(minter.clone()))
+            .layer(Server { rejected: 0 });
+
+        assert_eq!(call(&mut service, "https://db/a").await.body(), r#""db" 0"#);
+        assert_eq!(call(&mut service, "https://db/b").await.body(), r#""db" 0"#);
+        assert_eq!(
+            call(&mut service, "https://cache/").await.body(),
+            r#""cache" 1"#
+        );
+        assert_eq!(minter.minted.load(Ordering::SeqCst), 2);
+
+        // concurrent misses share a fetch
+        let cache = JwtSvidCache::new(minter.clone());
+        let (a, b) = tokio::join!(cache.get("api"), cache.get("api"));
+        assert_eq!(a.unwrap(), b.unwrap());
+        assert_eq!(minter.minted.load(Ordering::SeqCst), 3);
+
+        assert!(matches!(
+            JwtSvidCache::new(minter.clone()).get("").await,
+            Err(SourceError::InvalidAudience)
+        ));
+
+        let mut service = JwtSvidBearerLayer::new(JwtSvidCache::new(minter.clone()))
+            .audience(|uri, _| (uri.path() != "/public").then(|| "api".into()))
+            .layer(Server { rejected: 0 });
+        assert_eq!(call(&mut service, "/private").await.body(), r#""api" 3"#);
+        assert_eq!(call(&mut service, "/public").await.body(), "anonymous");
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        // expired tokens are fetched again
+        let minter = Minter::new(-1);
+        let mut service = JwtSvidBearerLayer::new(JwtSvidCache::new(minter.clone()))
+            .layer(Server { rejected: 0 });
+        call(&mut service, "https://db/").await;
+        call(&mut service, "https://db/").await;
+        assert_eq!(minter.minted.load(Ordering::SeqCst), 2);
+
+        // without retries, requests with any body are sent once
+        struct Streaming;
+        let mut service = JwtSvidBearerLayer::new(JwtSvidCache::new(Minter::new(300)))
+            .send_once()
+            .layer(Server { rejected: 1 });
+        let req = Request::get("https://db/").body(Streaming).unwrap();
+        poll_fn(|cx| Service::<Request<Streaming>>::poll_ready(&mut service, cx))
+            .await
+            .unwrap();
+        let response = service.call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.body(), r#""db" 0"#);
+
+        // a rejected token is replaced and the request sent once more
+        let minter = Minter::new(300);
+        let mut service = JwtSvidBearerLayer::new(JwtSvidCache::new(minter.clone()))
+            .layer(Server { rejected: 1 });
+        let response = call(&mut service, "https://db/").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), r#""db" 1"#);
+        assert_eq!(call(&mut service, "https://db/").await.body(), r#""db" 1"#);
+
+        // concurrent requests rejected with the same token replace it once
+        let minter = Minter::new(300);
+        let service = JwtSvidBearerLayer::new(JwtSvidCache::new(minter.clone()))
+            .layer(Server { rejected: 1 });
+        let (mut a, mut b) = (service.clone(), service);
+        let (a, b) = tokio::join!(call(&mut a, "https://db/"), call(&mut b, "https://db/"));
+        assert_eq!(
+            (a.body().as_str(), b.body().as_str()),
+            (r#""db" 1"#, r#""db" 1"#)
+        );
+        assert_eq!(minter.minted.load(Ordering::SeqCst), 2);
+
+        // but only once
+        let mut service = JwtSvidBearerLayer::new(JwtSvidCache::new(Minter::new(300)))
+            .layer(Server { rejected: 5 });
+        let response = call(&mut service, "https://db/").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.body(), r#""db" 1"#);
+    }
+}
@@ -0,0 +1,943 @@
+//! Minimal JOSE support: JSON Web Keys (RFC 7517) and compact JSON Web Signatures (RFC 7515).
+//!
+//! Only the algorithms SPIFFE implementations use in practice are supported: `ES256`, `ES384`,
+//! `EdDSA` (Ed25519) for signing and verification, and `RS*`/`PS*` for verification only.
+
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+#[cfg(any(feature = "jwt-svid", feature = "wit"))]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use ed25519_dalek::Signer as _;
+use rsa::{BigUint, RsaPublicKey, traits::PublicKeyParts};
+use serde_json::{Map, Value as JsonValue, json};
+use sha2::{Sha256, Sha384, Sha512};
+#[cfg(any(feature = "jwt-svid", feature = "wit"))]
+use spiffe_id::SpiffeId;
+
+use crate::{Secret, TokenError};
+
+/// Smallest RSA modulus accepted for verification.
+const MIN_RSA_BITS: usize = 2048;
+
+/// JWS signature algorithm, as in the `alg` header parameter.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Algorithm {
+    ES256,
+    ES384,
+    EdDSA,
+    RS256,
+    RS384,
+    RS512,
+    PS256,
+    PS384,
+    PS512,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "ES256" => Self::ES256,
+            "ES384" => Self::ES384,
+            "EdDSA" => Self::EdDSA,
+            "RS256" => Self::RS256,
+            "RS384" => Self::RS384,
+            "RS512" => Self::RS512,
+            "PS256" => Self::PS256,
+            "PS384" => Self::PS384,
+            "PS512" => Self::PS512,
+            _ => return None,
+        })
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::ES256 => "ES256",
+            Self::ES384 => "ES384",
+            Self::EdDSA => "EdDSA",
+            Self::RS256 => "RS256",
+            Self::RS384 => "RS384",
+            Self::RS512 => "RS512",
+            Self::PS256 => "PS256",
+            Self::PS384 => "PS384",
+            Self::PS512 => "PS512",
+        }
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum PublicKey {
+    P256(p256::ecdsa::VerifyingKey),
+    P384(p384::ecdsa::VerifyingKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+    Rsa(RsaPublicKey),
+}
+
+/// A public JSON Web Key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Jwk {
+    key_id: Option<Box<str>>,
+    algorithm: Option<Algorithm>,
+    public_use: Option<Box<str>>,
+    key: PublicKey,
+}
+
+impl Jwk {
+    /// Parses a single public JWK from its JSON representation.
+    pub fn from_json(json: &str) -> Result<Self, TokenError> {
+        let value = serde_json::from_str(json).map_err(|_| TokenError::InvalidKey)?;
+
+        Self::from_value(&value)
+    }
+
+    /// Parses a single public JWK from an already decoded JSON value.
+    pub fn from_value(value: &JsonValue) -> Result<Self, TokenError> {
+        let JsonValue::Object(jwk) = value else {
+            return Err(TokenError::InvalidKey);
+        };
+
+        let key = match (str_member(jwk, "kty")?, str_member(jwk, "crv")?) {
+            (Some("EC"), Some("P-256")) => {
+                let point = p256::EncodedPoint::from_affine_coordinates(
+                    fixed_member::<32>(jwk, "x")?.as_ref().into(),
+                    fixed_member::<32>(jwk, "y")?.as_ref().into(),
+                    false,
+                );
+                p256::ecdsa::VerifyingKey::from_encoded_point(&point)
+                    .map(PublicKey::P256)
+                    .map_err(|_| TokenError::InvalidKey)?
+            }
+            (Some("EC"), Some("P-384")) => {
+                let point = p384::EncodedPoint::from_affine_coordinates(
+                    fixed_member::<48>(jwk, "x")?.as_ref().into(),
+                    fixed_member::<48>(jwk, "y")?.as_ref().into(),
+                    false,
+                );
+                p384::ecdsa::VerifyingKey::from_encoded_point(&point)
+                    .map(PublicKey::P384)
+                    .map_err(|_| TokenError::InvalidKey)?
+            }
+            (Some("OKP"), Some("Ed25519")) => {
+                ed25519_dalek::VerifyingKey::from_bytes(&fixed_member::<32>(jwk, "x")?)
+                    .map(PublicKey::Ed25519)
+                    .map_err(|_| TokenError::InvalidKey)?
+            }
+            (Some("RSA"), _) => {
+                let n = BigUint::from_bytes_be(&bytes_member(jwk, "n")?);
+                let e = BigUint::from_bytes_be(&bytes_member(jwk, "e")?);
+                let key = RsaPublicKey::new(n, e).map_err(|_| TokenError::InvalidKey)?;
+                if key.size() * 8 < MIN_RSA_BITS {
+                    return Err(TokenError::InvalidKey);
+                }
+                PublicKey::Rsa(key)
+            }
+            _ => return Err(TokenError::UnsupportedAlgorithm),
+        };
+
+        let algorithm = match str_member(jwk, "alg")? {
+            Some(alg) => Some(Algorithm::from_name(alg).ok_or(TokenError::UnsupportedAlgorithm)?),
+            None => None,
+        };
+
+        let jwk = Self {
+            key_id: str_member(jwk, "kid")?.map(Into::into),
+            algorithm,
+            public_use: str_member(jwk, "use")?.map(Into::into),
+            key,
+        };
+
+        if let Some(alg) = algorithm
+            && !jwk.key_supports(alg)
+        {
+            return Err(TokenError::InvalidKey);
+        }
+
+        Ok(jwk)
+    }
+
+    /// The `kid` member.
+    #[inline]
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    /// The `alg` member.
+    #[inline]
+    pub fn algorithm(&self) -> Option<Algorithm> {
+        self.algorithm
+    }
+
+    /// The `use` member.
+    #[inline]
+    pub fn public_use(&self) -> Option<&str> {
+        self.public_use.as_deref()
+    }
+
+    /// Sets the `kid` member.
+    #[must_use]
+    pub fn with_key_id(mut self, key_id: impl Into<Box<str>>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Sets the `use` member.
+    #[must_use]
+    pub fn with_public_use(mut self, public_use: impl Into<Box<str>>) -> Self {
+        self.public_use = Some(public_use.into());
+        self
+    }
+
+    /// Whether the key can verify signatures made with `alg`.
+    pub fn supports(&self, alg: Algorithm) -> bool {
+        self.algorithm.is_none_or(|x| x == alg) && self.key_supports(alg)
+    }
+
+    /// Serializes the key to its JSON representation.
+    pub fn to_value(&self) -> JsonValue {
+        let mut jwk = match &self.key {
+            PublicKey::P256(key) => {
+                let point = key.to_encoded_point(false);
+                ec_jwk(
+                    "P-256",
+                    point.x().map(AsRef::as_ref),
+                    point.y().map(AsRef::as_ref),
+                )
+            }
+            PublicKey::P384(key) => {
+                let point = key.to_encoded_point(false);
+                ec_jwk(
+                    "P-384",
+                    point.x().map(AsRef::as_ref),
+                    point.y().map(AsRef::as_ref),
+                )
+            }
+            PublicKey::Ed25519(key) => json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": Base64UrlUnpadded::encode_string(key.as_bytes()),
+            }),
+            PublicKey::Rsa(key) => json!({
+                "kty": "RSA",
+                "n": Base64UrlUnpadded::encode_string(&key.n().to_bytes_be()),
+                "e": Base64UrlUnpadded::encode_string(&key.e().to_bytes_be()),
+            }),
+        };
+
+        let JsonValue::Object(members) = &mut jwk else {
+            unreachable!("JWK is always an object")
+        };
+        if let Some(kid) = &self.key_id {
+            members.insert("kid".into(), JsonValue::String(kid.as_ref().into()));
+        }
+        if let Some(alg) = self.algorithm {
+            members.insert("alg".into(), JsonValue::String(alg.as_str().into()));
+        }
+        if let Some(public_use) = &self.public_use {
+            members.insert("use".into(), JsonValue::String(public_use.as_ref().into()));
+        }
+
+        jwk
+    }
+
+    /// Verifies `signature` over `message` made with `alg`.
+    pub fn verify(
+        &self,
+        alg: Algorithm,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError> {
+        use rsa::signature::Verifier as _;
+
+        if !self.supports(alg) {
+            return Err(TokenError::UnsupportedAlgorithm);
+        }
+
+        let valid = match (&self.key, alg) {
+            (PublicKey::P256(key), Algorithm::ES256) => {
+                p256::ecdsa::Signature::from_slice(signature)
+                    .is_ok_and(|sig| key.verify(message, &sig).is_ok())
+            }
+            (PublicKey::P384(key), Algorithm::ES384) => {
+                p384::ecdsa::Signature::from_slice(signature)
+                    .is_ok_and(|sig| key.verify(message, &sig).is_ok())
+            }
+            (PublicKey::Ed25519(key), Algorithm::EdDSA) => {
+                ed25519_dalek::Signature::from_slice(signature)
+                    .is_ok_and(|sig| key.verify_strict(message, &sig).is_ok())
+            }
+            (PublicKey::Rsa(key), Algorithm::RS256) => {
+                verify_pkcs1v15::<Sha256>(key, message, signature)
+            }
+            (PublicKey::Rsa(key), Algorithm::RS384) => {
+                verify_pkcs1v15::<Sha384>(key, message, signature)
+            }
+            (PublicKey::Rsa(key), Algorithm::RS512) => {
+                verify_pkcs1v15::<Sha512>(key, message, signature)
+            }
+            (PublicKey::Rsa(key), Algorithm::PS256) => {
+                verify_pss::<Sha256>(key, message, signature)
+            }
+            (PublicKey::Rsa(key), Algorithm::PS384) => {
+                verify_pss::<Sha384>(key, message, signature)
+            }
+            (PublicKey::Rsa(key), Algorithm::PS512) => {
+                verify_pss::<Sha512>(key, message, signature)
+            }
+            _ => false,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(TokenError::InvalidSignature)
+        }
+    }
+
+    /// Creates a JWK without metadata members from a DER-encoded `SubjectPublicKeyInfo`.
+    #[cfg(feature = "bundle")]
+    pub(crate) fn from_spki(spki: &[u8]) -> Result<Self, TokenError> {
+        use rsa::pkcs8::DecodePublicKey;
+
+        let key = if let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_der(spki) {
+            PublicKey::P256(key)
+        } else if let Ok(key) = p384::ecdsa::VerifyingKey::from_public_key_der(spki) {
+            PublicKey::P384(key)
+        } else if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_der(spki) {
+            PublicKey::Ed25519(key)
+        } else if let Ok(key) = RsaPublicKey::from_public_key_der(spki) {
+            if key.size() * 8 < MIN_RSA_BITS {
+                return Err(TokenError::InvalidKey);
+            }
+            PublicKey::Rsa(key)
+        } else {
+            return Err(TokenError::UnsupportedAlgorithm);
+        };
+
+        Ok(Self {
+            key_id: None,
+            algorithm: None,
+            public_use: None,
+            key,
+        })
+    }
+
+    /// Whether both JWKs hold the same key material, ignoring the metadata members.
+    #[cfg(any(feature = "wit", feature = "bundle"))]
+    pub(crate) fn same_key(&self, other: &Jwk) -> bool {
+        self.key == other.key
+    }
+
+    fn key_supports(&self, alg: Algorithm) -> bool {
+        use Algorithm::*;
+
+        matches!(
+            (&self.key, alg),
+            (PublicKey::P256(_), ES256)
+                | (PublicKey::P384(_), ES384)
+                | (PublicKey::Ed25519(_), EdDSA)
+                | (
+                    PublicKey::Rsa(_),
+                    RS256 | RS384 | RS512 | PS256 | PS384 | PS512
+                )
+        )
+    }
+}
+
+impl Debug for Jwk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let kty = match &self.key {
+            PublicKey::P256(_) => "EC P-256",
+            PublicKey::P384(_) => "EC P-384",
+            PublicKey::Ed25519(_) => "OKP Ed25519",
+            PublicKey::Rsa(_) => "RSA",
+        };
+
+        f.debug_struct("Jwk")
+            .field("kty", &kty)
+            .field("kid", &self.key_id)
+            .field("alg", &self.algorithm)
+            .field("use", &self.public_use)
+            .finish()
+    }
+}
+
+/// A JSON Web Key Set.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+impl JwkSet {
+    pub fn new(keys: Vec<Jwk>) -> Self {
+        Self { keys }
+    }
+
+    /// Parses a JWKS document.
+    ///
+    /// Keys of unsupported types are skipped, so that a bundle carrying e.g. a post-quantum key
+    /// remains usable for the keys this crate understands. Malformed keys are rejected.
+    pub fn from_json(json: &str) -> Result<Self, TokenError> {
+        let value = serde_json::from_str(json).map_err(|_| TokenError::InvalidKey)?;
+
+        Self::from_value(&value)
+    }
+
+    /// Parses a JWKS document from an already decoded JSON value.
+    pub fn from_value(value: &JsonValue) -> Result<Self, TokenError> {
+        let Some(JsonValue::Array(keys)) = value.get("keys") else {
+            return Err(TokenError::InvalidKey);
+        };
+
+        let mut parsed = Vec::with_capacity(keys.len());
+        for key in keys {
+            match Jwk::from_value(key) {
+                Ok(key) => parsed.push(key),
+                Err(TokenError::UnsupportedAlgorithm) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Self { keys: parsed })
+    }
+
+    #[inline]
+    pub fn keys(&self) -> &[Jwk] {
+        &self.keys
+    }
+
+    /// Finds the key with the given `kid`.
+    pub fn find(&self, key_id: &str) -> Option<&Jwk> {
+        self.keys.iter().find(|x| x.key_id() == Some(key_id))
+    }
+
+    /// Serializes the key set to its JSON representation.
+    pub fn to_value(&self) -> JsonValue {
+        json!({ "keys": self.keys.iter().map(Jwk::to_value).collect::<Vec<_>>() })
+    }
+
+    #[inline]
+    pub fn into_parts(self) -> Vec<Jwk> {
+        self.keys
+    }
+}
+
+enum PrivateKey {
+    P256(p256::ecdsa::SigningKey),
+    P384(p384::ecdsa::SigningKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+impl PrivateKey {
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            Self::P256(_) => Algorithm::ES256,
+            Self::P384(_) => Algorithm::ES384,
+            Self::Ed25519(_) => Algorithm::EdDSA,
+        }
+    }
+
+    fn public_key(&self) -> PublicKey {
+        match self {
+            Self::P256(key) => PublicKey::P256(*key.verifying_key()),
+            Self::P384(key) => PublicKey::P384(*key.verifying_key()),
+            Self::Ed25519(key) => PublicKey::Ed25519(key.verifying_key()),
+        }
+    }
+}
+
+/// A private JSON Web Key able to produce JWS signatures.
+pub struct SigningKey {
+    key: PrivateKey,
+    public: Jwk,
+}
+
+impl SigningKey {
+    /// Parses a private JWK from its JSON representation.
+    ///
+    /// The public members (`x`, `y`) must match the private member `d`.
+    pub fn from_jwk(json: &str) -> Result<Self, TokenError> {
+        let mut value: JsonValue =
+            serde_json::from_str(json).map_err(|_| TokenError::InvalidKey)?;
+        // take the private member out first, so that every copy of it is wiped on drop
+        let d = match value.as_object_mut().and_then(|x| x.remove("d")) {
+            Some(JsonValue::String(d)) => Secret::new(d),
+            _ => return Err(TokenError::InvalidKey),
+        };
+        let d = Base64UrlUnpadded::decode_vec(d.expose_secret())
+            .map(Secret::new)
+            .map_err(|_| TokenError::InvalidKey)?;
+        let d = d.expose_secret().as_slice();
+
+        let public = Jwk::from_value(&value)?;
+        let key = match (&public.key, d.len()) {
+            (PublicKey::P256(_), 32) => p256::ecdsa::SigningKey::from_slice(d)
+                .map(PrivateKey::P256)
+                .map_err(|_| TokenError::InvalidKey)?,
+            (PublicKey::P384(_), 48) => p384::ecdsa::SigningKey::from_slice(d)
+                .map(PrivateKey::P384)
+                .map_err(|_| TokenError::InvalidKey)?,
+            (PublicKey::Ed25519(_), _) => <&[u8; 32]>::try_from(d)
+                .map(|x| PrivateKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(x)))
+                .map_err(|_| TokenError::InvalidKey)?,
+            (PublicKey::Rsa(_), _) => return Err(TokenError::UnsupportedAlgorithm),
+            _ => return Err(TokenError::InvalidKey),
+        };
+
+        if key.public_key() != public.key {
+            return Err(TokenError::InvalidKey);
+        }
+
+        Ok(Self { key, public })
+    }
+
+    /// Generates a new random key for `alg`.
+    pub fn generate(alg: Algorithm) -> Result<Self, TokenError> {
+        let key = match alg {
+            Algorithm::ES256 => loop {
+                if let Ok(key) = p256::ecdsa::SigningKey::from_slice(&random_bytes::<32>()) {
+                    break PrivateKey::P256(key);
+                }
+            },
+            Algorithm::ES384 => loop {
+                if let Ok(key) = p384::ecdsa::SigningKey::from_slice(&random_bytes::<48>()) {
+                    break PrivateKey::P384(key);
+                }
+            },
+            Algorithm::EdDSA => {
+                PrivateKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(&random_bytes()))
+            }
+            _ => return Err(TokenError::UnsupportedAlgorithm),
+        };
+
+        Ok(Self::from_private_key(key))
+    }
+
+    fn from_private_key(key: PrivateKey) -> Self {
+        let public = Jwk {
+            key_id: None,
+            algorithm: Some(key.algorithm()),
+            public_use: None,
+            key: key.public_key(),
+        };
+
+        Self { key, public }
+    }
+
+    /// The algorithm signatures are made with.
+    #[inline]
+    pub fn algorithm(&self) -> Algorithm {
+        self.key.algorithm()
+    }
+
+    /// The public half of the key.
+    #[inline]
+    pub fn public_key(&self) -> &Jwk {
+        &self.public
+    }
+
+    /// Sets the `kid` of the key, it's also emitted in the header of tokens signed with it.
+    #[must_use]
+    pub fn with_key_id(mut self, key_id: impl Into<Box<str>>) -> Self {
+        self.public.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Serializes the private key to its JSON representation.
+    pub fn to_jwk(&self) -> Secret<String> {
+        let public = self.public.to_value().to_string();
+        // the `d` member takes the place of the closing brace
+        let public = public.strip_suffix('}').expect("JWK is a JSON object");
+
+        let mut buf = [0; 64];
+        let d = match &self.key {
+            PrivateKey::P256(key) => encode_scalar(&mut key.to_bytes(), &mut buf),
+            PrivateKey::P384(key) => encode_scalar(&mut key.to_bytes(), &mut buf),
+            PrivateKey::Ed25519(key) => {
+                Base64UrlUnpadded::encode(key.as_bytes(), &mut buf).expect("key fits the buffer")
+            }
+        };
+
+        // sized up front, as growing the string would leave unwiped copies of the key behind
+        let mut jwk = Secret::new(String::with_capacity(public.len() + d.len() + 8));
+        let s = jwk.expose_mut();
+        s.push_str(public);
+        s.push_str(",\"d\":\"");
+        s.push_str(d);
+        s.push_str("\"}");
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut buf);
+
+        jwk
+    }
+
+    /// Signs `message`, returning the signature in JWS encoding.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match &self.key {
+            PrivateKey::P256(key) => {
+                let sig: p256::ecdsa::Signature = key.sign(message);
+                sig.to_vec()
+            }
+            PrivateKey::P384(key) => {
+                let sig: p384::ecdsa::Signature = key.sign(message);
+                sig.to_vec()
+            }
+            PrivateKey::Ed25519(key) => key.sign(message).to_vec(),
+        }
+    }
+}
+
+impl Debug for SigningKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("SigningKey")
+            .field("public", &self.public)
+            .field("key", &"[secret elided]")
+            .finish()
+    }
+}
+
+/// Encodes the private scalar of an EC key into `buf`, wiping the scalar.
+fn encode_scalar<'a>(scalar: &mut [u8], buf: &'a mut [u8]) -> &'a str {
+    let encoded = Base64UrlUnpadded::encode(scalar, buf).expect("scalar fits the buffer");
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(scalar);
+
+    encoded
+}
+
+/// A compact JWS split into its decoded parts.
+#[cfg(any(feature = "jwt-svid", feature = "wit", test))]
+#[derive(Debug)]
+pub(crate) struct CompactJws<'a> {
+    pub(crate) header: Map<String, JsonValue>,
+    pub(crate) claims: Map<String, JsonValue>,
+    pub(crate) algorithm: Algorithm,
+    pub(crate) signing_input: &'a str,
+    pub(crate) signature: Vec<u8>,
+}
+
+#[cfg(any(feature = "jwt-svid", feature = "wit", test))]
+impl<'a> CompactJws<'a> {
+    pub(crate) fn decode(token: &'a str) -> Result<Self, TokenError> {
+        const MALFORMED: TokenError = TokenError::Malformed;
+
+        let (signing_input, signature) = token.rsplit_once('.').ok_or(MALFORMED)?;
+        let (header, claims) = signing_input.split_once('.').ok_or(MALFORMED)?;
+
+        let decode_object = |part: &str| {
+            let json = Base64UrlUnpadded::decode_vec(part).map_err(|_| MALFORMED)?;
+            match serde_json::from_slice(&json) {
+                Ok(JsonValue::Object(x)) => Ok(x),
+                _ => Err(MALFORMED),
+            }
+        };
+
+        let header = decode_object(header)?;
+        let claims = decode_object(claims)?;
+        let signature = Base64UrlUnpadded::decode_vec(signature).map_err(|_| MALFORMED)?;
+
+        // `crit` extensions are not understood, RFC 7515 4.1.11 requires rejecting them
+        if header.contains_key("crit") {
+            return Err(MALFORMED);
+        }
+
+        let algorithm = match header.get("alg") {
+            Some(JsonValue::String(alg)) => {
+                Algorithm::from_name(alg).ok_or(TokenError::UnsupportedAlgorithm)?
+            }
+            _ => return Err(MALFORMED),
+        };
+
+        Ok(Self {
+            header,
+            claims,
+            algorithm,
+            signing_input,
+            signature,
+        })
+    }
+
+    pub(crate) fn header_str(&self, name: &str) -> Option<&str> {
+        self.header.get(name).and_then(JsonValue::as_str)
+    }
+
+    pub(crate) fn verify(&self, key: &Jwk) -> Result<(), TokenError> {
+        key.verify(
+            self.algorithm,
+            self.signing_input.as_bytes(),
+            &self.signature,
+        )
+    }
+}
+
+/// Signs `claims` with `key`, returning the compact JWS serialization.
+#[cfg(any(feature = "wit", test))]
+pub(crate) fn encode_compact(typ: &str, claims: &JsonValue, key: &SigningKey) -> String {
+    let mut header = json!({
+        "alg": key.algorithm().as_str(),
+        "typ": typ,
+    });
+    if let Some(kid) = key.public_key().key_id() {
+        header["kid"] = JsonValue::String(kid.into());
+    }
+
+    let mut token = Base64UrlUnpadded::encode_string(header.to_string().as_bytes());
+    token.push('.');
+    token.push_str(&Base64UrlUnpadded::encode_string(
+        claims.to_string().as_bytes(),
+    ));
+
+    let signature = key.sign(token.as_bytes());
+    token.push('.');
+    token.push_str(&Base64UrlUnpadded::encode_string(&signature));
+
+    token
+}
+
+/// Returns the base64url encoded SHA-256 hash of `token`, as used by the `wth` and `ath` claims.
+#[cfg(feature = "wit")]
+pub(crate) fn token_hash(token: &str) -> String {
+    use sha2::Digest;
+
+    Base64UrlUnpadded::encode_string(&Sha256::digest(token.as_bytes()))
+}
+
+/// Returns a random base64url encoded token identifier.
+#[cfg(feature = "wit")]
+pub(crate) fn random_id() -> String {
+    Base64UrlUnpadded::encode_string(&random_bytes::<16>())
+}
+
+#[cfg(any(feature = "jwt-svid", feature = "wit"))]
+pub(crate) fn subject(claims: &Map<String, JsonValue>) -> Result<SpiffeId, TokenError> {
+    let sub = claims
+        .get("sub")
+        .ok_or(TokenError::MissingClaim("sub"))?
+        .as_str()
+        .ok_or(TokenError::InvalidClaim("sub"))?;
+
+    Ok(SpiffeId::new(sub)?)
+}
+
+#[cfg(any(feature = "jwt-svid", feature = "wit"))]
+pub(crate) fn check_validity(
+    claims: &Map<String, JsonValue>,
+    now: SystemTime,
+) -> Result<(), TokenError> {
+    let expiry = numeric_date(claims, "exp")?.ok_or(TokenError::MissingClaim("exp"))?;
+    if now >= expiry {
+        return Err(TokenError::Expired);
+    }
+
+    if let Some(not_before) = numeric_date(claims, "nbf")?
+        && now < not_before
+    {
+        return Err(TokenError::NotYetValid);
+    }
+
+    Ok(())
+}
+
+#[cfg(any(feature = "jwt-svid", feature = "wit"))]
+pub(crate) fn numeric_date(
+    claims: &Map<String, JsonValue>,
+    name: &'static str,
+) -> Result<Option<SystemTime>, TokenError> {
+    let Some(value) = claims.get(name) else {
+        return Ok(None);
+    };
+
+    value
+        .as_f64()
+        .filter(|x| x.is_finite() && *x >= 0.0)
+        .and_then(|x| Duration::try_from_secs_f64(x).ok())
+        .and_then(|x| UNIX_EPOCH.checked_add(x))
+        .map(Some)
+        .ok_or(TokenError::InvalidClaim(name))
+}
+
+/// Checks that the `aud` claim, a string or an array of strings, contains `audience`.
+#[cfg(any(feature = "jwt-svid", feature = "wit"))]
+pub(crate) fn check_audience(
+    claims: &Map<String, JsonValue>,
+    audience: &str,
+) -> Result<(), TokenError> {
+    let matches = match claims.get("aud") {
+        Some(JsonValue::String(aud)) => aud == audience,
+        Some(JsonValue::Array(auds)) => auds.iter().any(|x| x.as_str() == Some(audience)),
+        Some(_) => return Err(TokenError::InvalidClaim("aud")),
+        None => return Err(TokenError::MissingClaim("aud")),
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(TokenError::Audience)
+    }
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::fill(&mut bytes).expect("system random number generator is unavailable");
+    bytes
+}
+
+fn verify_pkcs1v15<D>(key: &RsaPublicKey, message: &[u8], signature: &[u8]) -> bool
+where
+    D: sha2::Digest + rsa::pkcs8::AssociatedOid,
+{
+    use rsa::{pkcs1v15, signature::Verifier};
+
+    pkcs1v15::Signature::try_from(signature).is_ok_and(|sig| {
+        pkcs1v15::VerifyingKey::<D>::new(key.clone())
+            .verify(message, &sig)
+            .is_ok()
+    })
+}
+
+fn verify_pss<D>(key: &RsaPublicKey, message: &[u8], signature: &[u8]) -> bool
+where
+    D: sha2::Digest + sha2::digest::FixedOutputReset,
+{
+    use rsa::{pss, signature::Verifier};
+
+    pss::Signature::try_from(signature).is_ok_and(|sig| {
+        pss::VerifyingKey::<D>::new(key.clone())
+            .verify(message, &sig)
+            .is_ok()
+    })
+}
+
+fn ec_jwk(crv: &str, x: Option<&[u8]>, y: Option<&[u8]>) -> JsonValue {
+    json!({
+        "kty": "EC",
+        "crv": crv,
+        "x": Base64UrlUnpadded::encode_string(x.unwrap_or_default()),
+        "y": Base64UrlUnpadded::encode_string(y.unwrap_or_default()),
+    })
+}
+
+fn str_member<'a>(
+    jwk: &'a Map<String, JsonValue>,
+    name: &str,
+) -> Result<Option<&'a str>, TokenError> {
+    match jwk.get(name) {
+        None => Ok(None),
+        Some(JsonValue::String(x)) => Ok(Some(x)),
+        Some(_) => Err(TokenError::InvalidKey),
+    }
+}
+
+fn bytes_member(jwk: &Map<String, JsonValue>, name: &str) -> Result<Vec<u8>, TokenError> {
+    let value = str_member(jwk, name)?.ok_or(TokenError::InvalidKey)?;
+
+    Base64UrlUnpadded::decode_vec(value).map_err(|_| TokenError::InvalidKey)
+}
+
+fn fixed_member<const N: usize>(
+    jwk: &Map<String, JsonValue>,
+    name: &str,
+) -> Result<[u8; N], TokenError> {
+    bytes_member(jwk, name)?
+        .try_into()
+        .map_err(|_| TokenError::InvalidKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_verify() {
+        for alg in [Algorithm::ES256, Algorithm::ES384, Algorithm::EdDSA] {
+            let key = SigningKey::generate(alg).unwrap().with_key_id("k1");
+            let token = encode_compact("JWT", &json!({"sub": "x"}), &key);
+
+            let jws = CompactJws::decode(&token).unwrap();
+            assert_eq!(jws.algorithm, alg);
+            assert_eq!(jws.header_str("kid"), Some("k1"));
+            assert_eq!(jws.claims["sub"], "x");
+            jws.verify(key.public_key()).unwrap();
+
+            let other = SigningKey::generate(alg).unwrap();
+            assert!(matches!(
+                jws.verify(other.public_key()),
+                Err(TokenError::InvalidSignature)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_jwk_roundtrip() {
+        for alg in [Algorithm::ES256, Algorithm::ES384, Algorithm::EdDSA] {
+            let key = SigningKey::generate(alg).unwrap().with_key_id("k1");
+
+            let private = SigningKey::from_jwk(key.to_jwk().expose_secret()).unwrap();
+            assert_eq!(private.public_key(), key.public_key());
+
+            let public = Jwk::from_value(&key.public_key().to_value()).unwrap();
+            assert_eq!(&public, key.public_key());
+            assert_eq!(public.algorithm(), Some(alg));
+        }
+    }
+
+    #[test]
+    fn test_mismatched_private_key() {
+        let a = SigningKey::generate(Algorithm::ES256).unwrap();
+        let b = SigningKey::generate(Algorithm::ES256).unwrap();
+
+        let mut jwk: JsonValue = serde_json::from_str(a.to_jwk().expose_secret()).unwrap();
+        jwk["x"] = b.public_key().to_value()["x"].clone();
+
+        assert!(SigningKey::from_jwk(&jwk.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_jwk_set_skips_unsupported() {
+        let key = SigningKey::generate(Algorithm::ES256)
+            .unwrap()
+            .with_key_id("k1");
+        let jwks = json!({
+            "keys": [
+                {"kty": "OKP", "crv": "X25519", "x": "AAAA"},
+                key.public_key().to_value(),
+            ]
+        });
+
+        let set = JwkSet::from_value(&jwks).unwrap();
+        assert_eq!(set.keys().len(), 1);
+        assert!(set.find("k1").is_some());
+        assert!(set.find("k2").is_none());
+
+        assert!(JwkSet::from_json(r#"{"keys": [{"kty": "EC", "crv": "P-256"}]}"#).is_err());
+        assert!(JwkSet::from_json(r#"{"no_keys": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_reject_alg_confusion() {
+        let key = SigningKey::generate(Algorithm::ES256).unwrap();
+        let token = encode_compact("JWT", &json!({}), &key);
+        let (_, rest) = token.split_once('.').unwrap();
+
+        // same signature, header claims a different algorithm
+        let header = Base64UrlUnpadded::encode_string(br#"{"alg":"ES384"}"#);
+        let forged = format!("{header}.{rest}");
+        let jws = CompactJws::decode(&forged).unwrap();
+        assert!(matches!(
+            jws.verify(key.public_key()),
+            Err(TokenError::UnsupportedAlgorithm)
+        ));
+
+        let header = Base64UrlUnpadded::encode_string(br#"{"alg":"none"}"#);
+        let forged = format!("{header}.{rest}");
+        assert!(matches!(
+            CompactJws::decode(&forged),
+            Err(TokenError::UnsupportedAlgorithm)
+        ));
+    }
+}
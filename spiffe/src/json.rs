@@ -0,0 +1,196 @@
+//! Conversions between [`serde_json::Value`] and the protobuf well-known `google.protobuf.Struct`
+//! types from [`prost_types`], e.g. the JWT claims carried by `ValidateJWTSVIDResponse`.
+//!
+//! Protobuf represents every number as a double, so the conversions are defined as:
+//!
+//! - JSON integers are converted only if they fit in the exactly representable range of
+//!   [`f64`] (±2^53), otherwise [`JsonError::IntegerOutOfRange`] is returned.
+//! - Integral doubles within that range are converted back to JSON integers, so claims like
+//!   `exp` round-trip as integers instead of floats.
+//! - NaN and infinities have no JSON representation, [`JsonError::NonFiniteNumber`] is returned.
+//! - A [`prost_types::Value`] without a kind is treated as `null`.
+
+use alloc::string::String;
+
+use prost_types::{ListValue, Struct, Value as ProstValue, value::Kind};
+use serde_json::{Map, Number, Value as JsonValue};
+
+use crate::JsonError;
+
+/// Largest integer that every smaller integer can be represented exactly as an [`f64`].
+const MAX_SAFE_INTEGER: u64 = (1 << f64::MANTISSA_DIGITS) - 1;
+
+/// Converts a protobuf `Struct` into a JSON object.
+pub fn json_from_struct(s: Struct) -> Result<Map<String, JsonValue>, JsonError> {
+    s.fields
+        .into_iter()
+        .map(|(k, v)| json_from_value(v).map(|v| (k, v)))
+        .collect()
+}
+
+/// Converts a protobuf `Value` into a JSON value.
+pub fn json_from_value(v: ProstValue) -> Result<JsonValue, JsonError> {
+    let Some(kind) = v.kind else {
+        return Ok(JsonValue::Null);
+    };
+
+    Ok(match kind {
+        Kind::NullValue(_) => JsonValue::Null,
+        Kind::NumberValue(n) => JsonValue::Number(json_number_from_f64(n)?),
+        Kind::StringValue(s) => JsonValue::String(s),
+        Kind::BoolValue(b) => JsonValue::Bool(b),
+        Kind::StructValue(s) => JsonValue::Object(json_from_struct(s)?),
+        Kind::ListValue(l) => JsonValue::Array(
+            l.values
+                .into_iter()
+                .map(json_from_value)
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+/// Converts a JSON object into a protobuf `Struct`.
+pub fn struct_from_json(m: Map<String, JsonValue>) -> Result<Struct, JsonError> {
+    m.into_iter()
+        .map(|(k, v)| value_from_json(v).map(|v| (k, v)))
+        .collect::<Result<_, _>>()
+        .map(|fields| Struct { fields })
+}
+
+/// Converts a JSON value into a protobuf `Value`.
+pub fn value_from_json(v: JsonValue) -> Result<ProstValue, JsonError> {
+    let kind = match v {
+        JsonValue::Null => Kind::NullValue(0),
+        JsonValue::Bool(b) => Kind::BoolValue(b),
+        JsonValue::Number(n) => Kind::NumberValue(f64_from_json_number(&n)?),
+        JsonValue::String(s) => Kind::StringValue(s),
+        JsonValue::Array(a) => Kind::ListValue(ListValue {
+            values: a
+                .into_iter()
+                .map(value_from_json)
+                .collect::<Result<_, _>>()?,
+        }),
+        JsonValue::Object(m) => Kind::StructValue(struct_from_json(m)?),
+    };
+
+    Ok(ProstValue { kind: Some(kind) })
+}
+
+fn json_number_from_f64(n: f64) -> Result<Number, JsonError> {
+    if !n.is_finite() {
+        return Err(JsonError::NonFiniteNumber);
+    }
+
+    // `f64::fract` needs `std`, within the range an integral value survives the cast
+    if n.abs() <= MAX_SAFE_INTEGER as f64 && n as i64 as f64 == n {
+        // exact, the range check above ensures no truncation
+        return Ok(if n < 0.0 {
+            Number::from(n as i64)
+        } else {
+            Number::from(n as u64)
+        });
+    }
+
+    Number::from_f64(n).ok_or(JsonError::NonFiniteNumber)
+}
+
+fn f64_from_json_number(n: &Number) -> Result<f64, JsonError> {
+    if let Some(u) = n.as_u64() {
+        return if u <= MAX_SAFE_INTEGER {
+            Ok(u as f64)
+        } else {
+            Err(JsonError::IntegerOutOfRange)
+        };
+    }
+
+    if let Some(i) = n.as_i64() {
+        return if i.unsigned_abs() <= MAX_SAFE_INTEGER {
+            Ok(i as f64)
+        } else {
+            Err(JsonError::IntegerOutOfRange)
+        };
+    }
+
+    n.as_f64().ok_or(JsonError::NonFiniteNumber)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn number(n: f64) -> ProstValue {
+        ProstValue {
+            kind: Some(Kind::NumberValue(n)),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let claims = json!({
+            "sub": "spiffe://example.org/service",
+            "aud": ["a", "b"],
+            "exp": 1_700_000_000,
+            "ratio": 0.5,
+            "neg": -3,
+            "nested": [[1, [null, true]], {"k": {}}],
+            "none": null,
+        });
+        let JsonValue::Object(claims) = claims else {
+            unreachable!()
+        };
+
+        let s = struct_from_json(claims.clone()).unwrap();
+        assert_eq!(json_from_struct(s).unwrap(), claims);
+    }
+
+    #[test]
+    fn test_integer_precision() {
+        let max = MAX_SAFE_INTEGER as i64;
+
+        assert!(value_from_json(json!(max)).is_ok());
+        assert!(value_from_json(json!(-max)).is_ok());
+        assert!(matches!(
+            value_from_json(json!(max + 1)),
+            Err(JsonError::IntegerOutOfRange)
+        ));
+        assert!(matches!(
+            value_from_json(json!(-max - 1)),
+            Err(JsonError::IntegerOutOfRange)
+        ));
+        assert!(matches!(
+            value_from_json(json!(u64::MAX)),
+            Err(JsonError::IntegerOutOfRange)
+        ));
+
+        // large doubles are kept as floats rather than being turned into integers
+        assert_eq!(json_from_value(number(1e300)).unwrap(), json!(1e300));
+        assert_eq!(json_from_value(number(42.0)).unwrap(), json!(42));
+    }
+
+    #[test]
+    fn test_non_finite() {
+        for n in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(
+                json_from_value(number(n)),
+                Err(JsonError::NonFiniteNumber)
+            ));
+        }
+
+        let list = ProstValue {
+            kind: Some(Kind::ListValue(ListValue {
+                values: vec![number(1.0), number(f64::NAN)],
+            })),
+        };
+        assert!(json_from_value(list).is_err());
+    }
+
+    #[test]
+    fn test_missing_kind() {
+        assert_eq!(
+            json_from_value(ProstValue { kind: None }).unwrap(),
+            JsonValue::Null
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt::{Formatter, Result as FmtResult};
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt::{Formatter, Result as FmtResult};
 
 use base64ct::{Base64UrlUnpadded, Encoding};
 use serde_core::{
@@ -75,16 +76,38 @@ impl<'de> Visitor<'de> for SubClaimVisitor {
 ///
 /// Usually, this function is used to extract SPIFFE ID from a JWT-SVID that is already verified.
 pub fn spiffe_id_from_jwt_svid_unchecked(svid: &str) -> Result<SpiffeId, SpiffeError> {
-    const INVALID_JWT_ERR: SpiffeError = SpiffeError::InvalidJwtSvid;
-
-    let (prefix, _signature) = svid.rsplit_once('.').ok_or(INVALID_JWT_ERR)?;
-    let (_header, body) = prefix.rsplit_once('.').ok_or(INVALID_JWT_ERR)?;
-
-    let body_json = Base64UrlUnpadded::decode_vec(body).map_err(|_| INVALID_JWT_ERR)?;
-
-    let sub: Box<str> = JsonDeserializer::from_slice(&body_json)
+    let sub: Box<str> = JsonDeserializer::from_slice(&decode_claims(svid)?)
         .deserialize_struct("Claims", &["sub"], SubClaimVisitor)
         .map_err(|_| INVALID_JWT_ERR)?;
 
     SpiffeId::new(sub).map_err(SpiffeError::SpiffeId)
 }
+
+/// Returns whether the `aud` claim of a trusted JWT-SVID, a string or an array of strings,
+/// holds every audience of `audiences`.
+#[cfg(feature = "client")]
+pub(crate) fn jwt_svid_has_audiences<'a>(
+    svid: &str,
+    mut audiences: impl Iterator<Item = &'a str>,
+) -> Result<bool, SpiffeError> {
+    use serde_json::Value as JsonValue;
+
+    let claims: JsonValue =
+        serde_json::from_slice(&decode_claims(svid)?).map_err(|_| INVALID_JWT_ERR)?;
+
+    Ok(match claims.get("aud") {
+        Some(JsonValue::String(aud)) => audiences.all(|x| x == aud),
+        Some(JsonValue::Array(auds)) => audiences.all(|x| auds.iter().any(|aud| aud == x)),
+        _ => return Err(INVALID_JWT_ERR),
+    })
+}
+
+const INVALID_JWT_ERR: SpiffeError = SpiffeError::InvalidJwtSvid;
+
+/// Decodes the claims of a JWT, without checking its signature.
+fn decode_claims(svid: &str) -> Result<Vec<u8>, SpiffeError> {
+    let (prefix, _signature) = svid.rsplit_once('.').ok_or(INVALID_JWT_ERR)?;
+    let (_header, body) = prefix.rsplit_once('.').ok_or(INVALID_JWT_ERR)?;
+
+    Base64UrlUnpadded::decode_vec(body).map_err(|_| INVALID_JWT_ERR)
+}
@@ -0,0 +1,185 @@
+//! JWT-SVID validation against the JWT bundles of trust domains.
+//!
+//! # References
+//!
+//! - [The JWT-SVID](https://github.com/spiffe/spiffe/blob/main/standards/JWT-SVID.md)
+
+use std::{collections::HashMap, time::SystemTime};
+
+use serde_json::{Map, Value as JsonValue};
+use spiffe_id::{SpiffeId, TrustDomain};
+
+use crate::{
+    JwtSvid, TokenError,
+    jose::{CompactJws, JwkSet, check_audience, check_validity, numeric_date, subject},
+};
+
+/// JWT bundles keyed by trust domain.
+pub type JwtBundles = HashMap<TrustDomain<'static>, JwkSet>;
+
+/// Parses the JWKS documents yielded by [`JwtBundlesStream`](crate::client::JwtBundlesStream).
+pub fn parse_jwt_bundles(
+    bundles: &HashMap<TrustDomain<'static>, String>,
+) -> Result<JwtBundles, TokenError> {
+    bundles
+        .iter()
+        .map(|(td, jwks)| Ok((td.clone(), JwkSet::from_json(jwks)?)))
+        .collect()
+}
+
+/// Claims of a validated JWT-SVID.
+#[derive(Clone, Debug)]
+pub struct JwtSvidClaims {
+    spiffe_id: SpiffeId,
+    expiry: SystemTime,
+    claims: Map<String, JsonValue>,
+}
+
+impl JwtSvidClaims {
+    /// Validates a JWT-SVID presented for `audience` against the bundle of the trust domain of
+    /// its subject.
+    #[inline]
+    pub fn validate(token: &str, bundles: &JwtBundles, audience: &str) -> Result<Self, TokenError> {
+        Self::validate_at(token, bundles, audience, SystemTime::now())
+    }
+
+    /// Validates a JWT-SVID presented for `audience` against the bundle of the trust domain of
+    /// its subject, at time `now`.
+    pub fn validate_at(
+        token: &str,
+        bundles: &JwtBundles,
+        audience: &str,
+        now: SystemTime,
+    ) -> Result<Self, TokenError> {
+        let jws = CompactJws::decode(token)?;
+
+        // the `typ` header is optional, but must be one of these when present
+        match jws.header_str("typ") {
+            None | Some("JWT" | "JOSE") => (),
+            Some(_) => return Err(TokenError::Type),
+        }
+
+        let key_id = jws.header_str("kid").ok_or(TokenError::UnknownKey)?;
+        let spiffe_id = subject(&jws.claims)?;
+        let trust_domain = spiffe_id.trust_domain().into_owned();
+        let key = bundles
+            .get(&trust_domain)
+            .ok_or(TokenError::UnknownTrustDomain(trust_domain))?
+            .find(key_id)
+            .ok_or(TokenError::UnknownKey)?;
+
+        jws.verify(key)?;
+        check_validity(&jws.claims, now)?;
+        check_audience(&jws.claims, audience)?;
+
+        let expiry = numeric_date(&jws.claims, "exp")?.ok_or(TokenError::MissingClaim("exp"))?;
+
+        Ok(Self {
+            spiffe_id,
+            expiry,
+            claims: jws.claims,
+        })
+    }
+
+    /// Wraps the claims of a JWT-SVID validated by the Workload API.
+    #[cfg(all(feature = "http", feature = "transport"))]
+    pub(crate) fn from_validated(
+        spiffe_id: SpiffeId,
+        claims: Map<String, JsonValue>,
+    ) -> Result<Self, TokenError> {
+        let expiry = numeric_date(&claims, "exp")?.ok_or(TokenError::MissingClaim("exp"))?;
+
+        Ok(Self {
+            spiffe_id,
+            expiry,
+            claims,
+        })
+    }
+
+    #[inline]
+    pub fn spiffe_id(&self) -> &SpiffeId {
+        &self.spiffe_id
+    }
+
+    #[inline]
+    pub fn expiry(&self) -> SystemTime {
+        self.expiry
+    }
+
+    /// All claims of the token.
+    #[inline]
+    pub fn claims(&self) -> &Map<String, JsonValue> {
+        &self.claims
+    }
+
+    #[inline]
+    pub fn into_claims(self) -> Map<String, JsonValue> {
+        self.claims
+    }
+}
+
+impl JwtSvid {
+    /// Reads the `exp` claim of the token, without verifying it as it comes from the Workload
+    /// API.
+    pub fn expiry(&self) -> Result<SystemTime, TokenError> {
+        let jws = CompactJws::decode(self.svid())?;
+        numeric_date(&jws.claims, "exp")?.ok_or(TokenError::MissingClaim("exp"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::jose::{Algorithm, SigningKey, encode_compact};
+
+    const TD: TrustDomain = TrustDomain::const_new("example.org");
+
+    #[test]
+    fn test_validate_jwt_svid() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let authority = SigningKey::generate(Algorithm::ES256)
+            .unwrap()
+            .with_key_id("authority");
+        let claims = json!({
+            "sub": "spiffe://example.org/workload",
+            "aud": ["db", "cache"],
+            "exp": 1_800_000_300,
+        });
+        let token = encode_compact("JWT", &claims, &authority);
+
+        let bundles = parse_jwt_bundles(&HashMap::from([(
+            TD,
+            JwkSet::new(vec![authority.public_key().clone()])
+                .to_value()
+                .to_string(),
+        )]))
+        .unwrap();
+
+        let svid = JwtSvidClaims::validate_at(&token, &bundles, "cache", now).unwrap();
+        assert_eq!(svid.spiffe_id().as_str(), "spiffe://example.org/workload");
+        assert_eq!(svid.expiry(), now + Duration::from_secs(300));
+
+        assert!(matches!(
+            JwtSvidClaims::validate_at(&token, &bundles, "web", now),
+            Err(TokenError::Audience)
+        ));
+        assert!(matches!(
+            JwtSvidClaims::validate_at(&token, &bundles, "db", now + Duration::from_secs(300)),
+            Err(TokenError::Expired)
+        ));
+        assert!(matches!(
+            JwtSvidClaims::validate_at(&token, &JwtBundles::new(), "db", now),
+            Err(TokenError::UnknownTrustDomain(_))
+        ));
+
+        let wit = encode_compact("wit+jwt", &claims, &authority);
+        assert!(matches!(
+            JwtSvidClaims::validate_at(&wit, &bundles, "db", now),
+            Err(TokenError::Type)
+        ));
+    }
+}
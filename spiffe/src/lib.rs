@@ -1,19 +1,68 @@
 //! This module contains the high-level wrapper for the SPIFFE Workload API types
 //! and useful functions to work with them.
+//!
+//! Without the default `std` and `client` features, the SVID and bundle types, the DER helpers
+//! and JWT parsing are `no_std` + `alloc`.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+#[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "std")]
+mod crl;
 mod der;
 mod error;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "_jose")]
+pub mod jose;
+#[cfg(any(feature = "json", all(feature = "client", feature = "jwt")))]
+pub mod json;
 #[cfg(feature = "jwt")]
 mod jwt;
+#[cfg(feature = "jwt-svid")]
+pub mod jwt_svid;
+#[cfg(feature = "pem")]
+mod pem;
+mod secret;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "client")]
+pub mod source;
 mod types;
+#[cfg(feature = "wit")]
+pub mod wit;
 
+#[cfg(feature = "std")]
+pub use self::crl::{Crl, CrlSet};
+#[cfg(feature = "blocking")]
+pub use self::error::BlockingError;
+#[cfg(feature = "bundle")]
+pub use self::error::BundleError;
+#[cfg(feature = "transport")]
+pub use self::error::ConnectError;
+#[cfg(any(feature = "json", all(feature = "client", feature = "jwt")))]
+pub use self::error::JsonError;
+#[cfg(feature = "pem")]
+pub use self::error::PemError;
+#[cfg(feature = "client")]
+pub use self::error::SourceError;
+#[cfg(feature = "_jose")]
+pub use self::error::TokenError;
 #[cfg(feature = "jwt")]
 pub use self::jwt::spiffe_id_from_jwt_svid_unchecked;
 pub use self::{
     der::{CertificateIter, spiffe_id_from_x509_svid_unchecked, split_certificates},
-    error::{InvalidDerError, SpiffeError},
+    error::{EndpointError, InvalidDerError, SpiffeError},
+    secret::{Secret, Wipe},
     types::{JwtSvid, WitSvid, X509Bundle, X509Svid},
 };
 
-type StdError = Box<dyn core::error::Error + Send + Sync + 'static>;
+#[cfg(feature = "client")]
+type StdError = alloc::boxed::Box<dyn core::error::Error + Send + Sync + 'static>;
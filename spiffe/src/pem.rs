@@ -0,0 +1,234 @@
+//! PEM import and export of [`X509Svid`] and [`X509Bundle`], compatible with the files written by
+//! `spiffe-helper`.
+//!
+//! Private keys are always PKCS#8 (`PRIVATE KEY`), the format the Workload API delivers them in.
+
+use std::{fs, path::Path};
+
+use base64ct::{Base64, Encoding};
+use rustls_pki_types::{
+    CertificateDer, PrivatePkcs8KeyDer,
+    pem::{Error as PemDecodeError, PemObject},
+};
+
+use crate::{
+    InvalidDerError, PemError, Secret, X509Bundle, X509Svid,
+    der::{spki_from_certificate, split_certificates},
+    spiffe_id_from_x509_svid_unchecked,
+};
+
+const CERTIFICATE_LABEL: &str = "CERTIFICATE";
+const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+
+impl X509Bundle {
+    /// Parses a bundle from the `CERTIFICATE` sections of `pem`, other sections are ignored.
+    pub fn from_pem(pem: &[u8]) -> Result<Self, PemError> {
+        certificates_from_pem(pem).map(Self::from_certificates)
+    }
+
+    pub fn from_pem_file(path: impl AsRef<Path>) -> Result<Self, PemError> {
+        Self::from_pem(&fs::read(path)?)
+    }
+
+    pub fn to_pem(&self) -> String {
+        certificates_to_pem(self.bundle())
+    }
+}
+
+impl X509Svid {
+    /// Parses an X.509-SVID from its certificate chain, leaf first, and its PKCS#8 private key.
+    ///
+    /// The SPIFFE ID is extracted from the leaf certificate, which must match the private key.
+    /// The trust bundle is left empty, use [`X509Svid::with_bundle`] to attach one.
+    pub fn from_pem(cert_chain_pem: &[u8], key_pem: &[u8]) -> Result<Self, PemError> {
+        let svid = certificates_from_pem(cert_chain_pem)?;
+        let key = private_key_from_pem(key_pem)?;
+
+        let spiffe_id = spiffe_id_from_x509_svid_unchecked(&svid[0])?;
+        check_key_matches(&svid[0], key.expose_secret())?;
+
+        Ok(Self::from_parts(spiffe_id, svid.into(), key))
+    }
+
+    pub fn from_pem_files(
+        cert_chain_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Result<Self, PemError> {
+        Self::from_pem(&fs::read(cert_chain_path)?, &fs::read(key_path)?)
+    }
+
+    /// Returns the PEM encoded certificate chain and private key.
+    pub fn to_pem(&self) -> (String, Secret<String>) {
+        let key_der = self.key();
+        let der = key_der.secret_der();
+        // sized up front, as growing the string would leave unwiped copies of the key behind
+        let mut key = Secret::new(String::with_capacity(pem_len(PRIVATE_KEY_LABEL, der.len())));
+        encode_pem(key.expose_mut(), PRIVATE_KEY_LABEL, der);
+
+        (certificates_to_pem(self.svid()), key)
+    }
+}
+
+fn certificates_from_pem(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, PemError> {
+    let certs = CertificateDer::pem_slice_iter(pem).collect::<Result<Vec<_>, _>>()?;
+
+    // every section must hold exactly one well-formed certificate
+    for cert in &certs {
+        let mut iter = split_certificates(cert);
+        if !matches!((iter.next(), iter.next()), (Some(Ok(_)), None)) {
+            return Err(PemError::Svid(InvalidDerError.into()));
+        }
+    }
+
+    if certs.is_empty() {
+        return Err(PemError::NoCertificate);
+    }
+
+    Ok(certs)
+}
+
+/// Decodes the PKCS#8 private key of `pem`, wiping the decoded copy once moved into the secret.
+fn private_key_from_pem(pem: &[u8]) -> Result<Secret<Vec<u8>>, PemError> {
+    #[cfg_attr(not(feature = "zeroize"), expect(unused_mut))]
+    let mut key = PrivatePkcs8KeyDer::from_pem_slice(pem).map_err(|e| match e {
+        PemDecodeError::NoItemsFound => PemError::NoPrivateKey,
+        e => PemError::Pem(e),
+    })?;
+    let secret = Secret::new(key.secret_pkcs8_der().to_vec());
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut key);
+
+    Ok(secret)
+}
+
+fn certificates_to_pem(certs: &[CertificateDer<'_>]) -> String {
+    let mut pem = String::new();
+    for cert in certs {
+        encode_pem(&mut pem, CERTIFICATE_LABEL, cert);
+    }
+
+    pem
+}
+
+/// Returns the length of the PEM section encoding `der_len` bytes.
+fn pem_len(label: &str, der_len: usize) -> usize {
+    let body_len = der_len.div_ceil(3) * 4;
+    let boundaries_len = "-----BEGIN -----\n".len() + "-----END -----\n".len() + 2 * label.len();
+
+    boundaries_len + body_len + body_len.div_ceil(64)
+}
+
+fn encode_pem(pem: &mut String, label: &str, der: &[u8]) {
+    // may hold a private key
+    let body = Secret::new(Base64::encode_string(der));
+    let body = body.expose_secret();
+
+    pem.push_str("-----BEGIN ");
+    pem.push_str(label);
+    pem.push_str("-----\n");
+    // RFC 7468 wraps the base64 text at 64 characters
+    for line in body.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str("-----END ");
+    pem.push_str(label);
+    pem.push_str("-----\n");
+}
+
+/// Checks that the public key of `leaf` belongs to `key`.
+fn check_key_matches(leaf: &CertificateDer<'_>, key: &[u8]) -> Result<(), PemError> {
+    use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+
+    let spki = spki_from_certificate(leaf).ok_or(PemError::Svid(InvalidDerError.into()))?;
+
+    let matches = if let Ok(key) = p256::SecretKey::from_pkcs8_der(key) {
+        p256::PublicKey::from_public_key_der(spki).is_ok_and(|x| x == key.public_key())
+    } else if let Ok(key) = p384::SecretKey::from_pkcs8_der(key) {
+        p384::PublicKey::from_public_key_der(spki).is_ok_and(|x| x == key.public_key())
+    } else if let Ok(key) = ed25519_dalek::SigningKey::from_pkcs8_der(key) {
+        ed25519_dalek::VerifyingKey::from_public_key_der(spki)
+            .is_ok_and(|x| x == key.verifying_key())
+    } else if let Ok(key) = rsa::RsaPrivateKey::from_pkcs8_der(key) {
+        rsa::RsaPublicKey::from_public_key_der(spki).is_ok_and(|x| x == key.to_public_key())
+    } else {
+        return Err(PemError::UnsupportedKey);
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(PemError::KeyMismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spiffe_id::{SpiffeId, TrustDomain};
+    use spiffe_testkit::{IssueOptions, TestCa};
+
+    use super::*;
+
+    const TD: TrustDomain = TrustDomain::const_new("example.org");
+
+    fn issue(ca: &TestCa) -> (Vec<CertificateDer<'static>>, String) {
+        let spiffe_id = SpiffeId::new("spiffe://example.org/workload").unwrap();
+        let (chain, key) = ca.issue_x509_chain(&spiffe_id, &IssueOptions::new());
+        let mut pem = String::new();
+        encode_pem(&mut pem, PRIVATE_KEY_LABEL, key.secret_pkcs8_der());
+
+        (chain, pem)
+    }
+
+    #[test]
+    fn test_svid_roundtrip() {
+        let (chain, key) = issue(&TestCa::new(TD));
+        let cert = certificates_to_pem(&chain);
+
+        let svid = X509Svid::from_pem(cert.as_bytes(), key.as_bytes()).unwrap();
+        assert_eq!(svid.svid(), &chain[..1]);
+        assert_eq!(
+            svid.spiffe_id(),
+            &SpiffeId::new("spiffe://example.org/workload").unwrap()
+        );
+        assert!(svid.bundle().bundle().is_empty());
+
+        let (cert_pem, key_pem) = svid.to_pem();
+        assert_eq!(cert_pem, cert);
+        assert_eq!(key_pem.expose_secret(), &key);
+        // the key was encoded without growing its string
+        assert_eq!(key_pem.expose_secret().capacity(), key.len());
+    }
+
+    #[test]
+    fn test_svid_invalid() {
+        let ca = TestCa::new(TD);
+        let (chain, key) = issue(&ca);
+        let (_, other_key) = issue(&ca);
+        let cert = certificates_to_pem(&chain);
+
+        assert!(matches!(
+            X509Svid::from_pem(cert.as_bytes(), other_key.as_bytes()),
+            Err(PemError::KeyMismatch)
+        ));
+        assert!(matches!(
+            X509Svid::from_pem(cert.as_bytes(), cert.as_bytes()),
+            Err(PemError::NoPrivateKey)
+        ));
+        assert!(matches!(
+            X509Svid::from_pem(key.as_bytes(), key.as_bytes()),
+            Err(PemError::NoCertificate)
+        ));
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let (chain, key) = issue(&TestCa::new(TD));
+        let cert = certificates_to_pem(&chain);
+
+        let pem = format!("{cert}{key}{cert}");
+        let bundle = X509Bundle::from_pem(pem.as_bytes()).unwrap();
+        assert_eq!(bundle.bundle().len(), 2);
+        assert_eq!(bundle.to_pem(), format!("{cert}{cert}"));
+    }
+}
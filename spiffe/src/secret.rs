@@ -0,0 +1,151 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    hash::{Hash, Hasher},
+    hint, mem,
+};
+
+mod sealed {
+    use alloc::{string::String, vec::Vec};
+
+    pub trait Sealed {
+        fn bytes(&self) -> &[u8];
+    }
+
+    impl Sealed for String {
+        fn bytes(&self) -> &[u8] {
+            self.as_bytes()
+        }
+    }
+
+    impl Sealed for Vec<u8> {
+        fn bytes(&self) -> &[u8] {
+            self
+        }
+    }
+}
+
+/// Values [`Secret`] knows how to wipe.
+pub trait Wipe: sealed::Sealed + Default {
+    /// Overwrites the value, including spare capacity, when the `zeroize` feature is enabled.
+    fn wipe(&mut self);
+}
+
+impl Wipe for String {
+    #[inline]
+    fn wipe(&mut self) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+impl Wipe for Vec<u8> {
+    #[inline]
+    fn wipe(&mut self) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+/// Key material or bearer token.
+///
+/// `Debug` never prints the value, and with the `zeroize` feature the memory holding it is
+/// wiped on drop.
+#[derive(Clone, Default)]
+pub struct Secret<T: Wipe>(T);
+
+impl<T: Wipe> Secret<T> {
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    #[inline]
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+
+    #[cfg(any(feature = "pem", feature = "_jose"))]
+    #[inline]
+    pub(crate) fn expose_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Unwraps the value, which is no longer wiped on drop.
+    #[inline]
+    pub fn into_inner(mut self) -> T {
+        mem::take(&mut self.0)
+    }
+}
+
+impl<T: Wipe> From<T> for Secret<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+/// Compares in constant time, only the lengths of the values may leak.
+impl<T: Wipe> PartialEq for Secret<T> {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.0.bytes(), other.0.bytes());
+        a.len() == b.len()
+            && a.iter()
+                .zip(b)
+                .fold(0, |diff, (a, b)| hint::black_box(diff | (a ^ b)))
+                == 0
+    }
+}
+
+impl<T: Wipe> Eq for Secret<T> {}
+
+impl<T: Wipe + Hash> Hash for Secret<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<T: Wipe> Debug for Secret<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("[secret elided]")
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_redacted() {
+        let secret = Secret::new(String::from("token"));
+        assert_eq!(format!("{secret:?}"), "[secret elided]");
+        assert_eq!(secret.expose_secret(), "token");
+        assert_eq!(secret.into_inner(), "token");
+    }
+
+    #[test]
+    fn test_eq() {
+        let secret = Secret::new(vec![1, 2, 3]);
+        assert_eq!(secret, Secret::new(vec![1, 2, 3]));
+        assert_ne!(secret, Secret::new(vec![1, 2, 4]));
+        assert_ne!(secret, Secret::new(vec![1, 2]));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_wipe() {
+        let mut key = vec![0x42; 32];
+        key.truncate(16);
+        key.wipe();
+        assert!(key.is_empty());
+        // SAFETY: the capacity was initialized above and is still allocated
+        let spare = unsafe { core::slice::from_raw_parts(key.as_ptr(), 32) };
+        assert!(spare.iter().all(|x| *x == 0));
+    }
+}
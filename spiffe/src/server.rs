@@ -0,0 +1,591 @@
+//! Workload API server serving the identities of an [`IdentityProvider`].
+//!
+//! [`WorkloadApiServer`] encodes the domain types into the Workload API messages, keeps the
+//! streams of every caller open and re-sends them their identities when the provider signals
+//! a rotation through an [`UpdateNotifier`].
+
+mod convert;
+#[cfg(feature = "server-transport")]
+mod unix;
+
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use futures_util::{
+    StreamExt, future,
+    stream::{self, BoxStream},
+};
+use prost::bytes::Bytes;
+#[cfg(feature = "json")]
+use spiffe_id::SpiffeId;
+use spiffe_id::TrustDomain;
+use spiffe_proto::{
+    JwtBundlesRequest, JwtBundlesResponse, JwtSvidResponse, ValidateJwtSvidRequest,
+    ValidateJwtSvidResponse, WitBundlesRequest, WitBundlesResponse, WitSvidResponse,
+    X509BundlesRequest, X509BundlesResponse, X509SvidRequest, X509SvidResponse,
+    server::{SpiffeWorkloadApi, SpiffeWorkloadApiServer},
+};
+use tokio::sync::watch;
+#[cfg(feature = "server-transport")]
+use tonic::transport::{Error as TransportError, Server};
+use tonic::{Extensions, Request, Response, Result, Status};
+
+#[cfg(feature = "server-transport")]
+pub use self::unix::{PeerInfo, PeerStream, UnixIncoming};
+use crate::{
+    JwtSvid, WitSvid,
+    client::{JwtSvidRequest, WitSvidRequest, X509BundlesContext, X509SvidContext},
+};
+
+const NO_IDENTITY: &str = "no identity issued";
+
+type ResponseStream<T> = BoxStream<'static, Result<T>>;
+
+/// Peer of a Workload API call.
+#[derive(Clone, Debug, Default)]
+pub struct Caller {
+    extensions: Extensions,
+}
+
+impl Caller {
+    /// Returns the request extensions, holding e.g. the connection info of the transport.
+    #[inline]
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns the peer process of a call served on [`UnixIncoming`].
+    #[cfg(feature = "server-transport")]
+    #[inline]
+    pub fn peer(&self) -> Option<&PeerInfo> {
+        self.extensions.get()
+    }
+}
+
+impl<T> From<&Request<T>> for Caller {
+    fn from(req: &Request<T>) -> Self {
+        Self {
+            extensions: req.extensions().clone(),
+        }
+    }
+}
+
+/// Source of the identities served to Workload API callers.
+///
+/// An error fails the call, or ends the stream it is returned for. Empty results are
+/// answered with `PermissionDenied`, and methods not overridden return `Unimplemented`.
+pub trait IdentityProvider: Send + Sync + 'static {
+    /// Returns the X.509-SVIDs of `caller`, the default one first, with the CRLs and the
+    /// federated bundles.
+    fn x509_svid_context(
+        &self,
+        caller: &Caller,
+    ) -> impl Future<Output = Result<X509SvidContext>> + Send;
+
+    /// Returns the X.509 bundles and CRLs `caller` may use to authenticate peers.
+    fn x509_bundles_context(
+        &self,
+        caller: &Caller,
+    ) -> impl Future<Output = Result<X509BundlesContext>> + Send;
+
+    /// Returns JWT-SVIDs of `caller` for `request`.
+    fn jwt_svids(
+        &self,
+        _: &Caller,
+        _: &JwtSvidRequest,
+    ) -> impl Future<Output = Result<Vec<JwtSvid>>> + Send {
+        future::ready(Err(Status::unimplemented("JWT-SVIDs are not supported")))
+    }
+
+    /// Returns the JWT bundles `caller` may use, JWKS documents keyed by trust domain.
+    fn jwt_bundles(
+        &self,
+        _: &Caller,
+    ) -> impl Future<Output = Result<HashMap<TrustDomain<'static>, String>>> + Send {
+        future::ready(Err(Status::unimplemented("JWT bundles are not supported")))
+    }
+
+    /// Validates `svid` for `audience`, returning its SPIFFE ID and claims.
+    #[cfg(feature = "json")]
+    fn validate_jwt_svid(
+        &self,
+        _: &Caller,
+        _: &str,
+        _: &str,
+    ) -> impl Future<Output = Result<(SpiffeId, serde_json::Map<String, serde_json::Value>)>> + Send
+    {
+        future::ready(Err(Status::unimplemented(
+            "JWT-SVID validation is not supported",
+        )))
+    }
+
+    /// Returns WIT-SVIDs of `caller` for `request`.
+    fn wit_svids(
+        &self,
+        _: &Caller,
+        _: &WitSvidRequest,
+    ) -> impl Future<Output = Result<Vec<WitSvid>>> + Send {
+        future::ready(Err(Status::unimplemented("WIT-SVIDs are not supported")))
+    }
+
+    /// Returns the WIT bundles `caller` may use, JWKS documents keyed by trust domain.
+    fn wit_bundles(
+        &self,
+        _: &Caller,
+    ) -> impl Future<Output = Result<HashMap<TrustDomain<'static>, String>>> + Send {
+        future::ready(Err(Status::unimplemented("WIT bundles are not supported")))
+    }
+}
+
+/// Signals the open streams of a [`WorkloadApiServer`] that identities changed.
+#[derive(Clone, Debug)]
+pub struct UpdateNotifier {
+    tx: watch::Sender<()>,
+}
+
+impl UpdateNotifier {
+    /// Makes every open stream query the provider again, callers are only sent responses
+    /// that differ from the last one.
+    pub fn notify(&self) {
+        self.tx.send_replace(());
+    }
+}
+
+/// Workload API implementation on top of an [`IdentityProvider`].
+#[derive(Debug)]
+pub struct WorkloadApiServer<P> {
+    provider: Arc<P>,
+    updates: watch::Sender<()>,
+}
+
+impl<P: IdentityProvider> WorkloadApiServer<P> {
+    pub fn new(provider: P) -> Self {
+        Self::from_arc(Arc::new(provider))
+    }
+
+    pub fn from_arc(provider: Arc<P>) -> Self {
+        Self {
+            provider,
+            updates: watch::Sender::new(()),
+        }
+    }
+
+    #[inline]
+    pub fn provider(&self) -> &Arc<P> {
+        &self.provider
+    }
+
+    pub fn notifier(&self) -> UpdateNotifier {
+        UpdateNotifier {
+            tx: self.updates.clone(),
+        }
+    }
+
+    /// Wraps the server into the gRPC service, e.g. for `tonic::transport::Server::add_service`.
+    pub fn into_service(self) -> SpiffeWorkloadApiServer<Self> {
+        SpiffeWorkloadApiServer::from_arc(Arc::new(self))
+    }
+
+    /// Serves the Workload API on `incoming`, with the [`PeerInfo`] of every caller, until the
+    /// listener fails.
+    #[cfg(feature = "server-transport")]
+    pub async fn serve_unix(self, incoming: UnixIncoming) -> Result<(), TransportError> {
+        Server::builder()
+            .serve_with_incoming(self.into_service().into_service(), incoming)
+            .await
+    }
+
+    async fn watch<T, F, Fut>(&self, caller: Caller, fetch: F) -> Result<ResponseStream<T>>
+    where
+        T: PartialEq + Clone + Send + 'static,
+        F: Fn(Arc<P>, Caller) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        // subscribed before the first fetch, so no update after it is missed
+        let updates = self.updates.subscribe();
+        let first = fetch(self.provider.clone(), caller.clone()).await?;
+
+        let state = Watch {
+            provider: self.provider.clone(),
+            caller,
+            updates,
+            fetch,
+            last: first.clone(),
+        };
+        let rest = stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            loop {
+                // the server and all notifiers were dropped
+                state.updates.changed().await.ok()?;
+
+                match (state.fetch)(state.provider.clone(), state.caller.clone()).await {
+                    Ok(resp) if resp == state.last => {}
+                    Ok(resp) => {
+                        state.last = resp.clone();
+                        return Some((Ok(resp), Some(state)));
+                    }
+                    Err(status) => return Some((Err(status), None)),
+                }
+            }
+        });
+
+        Ok(stream::once(future::ready(Ok(first))).chain(rest).boxed())
+    }
+}
+
+struct Watch<P, T, F> {
+    provider: Arc<P>,
+    caller: Caller,
+    updates: watch::Receiver<()>,
+    fetch: F,
+    last: T,
+}
+
+fn check_identity<T>(items: &[T]) -> Result<()> {
+    if items.is_empty() {
+        Err(Status::permission_denied(NO_IDENTITY))
+    } else {
+        Ok(())
+    }
+}
+
+async fn x509_svid_response<P: IdentityProvider>(
+    provider: Arc<P>,
+    caller: Caller,
+) -> Result<X509SvidResponse> {
+    let context = provider.x509_svid_context(&caller).await?;
+    check_identity(&context.svids)?;
+
+    Ok(context.into())
+}
+
+async fn x509_bundles_response<P: IdentityProvider>(
+    provider: Arc<P>,
+    caller: Caller,
+) -> Result<X509BundlesResponse> {
+    let context = provider.x509_bundles_context(&caller).await?;
+    if context.bundles.is_empty() {
+        return Err(Status::permission_denied(NO_IDENTITY));
+    }
+
+    Ok(context.into())
+}
+
+async fn jwt_bundles_response<P: IdentityProvider>(
+    provider: Arc<P>,
+    caller: Caller,
+) -> Result<JwtBundlesResponse> {
+    let bundles = provider.jwt_bundles(&caller).await?;
+    if bundles.is_empty() {
+        return Err(Status::permission_denied(NO_IDENTITY));
+    }
+
+    Ok(JwtBundlesResponse {
+        bundles: convert::encode_bundles(bundles, Bytes::from),
+    })
+}
+
+async fn wit_svid_response<P: IdentityProvider>(
+    provider: Arc<P>,
+    caller: Caller,
+    request: WitSvidRequest,
+) -> Result<WitSvidResponse> {
+    let svids = provider.wit_svids(&caller, &request).await?;
+    check_identity(&svids)?;
+
+    Ok(WitSvidResponse {
+        svids: svids.into_iter().map(Into::into).collect(),
+    })
+}
+
+async fn wit_bundles_response<P: IdentityProvider>(
+    provider: Arc<P>,
+    caller: Caller,
+) -> Result<WitBundlesResponse> {
+    let bundles = provider.wit_bundles(&caller).await?;
+    if bundles.is_empty() {
+        return Err(Status::permission_denied(NO_IDENTITY));
+    }
+
+    Ok(WitBundlesResponse {
+        bundles: bundles
+            .into_iter()
+            .map(|(td, bundle)| (format!("spiffe://{td}"), bundle))
+            .collect(),
+    })
+}
+
+impl<P: IdentityProvider> SpiffeWorkloadApi for WorkloadApiServer<P> {
+    type FetchX509SvidStream = ResponseStream<X509SvidResponse>;
+
+    async fn fetch_x509_svid(
+        &self,
+        req: Request<X509SvidRequest>,
+    ) -> Result<Response<Self::FetchX509SvidStream>> {
+        self.watch(Caller::from(&req), x509_svid_response)
+            .await
+            .map(Response::new)
+    }
+
+    type FetchX509BundlesStream = ResponseStream<X509BundlesResponse>;
+
+    async fn fetch_x509_bundles(
+        &self,
+        req: Request<X509BundlesRequest>,
+    ) -> Result<Response<Self::FetchX509BundlesStream>> {
+        self.watch(Caller::from(&req), x509_bundles_response)
+            .await
+            .map(Response::new)
+    }
+
+    async fn fetch_jwt_svid(
+        &self,
+        req: Request<spiffe_proto::JwtSvidRequest>,
+    ) -> Result<Response<JwtSvidResponse>> {
+        let caller = Caller::from(&req);
+        let request = JwtSvidRequest::try_from(req.into_inner())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let svids = self.provider.jwt_svids(&caller, &request).await?;
+        check_identity(&svids)?;
+
+        Ok(Response::new(JwtSvidResponse {
+            svids: svids.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    type FetchJwtBundlesStream = ResponseStream<JwtBundlesResponse>;
+
+    async fn fetch_jwt_bundles(
+        &self,
+        req: Request<JwtBundlesRequest>,
+    ) -> Result<Response<Self::FetchJwtBundlesStream>> {
+        self.watch(Caller::from(&req), jwt_bundles_response)
+            .await
+            .map(Response::new)
+    }
+
+    #[cfg(feature = "json")]
+    async fn validate_jwt_svid(
+        &self,
+        req: Request<ValidateJwtSvidRequest>,
+    ) -> Result<Response<ValidateJwtSvidResponse>> {
+        let caller = Caller::from(&req);
+        let ValidateJwtSvidRequest { audience, svid } = req.into_inner();
+        if audience.is_empty() {
+            return Err(Status::invalid_argument("audience must be specified"));
+        }
+        if svid.is_empty() {
+            return Err(Status::invalid_argument("svid must be specified"));
+        }
+
+        let (spiffe_id, claims) = self
+            .provider
+            .validate_jwt_svid(&caller, &audience, &svid)
+            .await?;
+        let claims =
+            crate::json::struct_from_json(claims).map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(ValidateJwtSvidResponse {
+            spiffe_id: spiffe_id.into(),
+            claims: Some(claims),
+        }))
+    }
+
+    #[cfg(not(feature = "json"))]
+    async fn validate_jwt_svid(
+        &self,
+        _: Request<ValidateJwtSvidRequest>,
+    ) -> Result<Response<ValidateJwtSvidResponse>> {
+        Err(Status::unimplemented(
+            "JWT-SVID validation is not supported",
+        ))
+    }
+
+    type FetchWitSvidStream = ResponseStream<WitSvidResponse>;
+
+    async fn fetch_wit_svid(
+        &self,
+        req: Request<spiffe_proto::WitSvidRequest>,
+    ) -> Result<Response<Self::FetchWitSvidStream>> {
+        let caller = Caller::from(&req);
+        let request = WitSvidRequest::try_from(req.into_inner())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let fetch = move |provider, caller| wit_svid_response(provider, caller, request.clone());
+        self.watch(caller, fetch).await.map(Response::new)
+    }
+
+    type FetchWitBundlesStream = ResponseStream<WitBundlesResponse>;
+
+    async fn fetch_wit_bundles(
+        &self,
+        req: Request<WitBundlesRequest>,
+    ) -> Result<Response<Self::FetchWitBundlesStream>> {
+        self.watch(Caller::from(&req), wit_bundles_response)
+            .await
+            .map(Response::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures_util::FutureExt;
+    use tonic::Code;
+
+    use super::*;
+    use crate::X509Svid;
+
+    #[derive(Default)]
+    struct Provider {
+        svids: Mutex<Vec<X509Svid>>,
+    }
+
+    impl IdentityProvider for Provider {
+        async fn x509_svid_context(&self, _: &Caller) -> Result<X509SvidContext> {
+            Ok(X509SvidContext {
+                svids: self.svids.lock().unwrap().clone(),
+                ..Default::default()
+            })
+        }
+
+        async fn x509_bundles_context(&self, _: &Caller) -> Result<X509BundlesContext> {
+            Ok(X509BundlesContext::default())
+        }
+    }
+
+    fn svid(cert: &'static [u8]) -> X509Svid {
+        spiffe_proto::X509Svid {
+            spiffe_id: "spiffe://example.org/workload".into(),
+            x509_svid: cert.into(),
+            x509_svid_key: b"key".as_slice().into(),
+            bundle: [0x30, 0x00].as_slice().into(),
+            hint: "internal".into(),
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    fn fetch_x509_svid(
+        server: &WorkloadApiServer<Provider>,
+    ) -> Result<ResponseStream<X509SvidResponse>> {
+        server
+            .fetch_x509_svid(Request::new(X509SvidRequest {}))
+            .now_or_never()
+            .unwrap()
+            .map(Response::into_inner)
+    }
+
+    #[test]
+    fn test_x509_svid_rotation() {
+        let server = WorkloadApiServer::new(Provider::default());
+        let notifier = server.notifier();
+
+        let err = fetch_x509_svid(&server).err().unwrap();
+        assert_eq!(err.code(), Code::PermissionDenied);
+
+        *server.provider().svids.lock().unwrap() = vec![svid(&[0x30, 0x00])];
+        let mut stream = fetch_x509_svid(&server).unwrap();
+        let first = stream.next().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(first.svids[0].x509_svid.as_ref(), [0x30, 0x00]);
+        assert_eq!(first.svids[0].hint, "internal");
+        assert!(stream.next().now_or_never().is_none());
+
+        // unchanged identities are not sent again
+        notifier.notify();
+        assert!(stream.next().now_or_never().is_none());
+
+        *server.provider().svids.lock().unwrap() = vec![svid(&[0x30, 0x01, 0x00])];
+        notifier.notify();
+        let second = stream.next().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(
+            X509Svid::try_from(second.svids[0].clone()).unwrap(),
+            svid(&[0x30, 0x01, 0x00])
+        );
+
+        server.provider().svids.lock().unwrap().clear();
+        notifier.notify();
+        let err = stream.next().now_or_never().unwrap().unwrap().unwrap_err();
+        assert_eq!(err.code(), Code::PermissionDenied);
+        assert!(stream.next().now_or_never().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unimplemented_and_invalid_requests() {
+        let server = WorkloadApiServer::new(Provider::default());
+
+        let request = spiffe_proto::JwtSvidRequest {
+            audience: vec![],
+            spiffe_id: String::new(),
+        };
+        let err = server
+            .fetch_jwt_svid(Request::new(request))
+            .now_or_never()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        let request = spiffe_proto::JwtSvidRequest {
+            audience: vec!["db".into()],
+            spiffe_id: String::new(),
+        };
+        let err = server
+            .fetch_jwt_svid(Request::new(request))
+            .now_or_never()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.code(), Code::Unimplemented);
+
+        let err = server
+            .fetch_x509_bundles(Request::new(X509BundlesRequest {}))
+            .now_or_never()
+            .unwrap()
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), Code::PermissionDenied);
+    }
+
+    #[cfg(all(feature = "server-transport", feature = "transport"))]
+    #[tokio::test]
+    async fn test_serve_unix_attestation() {
+        use crate::client::SpiffeWorkloadApiClient;
+
+        struct Attesting;
+
+        impl IdentityProvider for Attesting {
+            async fn x509_svid_context(&self, caller: &Caller) -> Result<X509SvidContext> {
+                let peer = caller.peer().unwrap();
+                let svids = if peer.pid() == Some(std::process::id() as _) {
+                    vec![svid(&[0x30, 0x00])]
+                } else {
+                    vec![]
+                };
+
+                Ok(X509SvidContext {
+                    svids,
+                    ..Default::default()
+                })
+            }
+
+            async fn x509_bundles_context(&self, _: &Caller) -> Result<X509BundlesContext> {
+                Ok(X509BundlesContext::default())
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("spiffe-server-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let incoming = UnixIncoming::bind(&path).unwrap();
+        let task = tokio::spawn(WorkloadApiServer::new(Attesting).serve_unix(incoming));
+
+        let client = SpiffeWorkloadApiClient::connect(&format!("unix://{}", path.display()))
+            .await
+            .unwrap();
+        let mut svids = client.fetch_x509_svid().await.unwrap();
+        let context = svids.next().await.unwrap();
+        assert_eq!(context.svids, [svid(&[0x30, 0x00])]);
+
+        task.abort();
+        let _ = std::fs::remove_file(&path);
+    }
+}
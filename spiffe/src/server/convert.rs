@@ -0,0 +1,144 @@
+//! Conversions from the domain types into the Workload API messages.
+
+use std::collections::HashMap;
+
+use prost::bytes::Bytes;
+use rustls_pki_types::{CertificateDer, CertificateRevocationListDer};
+use spiffe_id::{SpiffeId, TrustDomain};
+use spiffe_proto::{JwtSvid as ProtoJwtSvid, WitSvid as ProtoWitSvid, X509Svid as ProtoX509Svid};
+
+use crate::{
+    JwtSvid, SpiffeError, WitSvid, X509Bundle, X509Svid,
+    client::{Audiences, JwtSvidRequest, WitSvidRequest, X509BundlesContext, X509SvidContext},
+};
+
+fn concat_certificates(certs: &[CertificateDer<'_>]) -> Bytes {
+    certs
+        .iter()
+        .map(AsRef::<[u8]>::as_ref)
+        .collect::<Vec<_>>()
+        .concat()
+        .into()
+}
+
+fn encode_hint(hint: Option<Box<str>>) -> String {
+    hint.map(Into::into).unwrap_or_default()
+}
+
+/// Keys the map by the SPIFFE ID of the trust domain, e.g. `spiffe://example.org`.
+pub(super) fn encode_bundles<T>(
+    bundles: HashMap<TrustDomain<'static>, T>,
+    encode: impl Fn(T) -> Bytes,
+) -> HashMap<String, Bytes> {
+    bundles
+        .into_iter()
+        .map(|(td, bundle)| (format!("spiffe://{td}"), encode(bundle)))
+        .collect()
+}
+
+fn encode_x509_bundle(bundle: X509Bundle) -> Bytes {
+    concat_certificates(bundle.bundle())
+}
+
+fn encode_crls(crls: Vec<CertificateRevocationListDer<'static>>) -> Vec<Bytes> {
+    crls.into_iter()
+        .map(|x| Bytes::copy_from_slice(x.as_ref()))
+        .collect()
+}
+
+fn parse_spiffe_id(spiffe_id: String) -> Result<Option<SpiffeId>, SpiffeError> {
+    if spiffe_id.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(SpiffeId::new(spiffe_id)?))
+    }
+}
+
+impl From<X509Svid> for ProtoX509Svid {
+    fn from(svid: X509Svid) -> Self {
+        Self {
+            spiffe_id: svid.spiffe_id.into(),
+            x509_svid: concat_certificates(&svid.svid),
+            x509_svid_key: svid.key.into_inner().into(),
+            bundle: encode_x509_bundle(svid.bundle),
+            hint: encode_hint(svid.hint),
+        }
+    }
+}
+
+impl From<JwtSvid> for ProtoJwtSvid {
+    fn from(svid: JwtSvid) -> Self {
+        Self {
+            spiffe_id: svid.spiffe_id.into(),
+            svid: svid.svid.into_inner(),
+            hint: encode_hint(svid.hint),
+        }
+    }
+}
+
+impl From<WitSvid> for ProtoWitSvid {
+    fn from(svid: WitSvid) -> Self {
+        Self {
+            spiffe_id: svid.spiffe_id.into(),
+            wit_svid: svid.svid.into_inner(),
+            wit_svid_key: svid.key.into_inner(),
+            hint: encode_hint(svid.hint),
+        }
+    }
+}
+
+impl From<X509SvidContext> for spiffe_proto::X509SvidResponse {
+    fn from(
+        X509SvidContext {
+            svids,
+            crl,
+            federated_bundles,
+        }: X509SvidContext,
+    ) -> Self {
+        Self {
+            svids: svids.into_iter().map(Into::into).collect(),
+            crl: encode_crls(crl),
+            federated_bundles: encode_bundles(federated_bundles, encode_x509_bundle),
+        }
+    }
+}
+
+impl From<X509BundlesContext> for spiffe_proto::X509BundlesResponse {
+    fn from(X509BundlesContext { crl, bundles }: X509BundlesContext) -> Self {
+        Self {
+            crl: encode_crls(crl),
+            bundles: encode_bundles(bundles, encode_x509_bundle),
+        }
+    }
+}
+
+impl TryFrom<spiffe_proto::JwtSvidRequest> for JwtSvidRequest {
+    type Error = SpiffeError;
+
+    fn try_from(
+        spiffe_proto::JwtSvidRequest {
+            audience,
+            spiffe_id,
+        }: spiffe_proto::JwtSvidRequest,
+    ) -> Result<Self, Self::Error> {
+        let request = JwtSvidRequest::new(Audiences::try_from(audience)?);
+
+        Ok(match parse_spiffe_id(spiffe_id)? {
+            Some(spiffe_id) => request.with_spiffe_id(spiffe_id),
+            None => request,
+        })
+    }
+}
+
+impl TryFrom<spiffe_proto::WitSvidRequest> for WitSvidRequest {
+    type Error = SpiffeError;
+
+    fn try_from(
+        spiffe_proto::WitSvidRequest { spiffe_id }: spiffe_proto::WitSvidRequest,
+    ) -> Result<Self, Self::Error> {
+        Ok(match parse_spiffe_id(spiffe_id)? {
+            Some(spiffe_id) => WitSvidRequest::new().with_spiffe_id(spiffe_id),
+            None => WitSvidRequest::new(),
+        })
+    }
+}
@@ -0,0 +1,247 @@
+//! Serving on Unix domain sockets with the credentials of the callers.
+
+use std::{
+    fs,
+    io::{self, Result as IoResult},
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use futures_core::Stream;
+use futures_util::stream::FuturesUnordered;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{
+        UnixListener, UnixStream,
+        unix::{gid_t, pid_t, uid_t},
+    },
+    task::{self, JoinHandle},
+};
+use tonic::transport::server::Connected;
+
+/// Process on the other end of a Unix domain socket, captured when the connection is accepted.
+///
+/// The process details are read from `/proc` and are `None` where it is not available, or
+/// when the process exited before the connection was accepted.
+///
+/// The credentials are those of the process at `connect` time, but `/proc` is read afterwards:
+/// if the peer exits and its pid is reused in between, [`cgroup`](Self::cgroup) and
+/// [`exe`](Self::exe) describe the new process. Do not rely on them alone to grant access.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PeerInfo {
+    pid: Option<pid_t>,
+    uid: uid_t,
+    gid: gid_t,
+    cgroup: Option<String>,
+    exe: Option<PathBuf>,
+}
+
+impl PeerInfo {
+    /// Reads the `SO_PEERCRED` credentials of `stream` and the details of the peer process.
+    ///
+    /// Reading `/proc` blocks, call it outside of the async tasks.
+    pub fn from_stream(stream: &UnixStream) -> IoResult<Self> {
+        let cred = stream.peer_cred()?;
+        let proc = cred.pid().map(|pid| PathBuf::from(format!("/proc/{pid}")));
+
+        Ok(Self {
+            pid: cred.pid(),
+            uid: cred.uid(),
+            gid: cred.gid(),
+            cgroup: proc
+                .as_ref()
+                .and_then(|x| fs::read_to_string(x.join("cgroup")).ok()),
+            exe: proc
+                .as_ref()
+                .and_then(|x| fs::read_link(x.join("exe")).ok()),
+        })
+    }
+
+    #[inline]
+    pub fn pid(&self) -> Option<pid_t> {
+        self.pid
+    }
+
+    #[inline]
+    pub fn uid(&self) -> uid_t {
+        self.uid
+    }
+
+    #[inline]
+    pub fn gid(&self) -> gid_t {
+        self.gid
+    }
+
+    /// Returns the content of `/proc/<pid>/cgroup`, one `hierarchy-ID:controllers:path` per line.
+    #[inline]
+    pub fn cgroup(&self) -> Option<&str> {
+        self.cgroup.as_deref()
+    }
+
+    /// Returns the path of the executable of the process.
+    #[inline]
+    pub fn exe(&self) -> Option<&Path> {
+        self.exe.as_deref()
+    }
+}
+
+/// Accepted connection, exposing its [`PeerInfo`] as the tonic connection info.
+#[derive(Debug)]
+pub struct PeerStream {
+    stream: UnixStream,
+    peer: PeerInfo,
+}
+
+impl PeerStream {
+    /// Wraps `stream`, reading its [`PeerInfo`], which blocks.
+    pub fn new(stream: UnixStream) -> IoResult<Self> {
+        let peer = PeerInfo::from_stream(&stream)?;
+
+        Ok(Self { stream, peer })
+    }
+
+    #[inline]
+    pub fn peer(&self) -> &PeerInfo {
+        &self.peer
+    }
+}
+
+impl Connected for PeerStream {
+    type ConnectInfo = PeerInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.peer.clone()
+    }
+}
+
+impl AsyncRead for PeerStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for PeerStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+}
+
+/// Connections accepted on a Unix domain socket, for `tonic::transport::Server::serve_with_incoming`.
+///
+/// The [`PeerInfo`] of the connections is read on the blocking thread pool of the runtime, they
+/// are yielded as the reads complete.
+#[derive(Debug)]
+pub struct UnixIncoming {
+    listener: UnixListener,
+    pending: FuturesUnordered<JoinHandle<IoResult<PeerStream>>>,
+}
+
+impl UnixIncoming {
+    /// Binds a new socket at `path`.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn bind(path: impl AsRef<Path>) -> IoResult<Self> {
+        UnixListener::bind(path).map(Self::new)
+    }
+
+    pub fn new(listener: UnixListener) -> Self {
+        Self {
+            listener,
+            pending: FuturesUnordered::new(),
+        }
+    }
+}
+
+impl Stream for UnixIncoming {
+    type Item = IoResult<PeerStream>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        // accepts until the listener is pending, which wakes the task on the next connection
+        while let Poll::Ready(accept) = this.listener.poll_accept(cx) {
+            let (stream, _) = accept?;
+            (this.pending).push(task::spawn_blocking(move || PeerStream::new(stream)));
+        }
+
+        match ready!(Pin::new(&mut this.pending).poll_next(cx)) {
+            Some(Ok(peer_stream)) => Poll::Ready(Some(peer_stream)),
+            Some(Err(e)) => Poll::Ready(Some(Err(io::Error::other(e)))),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_peer_info() {
+        let (a, b) = UnixStream::pair().unwrap();
+
+        for stream in [a, b] {
+            let stream = PeerStream::new(stream).unwrap();
+            let peer = stream.connect_info();
+            assert_eq!(peer.pid(), Some(std::process::id() as pid_t));
+            assert_eq!(peer.exe(), Some(env::current_exe().unwrap().as_path()));
+            assert_eq!(
+                peer.cgroup(),
+                fs::read_to_string("/proc/self/cgroup").ok().as_deref()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_incoming() {
+        let dir = env::temp_dir().join(format!("spiffe-incoming-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("api.sock");
+
+        let mut incoming = UnixIncoming::bind(&socket).unwrap();
+        let _a = UnixStream::connect(&socket).await.unwrap();
+        let _b = UnixStream::connect(&socket).await.unwrap();
+
+        for _ in 0..2 {
+            let stream = std::future::poll_fn(|cx| Pin::new(&mut incoming).poll_next(cx))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stream.peer().pid(), Some(std::process::id() as pid_t));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+//! Sources of SVIDs and bundles, modeled after the go-spiffe sources.
+//!
+//! Libraries should accept a source instead of a concrete Workload API stream, so they work with
+//! static values, files, the Workload API or test doubles alike.
+//!
+//! Implementations provided:
+//! - static values: [`X509Svid`], [`X509SvidContext`], [`X509BundlesContext`], [`X509Context`],
+//!   [`BundleSet`], JWKS maps and [`JwtSvid`]
+//! - the Workload API: [`WorkloadX509Source`] and [`WorkloadJwtSource`] (feature `transport`)
+//! - files written by `spiffe-helper`: [`X509FileSource`] (feature `pem`)
+
+#[cfg(feature = "pem")]
+mod file;
+#[cfg(feature = "transport")]
+mod workload;
+
+use std::{collections::HashMap, sync::Arc};
+
+use futures_core::future::BoxFuture;
+use spiffe_id::{SpiffeId, TrustDomain};
+
+#[cfg(feature = "pem")]
+pub use self::file::X509FileSource;
+#[cfg(feature = "transport")]
+pub use self::workload::{WorkloadJwtSource, WorkloadX509Source};
+#[cfg(feature = "jwt")]
+use crate::jwt::jwt_svid_has_audiences;
+use crate::{
+    JwtSvid, SourceError, X509Bundle, X509Svid,
+    client::{Audiences, BundleSet, X509BundlesContext, X509Context, X509SvidContext},
+};
+
+pub trait X509BundleSource {
+    /// Returns the X.509 bundle of `trust_domain`.
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError>;
+}
+
+pub trait X509SvidSource {
+    /// Returns the current X.509-SVID.
+    fn x509_svid(&self) -> Result<X509Svid, SourceError>;
+}
+
+pub trait JwtBundleSource {
+    /// Returns the JWT bundle of `trust_domain` as a JWKS document.
+    fn jwt_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<String, SourceError>;
+}
+
+pub trait JwtSvidSource {
+    /// Returns a JWT-SVID for `audiences`, optionally the one identified by `spiffe_id`.
+    fn jwt_svid<'a>(
+        &'a self,
+        audiences: &'a Audiences,
+        spiffe_id: Option<&'a SpiffeId>,
+    ) -> BoxFuture<'a, Result<JwtSvid, SourceError>>;
+}
+
+impl<T: X509BundleSource + ?Sized> X509BundleSource for Arc<T> {
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError> {
+        (**self).x509_bundle(trust_domain)
+    }
+}
+
+impl<T: X509SvidSource + ?Sized> X509SvidSource for Arc<T> {
+    fn x509_svid(&self) -> Result<X509Svid, SourceError> {
+        (**self).x509_svid()
+    }
+}
+
+impl<T: JwtBundleSource + ?Sized> JwtBundleSource for Arc<T> {
+    fn jwt_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<String, SourceError> {
+        (**self).jwt_bundle(trust_domain)
+    }
+}
+
+impl<T: JwtSvidSource + ?Sized> JwtSvidSource for Arc<T> {
+    fn jwt_svid<'a>(
+        &'a self,
+        audiences: &'a Audiences,
+        spiffe_id: Option<&'a SpiffeId>,
+    ) -> BoxFuture<'a, Result<JwtSvid, SourceError>> {
+        (**self).jwt_svid(audiences, spiffe_id)
+    }
+}
+
+impl X509BundleSource for BundleSet {
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError> {
+        self.get(trust_domain)
+            .cloned()
+            .ok_or_else(|| no_x509_bundle(trust_domain))
+    }
+}
+
+impl X509BundleSource for X509BundlesContext {
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError> {
+        self.bundles
+            .iter()
+            .find(|(td, _)| *td == trust_domain)
+            .map(|(_, bundle)| bundle.clone())
+            .ok_or_else(|| no_x509_bundle(trust_domain))
+    }
+}
+
+impl X509BundleSource for X509SvidContext {
+    /// The bundle of an SVID takes precedence over a federated bundle of the same trust domain,
+    /// as in [`X509SvidContext::bundle_set`].
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError> {
+        self.svids
+            .iter()
+            .find(|x| x.spiffe_id().trust_domain() == *trust_domain)
+            .map(X509Svid::bundle)
+            .or_else(|| {
+                self.federated_bundles
+                    .iter()
+                    .find(|(td, _)| *td == trust_domain)
+                    .map(|(_, bundle)| bundle)
+            })
+            .cloned()
+            .ok_or_else(|| no_x509_bundle(trust_domain))
+    }
+}
+
+impl X509BundleSource for X509Context {
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError> {
+        self.bundles
+            .iter()
+            .find(|(td, _)| *td == trust_domain)
+            .map(|(_, bundle)| bundle.clone())
+            .ok_or_else(|| no_x509_bundle(trust_domain))
+    }
+}
+
+impl X509SvidSource for X509Svid {
+    fn x509_svid(&self) -> Result<X509Svid, SourceError> {
+        Ok(self.clone())
+    }
+}
+
+impl X509SvidSource for X509SvidContext {
+    /// Returns the default SVID.
+    fn x509_svid(&self) -> Result<X509Svid, SourceError> {
+        self.default_svid().cloned().ok_or(SourceError::NoSvid)
+    }
+}
+
+impl X509SvidSource for X509Context {
+    /// Returns the default SVID.
+    fn x509_svid(&self) -> Result<X509Svid, SourceError> {
+        self.default_svid().cloned().ok_or(SourceError::NoSvid)
+    }
+}
+
+impl JwtBundleSource for HashMap<TrustDomain<'static>, String> {
+    fn jwt_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<String, SourceError> {
+        self.iter()
+            .find(|(td, _)| *td == trust_domain)
+            .map(|(_, bundle)| bundle.clone())
+            .ok_or_else(|| SourceError::NoJwtBundle(trust_domain.clone().into_owned()))
+    }
+}
+
+#[cfg(feature = "jwt")]
+impl JwtSvidSource for JwtSvid {
+    /// Returns the SVID if `spiffe_id` matches and its `aud` claim holds every audience of
+    /// `audiences`.
+    fn jwt_svid<'a>(
+        &'a self,
+        audiences: &'a Audiences,
+        spiffe_id: Option<&'a SpiffeId>,
+    ) -> BoxFuture<'a, Result<JwtSvid, SourceError>> {
+        let svid = match spiffe_id {
+            Some(x) if x != self.spiffe_id() => Err(SourceError::NoSvid),
+            _ if !jwt_svid_has_audiences(self.svid(), audiences.iter()).unwrap_or(false) => {
+                Err(SourceError::NoSvid)
+            }
+            _ => Ok(self.clone()),
+        };
+
+        Box::pin(async move { svid })
+    }
+}
+
+fn no_x509_bundle(trust_domain: &TrustDomain<'_>) -> SourceError {
+    SourceError::NoX509Bundle(trust_domain.clone().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::bytes::Bytes;
+
+    use super::*;
+
+    // smallest DER the certificate splitter accepts, the sources never parse it
+    const DUMMY_CERT: &[u8] = &[0x30, 0x00];
+
+    fn context() -> X509SvidContext {
+        let svid = spiffe_proto::X509Svid {
+            spiffe_id: "spiffe://example.org/workload".into(),
+            x509_svid: Bytes::from_static(DUMMY_CERT),
+            x509_svid_key: Bytes::from_static(b"key"),
+            bundle: Bytes::from_static(DUMMY_CERT),
+            hint: String::new(),
+        };
+
+        spiffe_proto::X509SvidResponse {
+            svids: vec![svid],
+            crl: vec![],
+            federated_bundles: HashMap::from([(
+                "spiffe://example.com".into(),
+                Bytes::from_static(&[0x30, 0x00, 0x30, 0x00]),
+            )]),
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_x509_context_source() {
+        let context = context();
+        let source: &dyn X509BundleSource = &context;
+
+        let org = TrustDomain::new("example.org").unwrap();
+        let com = TrustDomain::new("example.com").unwrap();
+        assert_eq!(source.x509_bundle(&org).unwrap().bundle().len(), 1);
+        assert_eq!(source.x509_bundle(&com).unwrap().bundle().len(), 2);
+        assert!(matches!(
+            source.x509_bundle(&TrustDomain::new("example.net").unwrap()),
+            Err(SourceError::NoX509Bundle(_))
+        ));
+
+        let svid = context.x509_svid().unwrap();
+        assert_eq!(
+            svid.spiffe_id(),
+            &SpiffeId::new("spiffe://example.org/workload").unwrap()
+        );
+
+        let bundles = context.bundle_set(&svid);
+        assert_eq!(
+            bundles.x509_bundle(&com).unwrap(),
+            context.x509_bundle(&com).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_jwt_bundle_source() {
+        let bundles = Arc::new(HashMap::from([(
+            TrustDomain::new("example.org").unwrap().into_owned(),
+            String::from(r#"{"keys":[]}"#),
+        )]));
+
+        assert_eq!(
+            bundles
+                .jwt_bundle(&TrustDomain::new("example.org").unwrap())
+                .unwrap(),
+            r#"{"keys":[]}"#
+        );
+        assert!(matches!(
+            bundles.jwt_bundle(&TrustDomain::new("example.com").unwrap()),
+            Err(SourceError::NoJwtBundle(_))
+        ));
+    }
+
+    #[cfg(feature = "jwt")]
+    #[tokio::test]
+    async fn test_jwt_svid_source() {
+        use base64ct::{Base64UrlUnpadded, Encoding};
+
+        use crate::Secret;
+
+        let claims = Base64UrlUnpadded::encode_string(
+            br#"{"sub":"spiffe://example.org/workload","aud":["a","b"]}"#,
+        );
+        let svid = JwtSvid {
+            spiffe_id: SpiffeId::new("spiffe://example.org/workload").unwrap(),
+            svid: Secret::new(format!("e30.{claims}.c2ln")),
+            hint: None,
+        };
+        let audiences = |x: &[&str]| Audiences::try_from_iter(x.iter().copied()).unwrap();
+
+        assert!(svid.jwt_svid(&audiences(&["a"]), None).await.is_ok());
+        assert!(
+            svid.jwt_svid(&audiences(&["b", "a"]), Some(svid.spiffe_id()))
+                .await
+                .is_ok()
+        );
+        assert!(matches!(
+            svid.jwt_svid(&audiences(&["a", "c"]), None).await,
+            Err(SourceError::NoSvid)
+        ));
+        let other = SpiffeId::new("spiffe://example.org/other").unwrap();
+        assert!(matches!(
+            svid.jwt_svid(&audiences(&["a"]), Some(&other)).await,
+            Err(SourceError::NoSvid)
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{PoisonError, RwLock},
+};
+
+use spiffe_id::TrustDomain;
+
+use super::{X509BundleSource, X509SvidSource};
+use crate::{PemError, SourceError, X509Bundle, X509Svid};
+
+/// X.509 source reading the PEM files written by `spiffe-helper`.
+///
+/// The files are read on creation and on every [`X509FileSource::reload`]. The bundle file is
+/// taken as the bundle of the SVID's trust domain.
+#[derive(Debug)]
+pub struct X509FileSource {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    bundle_path: PathBuf,
+    svid: RwLock<X509Svid>,
+}
+
+impl X509FileSource {
+    pub fn new(
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+        bundle_path: impl Into<PathBuf>,
+    ) -> Result<Self, PemError> {
+        let (cert_path, key_path, bundle_path) =
+            (cert_path.into(), key_path.into(), bundle_path.into());
+        let svid = load(&cert_path, &key_path, &bundle_path)?;
+
+        Ok(Self {
+            cert_path,
+            key_path,
+            bundle_path,
+            svid: RwLock::new(svid),
+        })
+    }
+
+    /// Reads the files again, the previous SVID is kept if they are invalid.
+    pub fn reload(&self) -> Result<(), PemError> {
+        let svid = load(&self.cert_path, &self.key_path, &self.bundle_path)?;
+        *self.svid.write().unwrap_or_else(PoisonError::into_inner) = svid;
+
+        Ok(())
+    }
+}
+
+impl X509SvidSource for X509FileSource {
+    fn x509_svid(&self) -> Result<X509Svid, SourceError> {
+        Ok(self
+            .svid
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone())
+    }
+}
+
+impl X509BundleSource for X509FileSource {
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError> {
+        let svid = self.svid.read().unwrap_or_else(PoisonError::into_inner);
+        if svid.spiffe_id().trust_domain() != *trust_domain {
+            return Err(SourceError::NoX509Bundle(trust_domain.clone().into_owned()));
+        }
+
+        Ok(svid.bundle().clone())
+    }
+}
+
+fn load(cert_path: &Path, key_path: &Path, bundle_path: &Path) -> Result<X509Svid, PemError> {
+    let svid = X509Svid::from_pem_files(cert_path, key_path)?;
+
+    Ok(svid.with_bundle(X509Bundle::from_pem_file(bundle_path)?))
+}
@@ -0,0 +1,190 @@
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use futures_core::{Stream, future::BoxFuture};
+use spiffe_id::{SpiffeId, TrustDomain};
+use tokio::{sync::watch, task::AbortHandle, time::sleep};
+use tonic::{Status, transport::Channel};
+
+use super::{JwtBundleSource, JwtSvidSource, X509BundleSource, X509SvidSource};
+use crate::{
+    JwtSvid, SourceError, X509Bundle, X509Svid,
+    client::{Audiences, SpiffeWorkloadApiClient, X509SvidContext, next},
+};
+
+/// Delay before a closed or failed Workload API stream is reopened.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+type SvidSelector = fn(&X509Svid) -> bool;
+
+/// X.509 source kept up to date by a Workload API stream.
+///
+/// The stream is watched by a background task, which reopens it after errors and is stopped
+/// when the source is dropped.
+#[derive(Debug)]
+pub struct WorkloadX509Source {
+    context: watch::Receiver<X509SvidContext>,
+    svid_selector: SvidSelector,
+    _task: AbortOnDrop,
+}
+
+impl WorkloadX509Source {
+    /// Creates the source once the first X.509 context is received.
+    ///
+    /// Must be called within a tokio runtime.
+    pub async fn new(client: SpiffeWorkloadApiClient<Channel>) -> Result<Self, SourceError> {
+        let (context, task) = watch_stream(move || {
+            let client = client.clone();
+            async move { client.fetch_x509_svid().await }
+        })
+        .await?;
+
+        Ok(Self {
+            context,
+            svid_selector: |_| true,
+            _task: task,
+        })
+    }
+
+    /// Sets which SVID [`X509SvidSource::x509_svid`] returns, the first one matching.
+    ///
+    /// Default: the default SVID
+    #[must_use]
+    pub fn svid_selector(mut self, selector: SvidSelector) -> Self {
+        self.svid_selector = selector;
+        self
+    }
+
+    /// Returns the latest X.509 context.
+    pub fn context(&self) -> X509SvidContext {
+        self.context.borrow().clone()
+    }
+
+    /// Waits for the next X.509 context update.
+    pub async fn changed(&mut self) -> Result<(), SourceError> {
+        self.context
+            .changed()
+            .await
+            .map_err(|_| SourceError::Closed)
+    }
+}
+
+impl X509SvidSource for WorkloadX509Source {
+    fn x509_svid(&self) -> Result<X509Svid, SourceError> {
+        self.context
+            .borrow()
+            .svids
+            .iter()
+            .find(|&x| (self.svid_selector)(x))
+            .cloned()
+            .ok_or(SourceError::NoSvid)
+    }
+}
+
+impl X509BundleSource for WorkloadX509Source {
+    fn x509_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<X509Bundle, SourceError> {
+        self.context.borrow().x509_bundle(trust_domain)
+    }
+}
+
+/// JWT source, bundles are kept up to date by a Workload API stream and JWT-SVIDs are fetched on
+/// demand.
+#[derive(Debug)]
+pub struct WorkloadJwtSource {
+    client: SpiffeWorkloadApiClient<Channel>,
+    bundles: watch::Receiver<HashMap<TrustDomain<'static>, String>>,
+    _task: AbortOnDrop,
+}
+
+impl WorkloadJwtSource {
+    /// Creates the source once the first JWT bundles are received.
+    ///
+    /// Must be called within a tokio runtime.
+    pub async fn new(client: SpiffeWorkloadApiClient<Channel>) -> Result<Self, SourceError> {
+        let (bundles, task) = watch_stream({
+            let client = client.clone();
+            move || {
+                let client = client.clone();
+                async move { client.fetch_jwt_bundles().await }
+            }
+        })
+        .await?;
+
+        Ok(Self {
+            client,
+            bundles,
+            _task: task,
+        })
+    }
+
+    /// Returns the latest JWT bundles.
+    pub fn bundles(&self) -> HashMap<TrustDomain<'static>, String> {
+        self.bundles.borrow().clone()
+    }
+}
+
+impl JwtBundleSource for WorkloadJwtSource {
+    fn jwt_bundle(&self, trust_domain: &TrustDomain<'_>) -> Result<String, SourceError> {
+        self.bundles.borrow().jwt_bundle(trust_domain)
+    }
+}
+
+impl JwtSvidSource for WorkloadJwtSource {
+    fn jwt_svid<'a>(
+        &'a self,
+        audiences: &'a Audiences,
+        spiffe_id: Option<&'a SpiffeId>,
+    ) -> BoxFuture<'a, Result<JwtSvid, SourceError>> {
+        Box::pin(async move {
+            self.client
+                .fetch_jwt_svid(audiences, spiffe_id)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or(SourceError::NoSvid)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Opens a stream and waits for its first item, later items are published by a spawned task.
+async fn watch_stream<T, S, F, Fut>(
+    open: F,
+) -> Result<(watch::Receiver<T>, AbortOnDrop), SourceError>
+where
+    T: Send + Sync + 'static,
+    S: Stream<Item = T> + Unpin + Send + 'static,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<S, Status>> + Send,
+{
+    let mut stream = open().await?;
+    let first = next(&mut stream).await.ok_or(SourceError::Closed)?;
+    let (tx, rx) = watch::channel(first);
+
+    let task = tokio::spawn(async move {
+        loop {
+            while let Some(x) = next(&mut stream).await {
+                if tx.send(x).is_err() {
+                    return;
+                }
+            }
+
+            // the stream ended or failed, keep serving the last value until it is reopened
+            stream = loop {
+                sleep(RETRY_INTERVAL).await;
+                if let Ok(x) = open().await {
+                    break x;
+                }
+            };
+        }
+    });
+
+    Ok((rx, AbortOnDrop(task.abort_handle())))
+}
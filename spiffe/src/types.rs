@@ -1,17 +1,20 @@
-use std::fmt::{Debug, Formatter, Result as FmtResult};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+#[cfg(feature = "std")]
+use std::time::SystemTime;
 
-use prost::bytes::Bytes;
 use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use spiffe_id::SpiffeId;
-use spiffe_proto::{JwtSvid as ProtoJwtSvid, WitSvid as ProtoWitSvid, X509Svid as ProtoX509Svid};
 
-use super::{InvalidDerError, SpiffeError, split_certificates};
+#[cfg(feature = "std")]
+use super::der::not_after_from_certificate;
+use super::{InvalidDerError, Secret, SpiffeError, split_certificates};
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct JwtSvid {
-    spiffe_id: SpiffeId,
-    svid: Box<str>,
-    hint: Option<Box<str>>,
+    pub(crate) spiffe_id: SpiffeId,
+    pub(crate) svid: Secret<String>,
+    pub(crate) hint: Option<Box<str>>,
 }
 
 impl JwtSvid {
@@ -22,7 +25,7 @@ impl JwtSvid {
 
     #[inline]
     pub fn svid(&self) -> &str {
-        &self.svid
+        self.svid.expose_secret()
     }
 
     #[inline]
@@ -31,8 +34,8 @@ impl JwtSvid {
     }
 
     #[inline]
-    pub fn into_parts(self) -> (SpiffeId, String) {
-        (self.spiffe_id, self.svid.into())
+    pub fn into_parts(self) -> (SpiffeId, Secret<String>) {
+        (self.spiffe_id, self.svid)
     }
 
     #[cfg(feature = "unchecked-api")]
@@ -40,7 +43,7 @@ impl JwtSvid {
     pub fn new_unchecked(spiffe_id: SpiffeId, svid: Box<str>, hint: Option<Box<str>>) -> Self {
         Self {
             spiffe_id,
-            svid,
+            svid: Secret::new(svid.into()),
             hint,
         }
     }
@@ -56,34 +59,12 @@ impl Debug for JwtSvid {
     }
 }
 
-impl TryFrom<ProtoJwtSvid> for JwtSvid {
-    type Error = SpiffeError;
-
-    fn try_from(
-        ProtoJwtSvid {
-            spiffe_id,
-            svid,
-            hint,
-        }: ProtoJwtSvid,
-    ) -> Result<Self, Self::Error> {
-        Ok(Self {
-            spiffe_id: SpiffeId::new(spiffe_id)?,
-            svid: svid.into(),
-            hint: if hint.is_empty() {
-                None
-            } else {
-                Some(hint.into())
-            },
-        })
-    }
-}
-
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct WitSvid {
-    spiffe_id: SpiffeId,
-    svid: Box<str>,
-    key: Box<str>,
-    hint: Option<Box<str>>,
+    pub(crate) spiffe_id: SpiffeId,
+    pub(crate) svid: Secret<String>,
+    pub(crate) key: Secret<String>,
+    pub(crate) hint: Option<Box<str>>,
 }
 
 impl WitSvid {
@@ -94,12 +75,12 @@ impl WitSvid {
 
     #[inline]
     pub fn svid(&self) -> &str {
-        &self.svid
+        self.svid.expose_secret()
     }
 
     #[inline]
     pub fn key(&self) -> &str {
-        &self.key
+        self.key.expose_secret()
     }
 
     #[inline]
@@ -108,8 +89,8 @@ impl WitSvid {
     }
 
     #[inline]
-    pub fn into_parts(self) -> (SpiffeId, String, String) {
-        (self.spiffe_id, self.svid.into(), self.key.into())
+    pub fn into_parts(self) -> (SpiffeId, Secret<String>, Secret<String>) {
+        (self.spiffe_id, self.svid, self.key)
     }
 
     #[cfg(feature = "unchecked-api")]
@@ -122,8 +103,8 @@ impl WitSvid {
     ) -> Self {
         Self {
             spiffe_id,
-            svid,
-            key,
+            svid: Secret::new(svid.into()),
+            key: Secret::new(key.into()),
             hint,
         }
     }
@@ -140,36 +121,25 @@ impl Debug for WitSvid {
     }
 }
 
-impl TryFrom<ProtoWitSvid> for WitSvid {
-    type Error = SpiffeError;
-
-    fn try_from(
-        ProtoWitSvid {
-            spiffe_id,
-            wit_svid,
-            wit_svid_key,
-            hint,
-        }: ProtoWitSvid,
-    ) -> Result<Self, Self::Error> {
-        Ok(Self {
-            spiffe_id: SpiffeId::new(spiffe_id)?,
-            svid: wit_svid.into(),
-            key: wit_svid_key.into(),
-            hint: if hint.is_empty() {
-                None
-            } else {
-                Some(hint.into())
-            },
-        })
-    }
-}
-
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct X509Bundle {
-    bundle: Box<[CertificateDer<'static>]>,
+    pub(crate) bundle: Box<[CertificateDer<'static>]>,
 }
 
 impl X509Bundle {
+    /// Parses a bundle from concatenated DER-encoded certificates, the Workload API format.
+    pub fn from_der(der: &[u8]) -> Result<Self, SpiffeError> {
+        if der.is_empty() {
+            return Err(SpiffeError::InvalidDer(InvalidDerError));
+        }
+
+        Ok(Self {
+            bundle: split_certificates(der)
+                .map(|x| x.map(CertificateDer::into_owned))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
     #[inline]
     pub fn bundle(&self) -> &[CertificateDer<'static>] {
         &self.bundle
@@ -185,31 +155,23 @@ impl X509Bundle {
     pub fn new_unchecked(bundle: Box<[CertificateDer<'static>]>) -> Self {
         Self { bundle }
     }
-}
-
-impl TryFrom<Bytes> for X509Bundle {
-    type Error = SpiffeError;
 
-    fn try_from(value: Bytes) -> Result<Self, Self::Error> {
-        if value.is_empty() {
-            return Err(SpiffeError::InvalidDer(InvalidDerError));
+    #[cfg(feature = "pem")]
+    pub(crate) fn from_certificates(bundle: Vec<CertificateDer<'static>>) -> Self {
+        Self {
+            bundle: bundle.into(),
         }
-
-        Ok(Self {
-            bundle: split_certificates(&value)
-                .map(|x| x.map(CertificateDer::into_owned))
-                .collect::<Result<_, _>>()?,
-        })
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct X509Svid {
-    spiffe_id: SpiffeId,
-    svid: Box<[CertificateDer<'static>]>,
-    key: PrivatePkcs8KeyDer<'static>,
-    bundle: X509Bundle,
-    hint: Option<Box<str>>,
+    pub(crate) spiffe_id: SpiffeId,
+    pub(crate) svid: Box<[CertificateDer<'static>]>,
+    // PKCS#8 DER
+    pub(crate) key: Secret<Vec<u8>>,
+    pub(crate) bundle: X509Bundle,
+    pub(crate) hint: Option<Box<str>>,
 }
 
 impl X509Svid {
@@ -225,7 +187,9 @@ impl X509Svid {
 
     #[inline]
     pub fn key(&self) -> PrivateKeyDer<'_> {
-        PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(self.key.secret_pkcs8_der()))
+        PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+            self.key.expose_secret().as_slice(),
+        ))
     }
 
     #[inline]
@@ -238,6 +202,16 @@ impl X509Svid {
         self.hint.as_deref()
     }
 
+    /// Returns the `notAfter` time of the leaf certificate, `None` if it cannot be parsed.
+    #[cfg(feature = "std")]
+    pub fn expiry(&self) -> Option<SystemTime> {
+        not_after_from_certificate(self.svid.first()?)
+    }
+
+    /// Splits the SVID into its parts.
+    ///
+    /// The private key is moved out without a copy, but is no longer wiped on drop as
+    /// [`PrivateKeyDer`] does not support it.
     pub fn into_parts(
         self,
     ) -> (
@@ -249,70 +223,50 @@ impl X509Svid {
         (
             self.spiffe_id,
             self.svid.into(),
-            PrivateKeyDer::Pkcs8(self.key),
+            PrivateKeyDer::Pkcs8(self.key.into_inner().into()),
             self.bundle,
         )
     }
 
-    #[cfg(feature = "unchecked-api")]
-    #[inline]
-    pub fn new_unchecked(
+    /// Replaces the trust bundle of the SVID.
+    #[must_use]
+    pub fn with_bundle(mut self, bundle: X509Bundle) -> Self {
+        self.bundle = bundle;
+        self
+    }
+
+    #[cfg(feature = "pem")]
+    pub(crate) fn from_parts(
         spiffe_id: SpiffeId,
         svid: Box<[CertificateDer<'static>]>,
-        key: PrivatePkcs8KeyDer<'static>,
-        bundle: X509Bundle,
-        hint: Option<Box<str>>,
+        key: Secret<Vec<u8>>,
     ) -> Self {
         Self {
             spiffe_id,
             svid,
             key,
-            bundle,
-            hint,
+            bundle: X509Bundle {
+                bundle: Box::new([]),
+            },
+            hint: None,
         }
     }
-}
 
-impl Clone for X509Svid {
-    fn clone(&self) -> Self {
+    #[cfg(feature = "unchecked-api")]
+    #[inline]
+    pub fn new_unchecked(
+        spiffe_id: SpiffeId,
+        svid: Box<[CertificateDer<'static>]>,
+        key: PrivatePkcs8KeyDer<'static>,
+        bundle: X509Bundle,
+        hint: Option<Box<str>>,
+    ) -> Self {
         Self {
-            spiffe_id: self.spiffe_id.clone(),
-            svid: self.svid.clone(),
-            key: self.key.clone_key(),
-            bundle: self.bundle.clone(),
-            hint: self.hint.clone(),
-        }
-    }
-}
-
-impl TryFrom<ProtoX509Svid> for X509Svid {
-    type Error = SpiffeError;
-
-    fn try_from(
-        ProtoX509Svid {
             spiffe_id,
-            x509_svid,
-            x509_svid_key,
+            svid,
+            key: Secret::new(key.secret_pkcs8_der().to_vec()),
             bundle,
             hint,
-        }: ProtoX509Svid,
-    ) -> Result<Self, Self::Error> {
-        if x509_svid.is_empty() || x509_svid_key.is_empty() || bundle.is_empty() {
-            return Err(SpiffeError::InvalidDer(InvalidDerError));
         }
-
-        Ok(Self {
-            spiffe_id: SpiffeId::new(spiffe_id)?,
-            svid: split_certificates(&x509_svid)
-                .map(|x| x.map(CertificateDer::into_owned))
-                .collect::<Result<_, _>>()?,
-            key: x509_svid_key.to_vec().into(),
-            bundle: bundle.try_into()?,
-            hint: if hint.is_empty() {
-                None
-            } else {
-                Some(hint.into())
-            },
-        })
     }
 }
@@ -0,0 +1,519 @@
+//! WIT-SVID validation and Workload Proof Token (WPT) support.
+//!
+//! A WIT-SVID is a Workload Identity Token whose `cnf` claim binds it to a key pair held by the
+//! workload. On each HTTP request the workload sends the WIT along with a short-lived WPT signed
+//! by that key, proving possession of the key and binding the proof to the request target.
+//!
+//! # References
+//!
+//! - [WIMSE Workload-to-Workload Authentication](https://datatracker.ietf.org/doc/draft-ietf-wimse-s2s-protocol/)
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Map, Value as JsonValue, json};
+use spiffe_id::{SpiffeId, TrustDomain};
+
+use crate::{
+    TokenError, WitSvid,
+    jose::{
+        CompactJws, Jwk, JwkSet, SigningKey, check_audience, check_validity, encode_compact,
+        numeric_date, random_id, subject, token_hash,
+    },
+};
+
+/// HTTP header carrying the WIT.
+pub const WIT_HEADER: &str = "Workload-Identity-Token";
+/// HTTP header carrying the WPT.
+pub const WPT_HEADER: &str = "Workload-Proof-Token";
+
+const WIT_TYPE: &str = "wit+jwt";
+const WPT_TYPE: &str = "wpt+jwt";
+
+/// Default lifetime of a WPT created by [`WptBuilder`].
+const DEFAULT_WPT_LIFETIME: Duration = Duration::from_secs(30);
+
+/// WIT bundles keyed by trust domain.
+pub type WitBundles = HashMap<TrustDomain<'static>, JwkSet>;
+
+/// Parses the JWKS documents yielded by [`WitBundlesStream`](crate::client::WitBundlesStream).
+pub fn parse_wit_bundles(
+    bundles: &HashMap<TrustDomain<'static>, String>,
+) -> Result<WitBundles, TokenError> {
+    bundles
+        .iter()
+        .map(|(td, jwks)| Ok((td.clone(), JwkSet::from_json(jwks)?)))
+        .collect()
+}
+
+/// A parsed Workload Identity Token.
+#[derive(Clone)]
+pub struct Wit {
+    token: Box<str>,
+    spiffe_id: SpiffeId,
+    expiry: SystemTime,
+    confirmation_key: Jwk,
+    claims: Map<String, JsonValue>,
+}
+
+impl Wit {
+    /// Parses a WIT without verifying its signature or validity period.
+    ///
+    /// Only use this on tokens obtained from a trusted source, e.g. the Workload API.
+    pub fn parse_unverified(token: &str) -> Result<Self, TokenError> {
+        let jws = CompactJws::decode(token)?;
+        check_type(&jws, WIT_TYPE)?;
+
+        Self::from_jws(token, jws)
+    }
+
+    /// Validates a WIT against the bundle of the trust domain of its subject.
+    #[inline]
+    pub fn validate(token: &str, bundles: &WitBundles) -> Result<Self, TokenError> {
+        Self::validate_at(token, bundles, SystemTime::now())
+    }
+
+    /// Validates a WIT against the bundle of the trust domain of its subject, at time `now`.
+    pub fn validate_at(
+        token: &str,
+        bundles: &WitBundles,
+        now: SystemTime,
+    ) -> Result<Self, TokenError> {
+        let jws = CompactJws::decode(token)?;
+        check_type(&jws, WIT_TYPE)?;
+
+        let key_id = jws.header_str("kid").ok_or(TokenError::UnknownKey)?;
+        let trust_domain = subject(&jws.claims)?.trust_domain().into_owned();
+        let key = bundles
+            .get(&trust_domain)
+            .ok_or(TokenError::UnknownTrustDomain(trust_domain))?
+            .find(key_id)
+            .ok_or(TokenError::UnknownKey)?;
+
+        jws.verify(key)?;
+        check_validity(&jws.claims, now)?;
+
+        Self::from_jws(token, jws)
+    }
+
+    fn from_jws(token: &str, jws: CompactJws<'_>) -> Result<Self, TokenError> {
+        let claims = jws.claims;
+
+        let spiffe_id = subject(&claims)?;
+
+        let expiry = numeric_date(&claims, "exp")?.ok_or(TokenError::MissingClaim("exp"))?;
+
+        let confirmation_key = claims
+            .get("cnf")
+            .ok_or(TokenError::MissingClaim("cnf"))?
+            .get("jwk")
+            .ok_or(TokenError::InvalidClaim("cnf"))
+            .and_then(|jwk| Jwk::from_value(jwk).map_err(|_| TokenError::InvalidClaim("cnf")))?;
+
+        Ok(Self {
+            token: token.into(),
+            spiffe_id,
+            expiry,
+            confirmation_key,
+            claims,
+        })
+    }
+
+    #[inline]
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    #[inline]
+    pub fn spiffe_id(&self) -> &SpiffeId {
+        &self.spiffe_id
+    }
+
+    #[inline]
+    pub fn expiry(&self) -> SystemTime {
+        self.expiry
+    }
+
+    /// The `iss` claim.
+    #[inline]
+    pub fn issuer(&self) -> Option<&str> {
+        self.claims.get("iss").and_then(JsonValue::as_str)
+    }
+
+    /// The `jti` claim.
+    #[inline]
+    pub fn jti(&self) -> Option<&str> {
+        self.claims.get("jti").and_then(JsonValue::as_str)
+    }
+
+    /// The key the workload proves possession of, from the `cnf` claim.
+    #[inline]
+    pub fn confirmation_key(&self) -> &Jwk {
+        &self.confirmation_key
+    }
+
+    /// All claims of the token.
+    #[inline]
+    pub fn claims(&self) -> &Map<String, JsonValue> {
+        &self.claims
+    }
+
+    /// Verifies a WPT presented along with this WIT for a request to `audience`.
+    #[inline]
+    pub fn verify_wpt(&self, wpt: &str, audience: &str) -> Result<Wpt, TokenError> {
+        self.verify_wpt_at(wpt, audience, SystemTime::now())
+    }
+
+    /// Verifies a WPT presented along with this WIT for a request to `audience`, at time `now`.
+    ///
+    /// The caller is responsible for replay detection based on [`Wpt::jti`].
+    pub fn verify_wpt_at(
+        &self,
+        wpt: &str,
+        audience: &str,
+        now: SystemTime,
+    ) -> Result<Wpt, TokenError> {
+        let jws = CompactJws::decode(wpt)?;
+        check_type(&jws, WPT_TYPE)?;
+        jws.verify(&self.confirmation_key)?;
+
+        let claims = jws.claims;
+        check_validity(&claims, now)?;
+
+        let expiry = numeric_date(&claims, "exp")?.ok_or(TokenError::MissingClaim("exp"))?;
+
+        check_audience(&claims, audience)?;
+
+        match claims.get("wth") {
+            Some(JsonValue::String(wth)) if *wth == token_hash(&self.token) => (),
+            Some(_) => return Err(TokenError::InvalidClaim("wth")),
+            None => return Err(TokenError::MissingClaim("wth")),
+        }
+
+        match claims.get("jti") {
+            Some(JsonValue::String(_)) => (),
+            Some(_) => return Err(TokenError::InvalidClaim("jti")),
+            None => return Err(TokenError::MissingClaim("jti")),
+        }
+
+        Ok(Wpt { expiry, claims })
+    }
+}
+
+impl Debug for Wit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Wit")
+            .field("spiffe_id", &self.spiffe_id)
+            .field("expiry", &self.expiry)
+            .field("confirmation_key", &self.confirmation_key)
+            .field("token", &"[secret elided]")
+            .finish()
+    }
+}
+
+/// A verified Workload Proof Token.
+#[derive(Clone, Debug)]
+pub struct Wpt {
+    expiry: SystemTime,
+    claims: Map<String, JsonValue>,
+}
+
+impl Wpt {
+    #[inline]
+    pub fn expiry(&self) -> SystemTime {
+        self.expiry
+    }
+
+    /// The `jti` claim, to be used for replay detection.
+    #[inline]
+    pub fn jti(&self) -> &str {
+        self.claims
+            .get("jti")
+            .and_then(JsonValue::as_str)
+            .expect("checked during verification")
+    }
+
+    /// The `ath` claim, hash of the access token the request carries.
+    #[inline]
+    pub fn access_token_hash(&self) -> Option<&str> {
+        self.claims.get("ath").and_then(JsonValue::as_str)
+    }
+
+    /// Whether the proof is bound to `access_token` via the `ath` claim.
+    pub fn is_bound_to_access_token(&self, access_token: &str) -> bool {
+        self.access_token_hash() == Some(token_hash(access_token).as_str())
+    }
+
+    /// All claims of the token.
+    #[inline]
+    pub fn claims(&self) -> &Map<String, JsonValue> {
+        &self.claims
+    }
+}
+
+/// Builder of Workload Proof Tokens, signed with the private key of a [`WitSvid`].
+#[derive(Clone, Debug)]
+pub struct WptBuilder<'a> {
+    audience: &'a str,
+    lifetime: Duration,
+    issued_at: Option<SystemTime>,
+    jti: Option<String>,
+    access_token: Option<&'a str>,
+}
+
+impl<'a> WptBuilder<'a> {
+    /// Creates a builder for a WPT bound to `audience`, the HTTP target URI of the request
+    /// without query and fragment.
+    pub fn new(audience: &'a str) -> Self {
+        Self {
+            audience,
+            lifetime: DEFAULT_WPT_LIFETIME,
+            issued_at: None,
+            jti: None,
+            access_token: None,
+        }
+    }
+
+    /// Sets the lifetime of the token.
+    ///
+    /// Default: `30s`
+    #[must_use]
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Sets the time the token is created at.
+    ///
+    /// Default: current time
+    #[must_use]
+    pub fn issued_at(mut self, issued_at: SystemTime) -> Self {
+        self.issued_at = Some(issued_at);
+        self
+    }
+
+    /// Sets the `jti` claim.
+    ///
+    /// Default: random 128-bit value
+    #[must_use]
+    pub fn jti(mut self, jti: impl Into<String>) -> Self {
+        self.jti = Some(jti.into());
+        self
+    }
+
+    /// Binds the proof to the access token sent with the request, via the `ath` claim.
+    #[must_use]
+    pub fn access_token(mut self, access_token: &'a str) -> Self {
+        self.access_token = Some(access_token);
+        self
+    }
+
+    /// Signs the token with the private key of `svid`.
+    pub fn sign(self, svid: &WitSvid) -> Result<String, TokenError> {
+        let wit = svid.wit()?;
+        let key = svid.signing_key()?;
+
+        if !key.public_key().same_key(wit.confirmation_key()) {
+            return Err(TokenError::InvalidKey);
+        }
+
+        let issued_at = self.issued_at.unwrap_or_else(SystemTime::now);
+        let expiry = issued_at
+            .checked_add(self.lifetime)
+            .ok_or(TokenError::InvalidClaim("exp"))?;
+
+        let mut claims = json!({
+            "aud": self.audience,
+            "exp": unix_seconds(expiry),
+            "jti": self.jti.unwrap_or_else(random_id),
+            "wth": token_hash(svid.svid()),
+        });
+        if let Some(access_token) = self.access_token {
+            claims["ath"] = JsonValue::String(token_hash(access_token));
+        }
+
+        Ok(encode_compact(WPT_TYPE, &claims, &key))
+    }
+}
+
+impl WitSvid {
+    /// Parses the WIT of this SVID, without verifying it as it comes from the Workload API.
+    pub fn wit(&self) -> Result<Wit, TokenError> {
+        Wit::parse_unverified(self.svid())
+    }
+
+    /// Parses the private key of this SVID.
+    pub fn signing_key(&self) -> Result<SigningKey, TokenError> {
+        SigningKey::from_jwk(self.key())
+    }
+}
+
+fn check_type(jws: &CompactJws<'_>, expected: &str) -> Result<(), TokenError> {
+    // RFC 7515 4.1.9, the `application/` prefix may be omitted and the value is case-insensitive
+    let typ = jws.header_str("typ").ok_or(TokenError::Type)?;
+    let typ = typ
+        .get(..12)
+        .filter(|x| x.eq_ignore_ascii_case("application/"))
+        .map_or(typ, |_| &typ[12..]);
+
+    if typ.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(TokenError::Type)
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use spiffe_proto::WitSvid as ProtoWitSvid;
+
+    use super::*;
+    use crate::jose::Algorithm;
+
+    const TD: TrustDomain = TrustDomain::const_new("example.org");
+
+    fn issue(authority: &SigningKey, workload: &SigningKey, now: SystemTime) -> WitSvid {
+        let claims = json!({
+            "sub": "spiffe://example.org/workload",
+            "exp": unix_seconds(now + Duration::from_secs(3600)),
+            "iat": unix_seconds(now),
+            "jti": "wit-1",
+            "cnf": { "jwk": workload.public_key().to_value() },
+        });
+
+        WitSvid::try_from(ProtoWitSvid {
+            spiffe_id: "spiffe://example.org/workload".into(),
+            wit_svid: encode_compact(WIT_TYPE, &claims, authority),
+            wit_svid_key: workload.to_jwk().into_inner(),
+            hint: String::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_wit() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let authority = SigningKey::generate(Algorithm::ES256)
+            .unwrap()
+            .with_key_id("authority");
+        let workload = SigningKey::generate(Algorithm::EdDSA).unwrap();
+        let svid = issue(&authority, &workload, now);
+        let bundles = parse_wit_bundles(&HashMap::from([(
+            TD,
+            JwkSet::new(vec![authority.public_key().clone()])
+                .to_value()
+                .to_string(),
+        )]))
+        .unwrap();
+
+        let wit = Wit::validate_at(svid.svid(), &bundles, now).unwrap();
+        assert_eq!(wit.spiffe_id(), svid.spiffe_id());
+        assert_eq!(wit.jti(), Some("wit-1"));
+
+        assert!(matches!(
+            Wit::validate_at(svid.svid(), &bundles, now + Duration::from_secs(3600)),
+            Err(TokenError::Expired)
+        ));
+        assert!(matches!(
+            Wit::validate_at(svid.svid(), &WitBundles::new(), now),
+            Err(TokenError::UnknownTrustDomain(_))
+        ));
+
+        let other = SigningKey::generate(Algorithm::ES256)
+            .unwrap()
+            .with_key_id("authority");
+        let other_bundles = HashMap::from([(TD, JwkSet::new(vec![other.public_key().clone()]))]);
+        assert!(matches!(
+            Wit::validate_at(svid.svid(), &other_bundles, now),
+            Err(TokenError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_wpt() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let authority = SigningKey::generate(Algorithm::ES256)
+            .unwrap()
+            .with_key_id("authority");
+        let workload = SigningKey::generate(Algorithm::EdDSA).unwrap();
+        let svid = issue(&authority, &workload, now);
+        let bundles = HashMap::from([(TD, JwkSet::new(vec![authority.public_key().clone()]))]);
+        let wit = Wit::validate_at(svid.svid(), &bundles, now).unwrap();
+        let audience = "https://service.example.org/api";
+
+        let wpt = WptBuilder::new(audience)
+            .issued_at(now)
+            .access_token("access-token")
+            .sign(&svid)
+            .unwrap();
+
+        let proof = wit.verify_wpt_at(&wpt, audience, now).unwrap();
+        assert!(!proof.jti().is_empty());
+        assert!(proof.is_bound_to_access_token("access-token"));
+        assert!(!proof.is_bound_to_access_token("other-token"));
+
+        assert!(matches!(
+            wit.verify_wpt_at(&wpt, "https://other.example.org/api", now),
+            Err(TokenError::Audience)
+        ));
+        assert!(matches!(
+            wit.verify_wpt_at(&wpt, audience, now + Duration::from_secs(30)),
+            Err(TokenError::Expired)
+        ));
+        assert!(matches!(
+            wit.verify_wpt_at(svid.svid(), audience, now),
+            Err(TokenError::Type)
+        ));
+    }
+
+    #[test]
+    fn test_wpt_bound_to_wit() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let authority = SigningKey::generate(Algorithm::ES256).unwrap();
+        let workload = SigningKey::generate(Algorithm::EdDSA).unwrap();
+        let svid = issue(&authority, &workload, now);
+        let audience = "https://service.example.org/api";
+        let wpt = WptBuilder::new(audience)
+            .issued_at(now)
+            .sign(&svid)
+            .unwrap();
+
+        // a WIT for the same key issued separately must not accept the proof
+        let other_authority = SigningKey::generate(Algorithm::ES256).unwrap();
+        let other = issue(&other_authority, &workload, now).wit().unwrap();
+        assert!(matches!(
+            other.verify_wpt_at(&wpt, audience, now),
+            Err(TokenError::InvalidClaim("wth"))
+        ));
+
+        // a proof signed by another key must be rejected
+        let stranger = WitSvid::try_from(ProtoWitSvid {
+            spiffe_id: "spiffe://example.org/workload".into(),
+            wit_svid: svid.svid().into(),
+            wit_svid_key: SigningKey::generate(Algorithm::EdDSA)
+                .unwrap()
+                .to_jwk()
+                .into_inner(),
+            hint: String::new(),
+        })
+        .unwrap();
+        assert!(matches!(
+            WptBuilder::new(audience).sign(&stranger),
+            Err(TokenError::InvalidKey)
+        ));
+
+        assert!(matches!(
+            WptBuilder::new(audience)
+                .lifetime(Duration::MAX)
+                .sign(&svid),
+            Err(TokenError::InvalidClaim("exp"))
+        ));
+    }
+}